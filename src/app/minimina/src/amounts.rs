@@ -11,10 +11,9 @@
 //! To avoid adding a new crate dependency the grammars are parsed by hand rather
 //! than with the `regex` crate.
 //!
-//! These functions have no consumer in the `minimina` binary yet (balances
-//! currently enter minimina only via a verbatim `--genesis-ledger` JSON file);
-//! they are exposed through the library target so their doc examples run as
-//! doctests and a real caller can adopt them later.
+//! The parsers back the `--ledger-spec` genesis ledger generator, the timing of
+//! generated accounts and the topology's `snark_worker_fee`. They are exposed
+//! through the library target so their doc examples run as doctests.
 
 use std::fmt;
 
@@ -0,0 +1,313 @@
+//! # Backend Module
+//!
+//! Defines the `Backend` trait, the common interface of the execution backends:
+//! - `DockerManager`: services run as docker compose containers.
+//! - `NativeManager`: services run as local processes tracked in `processes.json`.
+//!
+//! Commands are written once against this trait, and the backend for a run is
+//! picked from the `--mode` flag by [`for_mode`].
+
+use crate::{
+    cli::ExecutionMode, docker::manager::ContainerState, docker::manager::DockerManager,
+    faults::Faults, native::manager::NativeManager, output::network, service::ServiceConfig,
+};
+use std::{
+    io::{self, Result},
+    path::{Path, PathBuf},
+    process::Output,
+};
+
+/// Prefix of the archive databases, the same in every backend.
+/// Each archive node has its own database, see [`ServiceConfig::archive_db`].
+pub const ARCHIVE_DB: &str = "archive";
+
+pub trait Backend {
+    /// Prepare the backend for a freshly generated network
+    fn create(&self, services: &[ServiceConfig]) -> Result<()>;
+
+    /// Create the archive database and apply its schema scripts
+    fn setup_archive(&self, archive_node: &ServiceConfig) -> Result<()>;
+
+    /// Start all services of the network, returning the backend's output
+    fn start_all(&self, services: &[ServiceConfig]) -> Result<String>;
+
+    /// Stop all services of the network
+    fn stop_all(&self) -> Result<()>;
+
+    /// Start a single service, returning the backend's output
+    fn start_service(&self, service: &ServiceConfig) -> Result<String>;
+
+    /// Stop a single service, returning the backend's output
+    fn stop_service(&self, service_name: &str) -> Result<String>;
+
+    /// State of a single service, `None` if the backend does not know it
+    fn service_state(&self, service_name: &str) -> Result<Option<ContainerState>>;
+
+    /// Recreate a single service with its updated config, e.g. a new image, keeping its state.
+    /// It is started again only if it was running. `services` are all services of the network.
+    fn upgrade_service(&self, service: &ServiceConfig, services: &[ServiceConfig]) -> Result<()>;
+
+    /// Discard the state of a single service so it starts fresh, stopping it if needed
+    fn reset_service(&self, service: &ServiceConfig) -> Result<()>;
+
+    /// Import the account at `network-keypairs/<account_file>` into a service
+    fn import_account(&self, service: &ServiceConfig, account_file: &str) -> Result<()>;
+
+    /// Path of `path`, relative to the network directory, as seen by the services
+    fn service_path(&self, path: &Path) -> PathBuf;
+
+    /// Logs of a single service
+    fn logs(&self, service: &ServiceConfig) -> Result<String>;
+
+    /// Precomputed blocks logged by daemon `service_name`
+    fn dump_precomputed_blocks(&self, service_name: &str) -> Result<String>;
+
+    /// Execute a command in the context of a service
+    fn exec(&self, service_name: &str, cmd: &[&str]) -> Result<Output>;
+
+    /// Run the replayer against the database of `archive_node`
+    fn run_replayer(&self, archive_node: &ServiceConfig) -> Result<Output>;
+
+    /// Export `ledger` (`staged-ledger`, `staking-epoch-ledger` or `next-epoch-ledger`)
+    /// of the running daemon `service`, as the JSON accounts of a runtime config
+    fn export_ledger(&self, service: &ServiceConfig, ledger: &str) -> Result<String>;
+
+    /// Status of the network and its services
+    fn status(&self, network_id: &str, services: &[ServiceConfig]) -> Result<network::Status>;
+
+    /// Tear down all services and the state they own
+    fn destroy(&self) -> Result<()>;
+
+    /// Save the state the stopped services keep outside of the network directory
+    /// (volumes, archive databases) under `state_dir`, returning the saved entries
+    fn save_state(&self, services: &[ServiceConfig], state_dir: &Path) -> Result<Vec<String>>;
+
+    /// Load the `entries` saved by [`Backend::save_state`] from `state_dir`
+    /// into the freshly created services
+    fn load_state(
+        &self,
+        services: &[ServiceConfig],
+        state_dir: &Path,
+        entries: &[String],
+    ) -> Result<()>;
+
+    /// Apply `faults` to the running `services`, replacing the faults applied to them before
+    fn apply_faults(&self, faults: &Faults, services: &[ServiceConfig]) -> Result<()>;
+}
+
+/// Picks the backend for `mode`.
+/// `bin_path` must be resolved in native mode.
+pub fn for_mode(
+    mode: &ExecutionMode,
+    network_path: &Path,
+    bin_path: Option<&Path>,
+) -> Box<dyn Backend> {
+    match mode {
+        ExecutionMode::Docker => Box::new(DockerManager::new(network_path)),
+        ExecutionMode::Native => Box::new(NativeManager::new(
+            network_path,
+            bin_path.expect("native mode guarantees bin_path is resolved"),
+        )),
+    }
+}
+
+/// Turns an unsuccessful command output into an error carrying its stderr
+pub fn ensure_success(output: Result<Output>) -> Result<Output> {
+    let output = output?;
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// Backend recording the calls it receives, to test the flows written against [`Backend`]
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use crate::{docker::manager::ContainerState, service::ServiceType};
+    use std::{cell::RefCell, collections::HashMap, fs};
+
+    #[derive(Default)]
+    pub struct MockBackend {
+        calls: RefCell<Vec<String>>,
+        states: HashMap<String, ContainerState>,
+    }
+
+    impl MockBackend {
+        pub fn with_state(service_name: &str, state: ContainerState) -> Self {
+            MockBackend {
+                states: HashMap::from([(service_name.to_string(), state)]),
+                ..Default::default()
+            }
+        }
+
+        pub fn record(&self, call: String) {
+            self.calls.borrow_mut().push(call);
+        }
+
+        pub fn calls(&self) -> Vec<String> {
+            self.calls.borrow().clone()
+        }
+    }
+
+    /// Config of a service with only a name and a type
+    pub fn service(service_name: &str, service_type: ServiceType) -> ServiceConfig {
+        ServiceConfig {
+            service_name: service_name.to_string(),
+            service_type,
+            ..Default::default()
+        }
+    }
+
+    impl Backend for MockBackend {
+        fn create(&self, services: &[ServiceConfig]) -> Result<()> {
+            self.record(format!("create {}", services.len()));
+            Ok(())
+        }
+
+        fn setup_archive(&self, archive_node: &ServiceConfig) -> Result<()> {
+            self.record(format!("setup_archive {}", archive_node.service_name));
+            Ok(())
+        }
+
+        fn start_all(&self, services: &[ServiceConfig]) -> Result<String> {
+            self.record(format!("start_all {}", services.len()));
+            Ok(String::new())
+        }
+
+        fn stop_all(&self) -> Result<()> {
+            self.record("stop_all".to_string());
+            Ok(())
+        }
+
+        fn start_service(&self, service: &ServiceConfig) -> Result<String> {
+            self.record(format!("start_service {}", service.service_name));
+            Ok("started".to_string())
+        }
+
+        fn stop_service(&self, service_name: &str) -> Result<String> {
+            self.record(format!("stop_service {service_name}"));
+            Ok(String::new())
+        }
+
+        fn service_state(&self, service_name: &str) -> Result<Option<ContainerState>> {
+            Ok(self.states.get(service_name).cloned())
+        }
+
+        fn upgrade_service(
+            &self,
+            service: &ServiceConfig,
+            services: &[ServiceConfig],
+        ) -> Result<()> {
+            self.record(format!(
+                "upgrade_service {} {} of {}",
+                service.service_name,
+                service.docker_image.as_deref().unwrap_or_default(),
+                services.len()
+            ));
+            Ok(())
+        }
+
+        fn reset_service(&self, service: &ServiceConfig) -> Result<()> {
+            self.record(format!("reset_service {}", service.service_name));
+            Ok(())
+        }
+
+        fn import_account(&self, service: &ServiceConfig, account_file: &str) -> Result<()> {
+            self.record(format!(
+                "import_account {} {account_file}",
+                service.service_name
+            ));
+            Ok(())
+        }
+
+        fn service_path(&self, path: &Path) -> PathBuf {
+            Path::new("/network").join(path)
+        }
+
+        fn logs(&self, service: &ServiceConfig) -> Result<String> {
+            Ok(format!("logs of {:?}", service.service_type))
+        }
+
+        fn dump_precomputed_blocks(&self, service_name: &str) -> Result<String> {
+            self.record(format!("dump_precomputed_blocks {service_name}"));
+            Ok(String::new())
+        }
+
+        fn exec(&self, service_name: &str, cmd: &[&str]) -> Result<Output> {
+            self.record(format!("exec {service_name} {}", cmd.join(" ")));
+            std::process::Command::new("true").output()
+        }
+
+        fn export_ledger(&self, service: &ServiceConfig, ledger: &str) -> Result<String> {
+            self.record(format!("export_ledger {} {ledger}", service.service_name));
+            Ok(format!(r#"[{{"pk": "B62q{ledger}", "balance": "1000"}}]"#))
+        }
+
+        fn run_replayer(&self, archive_node: &ServiceConfig) -> Result<Output> {
+            self.record(format!("run_replayer {}", archive_node.service_name));
+            std::process::Command::new("true").output()
+        }
+
+        fn status(&self, network_id: &str, _services: &[ServiceConfig]) -> Result<network::Status> {
+            self.record("status".to_string());
+            Ok(network::Status::new(network_id))
+        }
+
+        fn destroy(&self) -> Result<()> {
+            self.record("destroy".to_string());
+            Ok(())
+        }
+
+        fn save_state(&self, services: &[ServiceConfig], state_dir: &Path) -> Result<Vec<String>> {
+            self.record(format!("save_state {}", services.len()));
+            fs::write(state_dir.join("volume.tar.gz"), "volume")?;
+            Ok(vec!["volume.tar.gz".to_string()])
+        }
+
+        fn load_state(
+            &self,
+            services: &[ServiceConfig],
+            state_dir: &Path,
+            entries: &[String],
+        ) -> Result<()> {
+            self.record(format!("load_state {}", services.len()));
+            for entry in entries {
+                assert_eq!(fs::read_to_string(state_dir.join(entry))?, "volume");
+            }
+            Ok(())
+        }
+
+        fn apply_faults(&self, faults: &Faults, services: &[ServiceConfig]) -> Result<()> {
+            let services: Vec<&str> = services.iter().map(|s| s.service_name.as_str()).collect();
+            self.record(format!(
+                "apply_faults {} {} {}",
+                faults.groups.len(),
+                faults.links.len(),
+                services.join(",")
+            ));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_ensure_success() {
+        let ok = Command::new("sh").args(["-c", "echo ok"]).output();
+        assert!(ensure_success(ok).is_ok());
+
+        let failed = Command::new("sh")
+            .args(["-c", "echo boom >&2; exit 1"])
+            .output();
+        let err = ensure_success(failed).unwrap_err();
+        assert_eq!(err.to_string(), "boom");
+    }
+}
@@ -0,0 +1,200 @@
+//! # Bundle Module
+//!
+//! A bundle is a relocatable copy of a network, to recreate it on another machine.
+//! It is laid out like a snapshot (see [`crate::snapshot`]) without backend state:
+//! - `manifest.json`: what was exported, see [`Manifest`]
+//! - `network/`: the keys, genesis ledger, topology and `services.json` of the network
+//!
+//! Paths in the bundled `services.json` are relative to the network directory,
+//! they are resolved against the network directory the bundle is imported into.
+
+use crate::{
+    directory_manager::{LIBP2P_KEYPAIRS, NETWORK_KEYPAIRS, PEER_LIST_FILE, UPTIME_SERVICE_CONFIG},
+    service::ServiceConfig,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+/// Content of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Manifest {
+    /// Network the bundle was exported from
+    pub network_id: String,
+    pub created_at: String,
+    pub minimina_version: String,
+    /// Docker images of the services
+    pub images: Vec<String>,
+    pub services: Vec<String>,
+}
+
+impl Manifest {
+    pub fn new(network_id: &str, services: &[ServiceConfig]) -> Self {
+        let images: BTreeSet<&String> = services
+            .iter()
+            .flat_map(|s| [&s.docker_image, &s.archive_docker_image])
+            .flatten()
+            .collect();
+        Manifest {
+            network_id: network_id.to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            minimina_version: env!("CARGO_PKG_VERSION").to_string(),
+            images: images.into_iter().cloned().collect(),
+            services: services.iter().map(|s| s.service_name.clone()).collect(),
+        }
+    }
+}
+
+/// Makes the paths of `services` relative to the network directory at `network_path`.
+/// Keys and uptime service configs from outside of it point at the copies
+/// made when the network was created.
+pub fn relativize_paths(services: &mut [ServiceConfig], network_path: &Path) {
+    let relative = |path: &Path, copy: PathBuf| {
+        path.strip_prefix(network_path)
+            .map(Path::to_path_buf)
+            .unwrap_or(copy)
+    };
+
+    for service in services.iter_mut() {
+        let key_file = format!("{}.json", service.service_name);
+        let uptime_service_config = Path::new(UPTIME_SERVICE_CONFIG).join(&service.service_name);
+        let config_copy =
+            |path: &Path| uptime_service_config.join(path.file_name().unwrap_or(path.as_os_str()));
+
+        if let Some(path) = &mut service.private_key_path {
+            *path = relative(path, Path::new(NETWORK_KEYPAIRS).join(&key_file));
+        }
+        if let Some(path) = &mut service.libp2p_keypair_path {
+            *path = relative(path, Path::new(LIBP2P_KEYPAIRS).join(&key_file));
+        }
+        if let Some(path) = &mut service.peer_list_file {
+            *path = relative(path, PathBuf::from(PEER_LIST_FILE));
+        }
+        for path in [
+            &mut service.uptime_service_backend_app_config,
+            &mut service.uptime_service_backend_minasheets,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(
+            service
+                .uptime_service_other_config_files
+                .iter_mut()
+                .flatten(),
+        ) {
+            *path = relative(path, config_copy(path));
+        }
+    }
+}
+
+/// Resolves the relative paths of `services` against the network directory at `network_path`
+pub fn resolve_paths(services: &mut [ServiceConfig], network_path: &Path) {
+    for service in services.iter_mut() {
+        for path in [
+            &mut service.private_key_path,
+            &mut service.libp2p_keypair_path,
+            &mut service.peer_list_file,
+            &mut service.uptime_service_backend_app_config,
+            &mut service.uptime_service_backend_minasheets,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(
+            service
+                .uptime_service_other_config_files
+                .iter_mut()
+                .flatten(),
+        ) {
+            if path.is_relative() {
+                *path = network_path.join(&path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relativize_and_resolve_paths() {
+        let services = vec![
+            ServiceConfig {
+                service_name: "mina-bp-1".to_string(),
+                docker_image: Some("mina-daemon:3.0.0".to_string()),
+                private_key_path: Some(PathBuf::from("/home/lucy/keys/bp-1.key")),
+                libp2p_keypair_path: Some(PathBuf::from(
+                    "/home/.minimina/test/libp2p-keypairs/mina-bp-1.json",
+                )),
+                peer_list_file: Some(PathBuf::from("/home/.minimina/test/peer_list_file.txt")),
+                ..Default::default()
+            },
+            ServiceConfig {
+                service_name: "uptime-a".to_string(),
+                docker_image: Some("uptime:1.0".to_string()),
+                uptime_service_backend_app_config: Some(PathBuf::from(
+                    "/home/lucy/uptime/app_config.json",
+                )),
+                uptime_service_other_config_files: Some(vec![PathBuf::from(
+                    "/home/lucy/uptime/aws.json",
+                )]),
+                ..Default::default()
+            },
+            ServiceConfig {
+                service_name: "mina-archive-1".to_string(),
+                docker_image: Some("mina-daemon:3.0.0".to_string()),
+                archive_docker_image: Some("mina-archive:3.0.0".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let manifest = Manifest::new("test", &services);
+        assert_eq!(
+            manifest.images,
+            ["mina-archive:3.0.0", "mina-daemon:3.0.0", "uptime:1.0"]
+        );
+
+        let mut relocated = services.clone();
+        relativize_paths(&mut relocated, Path::new("/home/.minimina/test"));
+        assert_eq!(
+            relocated[0].private_key_path.as_deref(),
+            Some(Path::new("network-keypairs/mina-bp-1.json"))
+        );
+        assert_eq!(
+            relocated[0].libp2p_keypair_path.as_deref(),
+            Some(Path::new("libp2p-keypairs/mina-bp-1.json"))
+        );
+        assert_eq!(
+            relocated[0].peer_list_file.as_deref(),
+            Some(Path::new("peer_list_file.txt"))
+        );
+        assert_eq!(
+            relocated[1].uptime_service_backend_app_config.as_deref(),
+            Some(Path::new("uptime_service_config/uptime-a/app_config.json"))
+        );
+        assert_eq!(
+            relocated[1].uptime_service_other_config_files.as_deref(),
+            Some([PathBuf::from("uptime_service_config/uptime-a/aws.json")].as_slice())
+        );
+
+        resolve_paths(&mut relocated, Path::new("/data/minimina/test"));
+        assert_eq!(
+            relocated[0].private_key_path.as_deref(),
+            Some(Path::new(
+                "/data/minimina/test/network-keypairs/mina-bp-1.json"
+            ))
+        );
+        assert_eq!(
+            relocated[0].peer_list_file.as_deref(),
+            Some(Path::new("/data/minimina/test/peer_list_file.txt"))
+        );
+        assert_eq!(
+            relocated[1].uptime_service_backend_app_config.as_deref(),
+            Some(Path::new(
+                "/data/minimina/test/uptime_service_config/uptime-a/app_config.json"
+            ))
+        );
+    }
+}
@@ -0,0 +1,495 @@
+//! # Chaos Module
+//!
+//! Kills and restarts random nodes of a running network, to exercise the daemons'
+//! bootstrap and catchup paths.
+//!
+//! A run is planned upfront from a seed, see [`plan`]: kills are spaced by intervals drawn
+//! from a [`Distribution`], each killed node stays down for a drawn downtime, and at most
+//! `max_down` nodes are down at once. A share of the restarts wipe the node's state so that
+//! it bootstraps from scratch. The run ends with every node it killed restarted.
+//!
+//! Every action is appended to a JSON [`EventLog`] as it runs, from which a failing run
+//! is replayed exactly with [`Runner::replay`].
+
+use crate::{
+    backend::Backend,
+    commands,
+    directory_manager::DirectoryManager,
+    output,
+    service::{self, Role},
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    io::Result,
+    path::Path,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Distribution of a duration in seconds: `N`, `uniform:MIN-MAX` or `exp:MEAN`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Distribution {
+    Fixed(f64),
+    Uniform { min: f64, max: f64 },
+    Exponential { mean: f64 },
+}
+
+impl Distribution {
+    fn sample(&self, rng: &mut Rng) -> f64 {
+        match *self {
+            Distribution::Fixed(secs) => secs,
+            Distribution::Uniform { min, max } => min + (max - min) * rng.next_f64(),
+            Distribution::Exponential { mean } => -mean * (1.0 - rng.next_f64()).ln(),
+        }
+    }
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || {
+            format!("Invalid distribution '{s}', expected N, uniform:MIN-MAX or exp:MEAN seconds")
+        };
+        let secs = |value: &str| match value.trim().parse::<f64>() {
+            Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(secs),
+            _ => Err(invalid()),
+        };
+        match s.split_once(':') {
+            None => Ok(Distribution::Fixed(secs(s)?)),
+            Some(("uniform", range)) => {
+                let (min, max) = range.split_once('-').ok_or_else(invalid)?;
+                let (min, max) = (secs(min)?, secs(max)?);
+                if min > max {
+                    return Err(invalid());
+                }
+                Ok(Distribution::Uniform { min, max })
+            }
+            Some(("exp", mean)) => Ok(Distribution::Exponential { mean: secs(mean)? }),
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for Distribution {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Distribution> for String {
+    fn from(distribution: Distribution) -> Self {
+        distribution.to_string()
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Distribution::Fixed(secs) => write!(f, "{secs}"),
+            Distribution::Uniform { min, max } => write!(f, "uniform:{min}-{max}"),
+            Distribution::Exponential { mean } => write!(f, "exp:{mean}"),
+        }
+    }
+}
+
+/// SplitMix64, small and stable across versions so that a seed always plans the same run
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    pub seed: u64,
+    /// Roles of the nodes to kill, ignored when `nodes` is set
+    pub roles: Vec<Role>,
+    /// Nodes to kill
+    pub nodes: Vec<String>,
+    pub duration_secs: u64,
+    /// Time between two kills
+    pub interval: Distribution,
+    /// Time a killed node stays down
+    pub downtime: Distribution,
+    /// Maximum number of nodes down at once
+    pub max_down: usize,
+    /// Share of the restarts with fresh state
+    pub fresh_state_ratio: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionKind {
+    Stop,
+    Start,
+}
+
+/// Stop or start of `node`, `at_secs` after the start of the run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Action {
+    pub at_secs: f64,
+    pub kind: ActionKind,
+    pub node: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fresh_state: bool,
+}
+
+/// Action as it ran
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(flatten)]
+    pub action: Action,
+    pub elapsed_secs: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Content of a chaos event file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventLog {
+    pub network_id: String,
+    pub started_at: String,
+    pub config: Config,
+    pub events: Vec<Event>,
+}
+
+impl EventLog {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Summary of the run
+    pub fn summary(&self, events_path: &Path) -> output::chaos::Run {
+        let count = |kind: ActionKind| {
+            self.events
+                .iter()
+                .filter(|event| event.action.kind == kind)
+                .count()
+        };
+        output::chaos::Run {
+            network_id: self.network_id.clone(),
+            seed: self.config.seed,
+            events_file: events_path.display().to_string(),
+            stops: count(ActionKind::Stop),
+            starts: count(ActionKind::Start),
+            fresh_starts: self
+                .events
+                .iter()
+                .filter(|event| event.action.fresh_state)
+                .count(),
+            errors: self
+                .events
+                .iter()
+                .filter(|event| event.error.is_some())
+                .count(),
+        }
+    }
+}
+
+/// Plans the actions of a run of `config` killing `targets`, in time order
+pub fn plan(config: &Config, targets: &[String]) -> Vec<Action> {
+    let mut rng = Rng(config.seed);
+    let duration = config.duration_secs as f64;
+    let mut actions = vec![];
+    // killed nodes with the time of their restart
+    let mut down: Vec<(f64, String)> = vec![];
+    let mut next_kill = config.interval.sample(&mut rng);
+    loop {
+        let next_restart = down
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.0.total_cmp(&b.0))
+            .map(|(i, (at, _))| (i, *at));
+        match next_restart {
+            // nodes still down at the end of the run are restarted then
+            Some((i, at)) if at <= next_kill || next_kill >= duration => {
+                let (_, node) = down.remove(i);
+                actions.push(Action {
+                    at_secs: at.min(duration),
+                    kind: ActionKind::Start,
+                    node,
+                    fresh_state: rng.next_f64() < config.fresh_state_ratio,
+                });
+            }
+            _ if next_kill >= duration => return actions,
+            _ => {
+                let up: Vec<&String> = targets
+                    .iter()
+                    .filter(|target| !down.iter().any(|(_, node)| node == *target))
+                    .collect();
+                if down.len() < config.max_down && !up.is_empty() {
+                    let node = up[rng.below(up.len())].clone();
+                    let restart_at = next_kill + config.downtime.sample(&mut rng);
+                    actions.push(Action {
+                        at_secs: next_kill,
+                        kind: ActionKind::Stop,
+                        node: node.clone(),
+                        fresh_state: false,
+                    });
+                    down.push((restart_at, node));
+                }
+                next_kill += config.interval.sample(&mut rng);
+            }
+        }
+    }
+}
+
+/// Runs chaos on network `network_id`
+pub struct Runner<'a> {
+    pub backend: &'a dyn Backend,
+    pub directory_manager: &'a DirectoryManager,
+    pub network_id: &'a str,
+}
+
+impl Runner<'_> {
+    /// Runs the plan of `config`, logging its events to `events_path`
+    pub fn run(&self, config: &Config, events_path: &Path) -> Result<EventLog> {
+        let services = self.directory_manager.get_services_info(self.network_id)?;
+        let targets = service::select_services(&services, &config.roles, &config.nodes)?;
+        info!(
+            "Running chaos with seed {} on {targets:?} of network '{}'.",
+            config.seed, self.network_id
+        );
+        self.execute(config, plan(config, &targets), events_path)
+    }
+
+    /// Runs the actions of the events of `log` again, at the same times,
+    /// logging the new events to `events_path`
+    pub fn replay(&self, log: &EventLog, events_path: &Path) -> Result<EventLog> {
+        info!(
+            "Replaying {} chaos events of seed {} on network '{}'.",
+            log.events.len(),
+            log.config.seed,
+            self.network_id
+        );
+        let actions = log
+            .events
+            .iter()
+            .map(|event| event.action.clone())
+            .collect();
+        self.execute(&log.config, actions, events_path)
+    }
+
+    fn execute(
+        &self,
+        config: &Config,
+        actions: Vec<Action>,
+        events_path: &Path,
+    ) -> Result<EventLog> {
+        let mut log = EventLog {
+            network_id: self.network_id.to_string(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            config: config.clone(),
+            events: vec![],
+        };
+        log.save(events_path)?;
+
+        let start = Instant::now();
+        for action in actions {
+            let at = Duration::from_secs_f64(action.at_secs);
+            thread::sleep(at.saturating_sub(start.elapsed()));
+
+            let node = action.node.as_str();
+            let result = match action.kind {
+                ActionKind::Stop => {
+                    info!("Killing node '{node}'.");
+                    self.backend.stop_service(node).map(|_| ())
+                }
+                ActionKind::Start => {
+                    info!(
+                        "Restarting node '{node}'{}.",
+                        if action.fresh_state {
+                            " with fresh state"
+                        } else {
+                            ""
+                        }
+                    );
+                    commands::start_node(
+                        self.backend,
+                        self.directory_manager,
+                        node,
+                        self.network_id,
+                        action.fresh_state,
+                        false,
+                    )
+                    .map(|_| ())
+                }
+            };
+            if let Err(e) = &result {
+                warn!("Failed to {:?} node '{node}': {e}", action.kind);
+            }
+            log.events.push(Event {
+                action,
+                elapsed_secs: start.elapsed().as_secs_f64(),
+                error: result.err().map(|e| e.to_string()),
+            });
+            // saved after each event, so that an interrupted run can be replayed too
+            log.save(events_path)?;
+        }
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::mock::{service, MockBackend},
+        docker::manager::ContainerState,
+        service::ServiceType,
+    };
+    use tempdir::TempDir;
+
+    fn config(seed: u64) -> Config {
+        Config {
+            seed,
+            roles: vec![Role::BlockProducer, Role::Seed],
+            nodes: vec![],
+            duration_secs: 600,
+            interval: "uniform:10-60".parse().unwrap(),
+            downtime: "exp:30".parse().unwrap(),
+            max_down: 2,
+            fresh_state_ratio: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_parse_distribution() {
+        assert_eq!("30".parse(), Ok(Distribution::Fixed(30.0)));
+        assert_eq!(
+            "uniform:10-60.5".parse(),
+            Ok(Distribution::Uniform {
+                min: 10.0,
+                max: 60.5
+            })
+        );
+        assert_eq!(
+            "exp:45".parse(),
+            Ok(Distribution::Exponential { mean: 45.0 })
+        );
+        for invalid in [
+            "0",
+            "-1",
+            "uniform:60-10",
+            "uniform:10",
+            "normal:10",
+            "exp:x",
+        ] {
+            assert!(invalid.parse::<Distribution>().is_err(), "{invalid}");
+        }
+        for distribution in ["30", "uniform:10-60.5", "exp:45"] {
+            assert_eq!(
+                distribution.parse::<Distribution>().unwrap().to_string(),
+                distribution
+            );
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let targets: Vec<String> = ["mina-seed-1", "mina-bp-1", "mina-bp-2"]
+            .map(String::from)
+            .to_vec();
+        let actions = plan(&config(42), &targets);
+        assert_eq!(actions, plan(&config(42), &targets));
+        assert_ne!(actions, plan(&config(43), &targets));
+
+        let stops = actions
+            .iter()
+            .filter(|a| a.kind == ActionKind::Stop)
+            .count();
+        assert!(stops > 5);
+        assert!(actions.iter().any(|action| action.fresh_state));
+        assert!(actions
+            .windows(2)
+            .all(|pair| pair[0].at_secs <= pair[1].at_secs));
+        assert!(actions.iter().all(|action| action.at_secs <= 600.0));
+
+        // never more than max_down nodes down, all of them restarted in the end
+        let mut down: Vec<&str> = vec![];
+        for action in &actions {
+            match action.kind {
+                ActionKind::Stop => {
+                    assert!(!down.contains(&action.node.as_str()));
+                    down.push(&action.node);
+                    assert!(down.len() <= 2);
+                }
+                ActionKind::Start => down.retain(|node| *node != action.node),
+            }
+        }
+        assert!(down.is_empty());
+    }
+
+    #[test]
+    fn test_run_and_replay() {
+        let tempdir = TempDir::new("test_chaos").expect("Cannot create temporary directory");
+        let directory_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        directory_manager.generate_dir_structure("test").unwrap();
+        directory_manager
+            .save_services_info("test", &[service("mina-bp-1", ServiceType::BlockProducer)])
+            .unwrap();
+        let backend = MockBackend::with_state("mina-bp-1", ContainerState::Exited);
+        let runner = Runner {
+            backend: &backend,
+            directory_manager: &directory_manager,
+            network_id: "test",
+        };
+
+        let config = Config {
+            duration_secs: 1,
+            interval: Distribution::Fixed(0.4),
+            downtime: Distribution::Fixed(0.2),
+            max_down: 1,
+            fresh_state_ratio: 1.0,
+            ..config(7)
+        };
+        let events_path = tempdir.path().join("events.json");
+        let log = runner.run(&config, &events_path).unwrap();
+        assert_eq!(log, EventLog::from_file(&events_path).unwrap());
+        let summary = log.summary(&events_path);
+        assert_eq!((summary.stops, summary.starts), (2, 2));
+        assert_eq!((summary.fresh_starts, summary.errors), (2, 0));
+        let calls = [
+            "stop_service mina-bp-1",
+            "reset_service mina-bp-1",
+            "start_service mina-bp-1",
+        ];
+        assert_eq!(backend.calls(), [calls, calls].concat());
+
+        let replay_path = tempdir.path().join("replay.json");
+        let replay = runner.replay(&log, &replay_path).unwrap();
+        let actions = |log: &EventLog| -> Vec<Action> {
+            log.events.iter().map(|e| e.action.clone()).collect()
+        };
+        assert_eq!(actions(&replay), actions(&log));
+        assert_eq!(replay.config, log.config);
+    }
+}
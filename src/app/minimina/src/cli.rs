@@ -1,9 +1,21 @@
 //! # `minimina` Command-Line Interface (CLI)
 
 use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, ValueEnum, PartialEq)]
+use minimina::amounts::amount_dsl_to_nanomina;
+
+use crate::{
+    chaos::Distribution,
+    faults::{Groups, Link},
+    genesis_ledger::{import::Subset, ForkConfig, ProtocolConstants, Slots, TimingSpec},
+    service::Role,
+    wait::Condition,
+};
+
+#[derive(Debug, Clone, ValueEnum, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ExecutionMode {
     Docker,
     Native,
@@ -39,12 +51,32 @@ pub enum Command {
     /// Manage a single node
     #[clap(subcommand)]
     Node(NodeCommand),
+
+    /// Work with topology files
+    #[clap(subcommand)]
+    Topology(TopologyCommand),
+
+    /// Run scripted scenarios against a network
+    #[clap(subcommand)]
+    Scenario(ScenarioCommand),
+
+    /// Send transactions between the accounts of a network
+    #[clap(subcommand)]
+    Tx(TxCommand),
+
+    /// Generate transaction load on a network
+    #[clap(subcommand)]
+    Load(LoadCommand),
+
+    /// Kill and restart random nodes of a network
+    #[clap(subcommand)]
+    Chaos(ChaosCommand),
 }
 
 #[derive(Subcommand)]
 pub enum NetworkCommand {
     /// Create a local network
-    Create(CreateNetworkArgs),
+    Create(Box<CreateNetworkArgs>),
     /// Delete a local network
     Delete(NetworkId),
     /// List local networks
@@ -57,6 +89,26 @@ pub enum NetworkCommand {
     Start(StartNetworkArgs),
     /// Stop a local network
     Stop(NetworkId),
+    /// Stop a local network and save its state as a snapshot
+    Snapshot(SnapshotArgs),
+    /// Recreate a network from a snapshot
+    Restore(RestoreArgs),
+    /// Create a network from the keys, genesis ledger and topology of another
+    Clone(CloneNetworkArgs),
+    /// Save a network as a bundle which can be imported on another machine
+    Export(ExportArgs),
+    /// Create a network from a bundle
+    Import(ImportArgs),
+    /// Wait until the nodes of a network are synced or reached a height, epoch or slot
+    Wait(WaitArgs),
+    /// Partition the services of a running network and shape the links between them
+    Partition(PartitionArgs),
+    /// Remove the partitions and link shaping of a running network
+    Heal(NetworkId),
+    /// Change the image or binaries of services, restarting them one batch at a time
+    Upgrade(UpgradeArgs),
+    /// Stop the network at a fork point and create a successor network continuing its chain
+    Fork(ForkArgs),
 }
 
 #[derive(Args, Debug, Clone)]
@@ -66,6 +118,175 @@ pub struct NetworkId {
     pub network_id: String,
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct SnapshotArgs {
+    /// Snapshot name
+    #[clap(long)]
+    pub name: String,
+
+    #[clap(flatten)]
+    pub network_id: NetworkId,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RestoreArgs {
+    /// Snapshot name
+    #[clap(long)]
+    pub name: String,
+
+    /// Network identifier to restore as, the snapshot's network by default
+    #[clap(short, long)]
+    pub network_id: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ExportArgs {
+    /// Bundle file to write, `<network id>.tar.gz` by default
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub network_id: NetworkId,
+}
+
+impl ExportArgs {
+    pub fn output(&self) -> PathBuf {
+        self.output
+            .clone()
+            .unwrap_or_else(|| format!("{}.tar.gz", self.network_id.network_id).into())
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ImportArgs {
+    /// Bundle file to import
+    #[clap(long)]
+    pub file: PathBuf,
+
+    /// Network identifier to import as, the bundle's network by default
+    #[clap(short, long)]
+    pub network_id: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WaitArgs {
+    /// Condition to wait for: synced, height>=N, epoch>=E or slot>=S
+    #[clap(long)]
+    pub until: Condition,
+
+    /// Node to wait for, all nodes with a graphql endpoint by default (repeatable)
+    #[clap(long = "node")]
+    pub nodes: Vec<String>,
+
+    /// Timeout in seconds
+    #[clap(long, default_value_t = 600)]
+    pub timeout: u64,
+
+    #[clap(flatten)]
+    pub network_id: NetworkId,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct PartitionArgs {
+    /// Groups of services which cannot reach each other: a,b|c,d
+    #[clap(long, required_unless_present = "links")]
+    pub groups: Option<Groups>,
+
+    /// Link to shape: a:b,latency=200ms,loss=5%,bandwidth=1mbit (repeatable)
+    #[clap(long = "link")]
+    pub links: Vec<Link>,
+
+    #[clap(flatten)]
+    pub network_id: NetworkId,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct UpgradeArgs {
+    /// Docker image of the mina daemons
+    #[clap(long, required_unless_present_any = ["archive_image", "bin"])]
+    pub image: Option<String>,
+
+    /// Docker image of the archive services
+    #[clap(long)]
+    pub archive_image: Option<String>,
+
+    /// Directory of the mina binaries, in native mode
+    #[clap(long, conflicts_with_all = ["image", "archive_image"])]
+    pub bin: Option<PathBuf>,
+
+    /// Role of the services to upgrade, all mina daemons by default (repeatable)
+    #[clap(long = "role", value_enum)]
+    pub roles: Vec<Role>,
+
+    /// Service to upgrade instead of those of the roles (repeatable)
+    #[clap(long = "node", conflicts_with = "roles")]
+    pub nodes: Vec<String>,
+
+    /// Number of services upgraded at once
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub batch: u64,
+
+    /// Wait for the upgraded nodes to be synced before upgrading the next batch
+    #[clap(long)]
+    pub wait_synced: bool,
+
+    /// Seconds to wait for each batch to be synced
+    #[clap(long, default_value_t = 600, requires = "wait_synced")]
+    pub timeout: u64,
+
+    #[clap(flatten)]
+    pub network_id: NetworkId,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ForkArgs {
+    /// Network identifier of the successor network
+    #[clap(long)]
+    pub successor: String,
+
+    /// Node to read the fork point and ledgers from, the first with a graphql endpoint by default
+    #[clap(long)]
+    pub node: Option<String>,
+
+    /// Global slot the node's best tip must reach before the fork, the current best tip otherwise
+    #[clap(long)]
+    pub slot: Option<u64>,
+
+    /// Seconds to wait for the slot
+    #[clap(long, default_value_t = 3600, requires = "slot")]
+    pub timeout: u64,
+
+    /// Docker image of the successor's mina daemons
+    #[clap(long)]
+    pub image: Option<String>,
+
+    /// Docker image of the successor's archive services
+    #[clap(long)]
+    pub archive_image: Option<String>,
+
+    /// Directory of the successor's mina binaries, in native mode
+    #[clap(long, conflicts_with_all = ["image", "archive_image"])]
+    pub bin: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub network_id: NetworkId,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CloneNetworkArgs {
+    /// Network to clone
+    #[clap(long)]
+    pub from: String,
+
+    /// Network identifier of the clone
+    #[clap(long)]
+    pub to: String,
+
+    /// Set the clone's genesis timestamp to the current time
+    #[clap(long)]
+    pub reset_genesis_timestamp: bool,
+}
+
 #[derive(Args, Clone)]
 pub struct CreateNetworkArgs {
     /// Path to the (JSON) topology file
@@ -76,6 +297,38 @@ pub struct CreateNetworkArgs {
     #[clap(short = 'g', long)]
     pub genesis_ledger: Option<std::path::PathBuf>,
 
+    /// Path to a (JSON) ledger spec to generate the genesis ledger and account keys from
+    #[clap(long, conflicts_with = "genesis_ledger")]
+    pub ledger_spec: Option<std::path::PathBuf>,
+
+    /// Path to a ledger exported with `mina ledger export` to import the genesis ledger from
+    #[clap(long, conflicts_with_all = ["genesis_ledger", "ledger_spec"])]
+    pub ledger_from: Option<std::path::PathBuf>,
+
+    /// Import only the given number of accounts with the largest balances
+    #[clap(long, requires = "ledger_from", conflicts_with = "ledger_sample")]
+    pub ledger_top: Option<usize>,
+
+    /// Import the given number of accounts, evenly spread over the balance ranking
+    #[clap(long, requires = "ledger_from")]
+    pub ledger_sample: Option<usize>,
+
+    /// Public key of an imported account to hand over to a block producer (repeatable).
+    /// By default block producers take over the accounts with the largest delegated stake
+    #[clap(long, requires = "ledger_from")]
+    pub replace_key: Vec<String>,
+
+    /// Path to the mina checkout to build the `git_build` refs of the topology from
+    /// [default: $MINIMINA_MINA_REPO]
+    #[clap(long, requires = "topology")]
+    pub mina_repo: Option<std::path::PathBuf>,
+
+    #[clap(flatten)]
+    pub timing: TimingArgs,
+
+    #[clap(flatten)]
+    pub protocol: ProtocolArgs,
+
     /// Network identifier
     #[clap(flatten)]
     pub network_id: NetworkId,
@@ -85,6 +338,126 @@ pub struct CreateNetworkArgs {
     pub log_level: String,
 }
 
+/// Vesting schedule of the block producers' accounts in the default genesis ledger.
+/// Amounts use the amount DSL (`1000mina`), times are slots or ISO-8601 durations (`PT86400S`).
+#[derive(Args, Clone, Debug, Default)]
+pub struct TimingArgs {
+    /// Initial minimum balance of the timed accounts
+    #[clap(
+        long,
+        requires_all = ["cliff_time", "cliff_amount", "vesting_period", "vesting_increment"],
+        conflicts_with_all = ["genesis_ledger", "ledger_spec", "ledger_from"]
+    )]
+    pub initial_minimum_balance: Option<String>,
+
+    /// Slot (or duration since genesis) of the cliff
+    #[clap(long, requires = "initial_minimum_balance")]
+    pub cliff_time: Option<Slots>,
+
+    /// Amount unlocked at the cliff
+    #[clap(long, requires = "initial_minimum_balance")]
+    pub cliff_amount: Option<String>,
+
+    /// Slots (or duration) between two vesting increments
+    #[clap(long, requires = "initial_minimum_balance")]
+    pub vesting_period: Option<Slots>,
+
+    /// Amount unlocked every vesting period after the cliff
+    #[clap(long, requires = "initial_minimum_balance")]
+    pub vesting_increment: Option<String>,
+}
+
+impl CreateNetworkArgs {
+    /// Accounts imported with `--ledger-from`
+    pub fn ledger_subset(&self) -> Subset {
+        match (self.ledger_top, self.ledger_sample) {
+            (Some(n), _) => Subset::Top(n),
+            (_, Some(n)) => Subset::Sample(n),
+            _ => Subset::All,
+        }
+    }
+}
+
+impl TimingArgs {
+    /// Timing spec of the arguments, if given
+    pub fn timing_spec(&self) -> Option<TimingSpec> {
+        Some(TimingSpec {
+            initial_minimum_balance: self.initial_minimum_balance.clone()?,
+            cliff_time: self.cliff_time.clone()?,
+            cliff_amount: self.cliff_amount.clone()?,
+            vesting_period: self.vesting_period.clone()?,
+            vesting_increment: self.vesting_increment.clone()?,
+        })
+    }
+}
+
+/// Protocol constants written to the runtime config (`genesis_ledger.json`) of the network
+#[derive(Args, Clone, Debug, Default)]
+pub struct ProtocolArgs {
+    /// Number of blocks after which a block is final
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub k: Option<u32>,
+
+    /// Maximum delay of a block, in slots
+    #[clap(long)]
+    pub delta: Option<u32>,
+
+    /// Number of slots in an epoch
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub slots_per_epoch: Option<u32>,
+
+    /// Number of slots in a sub-window
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub slots_per_sub_window: Option<u32>,
+
+    /// Duration of a slot in milliseconds
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub block_window_duration_ms: Option<u64>,
+
+    /// Maximum number of transactions in the transaction pool
+    #[clap(long)]
+    pub txpool_max_size: Option<u32>,
+
+    /// Maximum number of transactions per block, as a power of 2
+    #[clap(long)]
+    pub transaction_capacity: Option<u32>,
+
+    /// State hash of the block the network forks from
+    #[clap(
+        long,
+        requires_all = ["fork_blockchain_length", "fork_global_slot_since_genesis"]
+    )]
+    pub fork_state_hash: Option<String>,
+
+    /// Blockchain length of the fork block
+    #[clap(long, requires = "fork_state_hash")]
+    pub fork_blockchain_length: Option<u32>,
+
+    /// Global slot since genesis of the fork block
+    #[clap(long, requires = "fork_state_hash")]
+    pub fork_global_slot_since_genesis: Option<u32>,
+}
+
+impl ProtocolArgs {
+    pub fn protocol_constants(&self) -> ProtocolConstants {
+        let fork = self.fork_state_hash.as_ref().map(|state_hash| ForkConfig {
+            state_hash: state_hash.clone(),
+            blockchain_length: self.fork_blockchain_length.unwrap_or_default(),
+            global_slot_since_genesis: self.fork_global_slot_since_genesis.unwrap_or_default(),
+        });
+        ProtocolConstants {
+            k: self.k,
+            delta: self.delta,
+            slots_per_epoch: self.slots_per_epoch,
+            slots_per_sub_window: self.slots_per_sub_window,
+            block_window_duration_ms: self.block_window_duration_ms,
+            txpool_max_size: self.txpool_max_size,
+            transaction_capacity: self.transaction_capacity,
+            fork,
+        }
+    }
+}
+
 #[derive(Args, Clone)]
 pub struct StartNetworkArgs {
     /// Network identifier
@@ -98,6 +471,256 @@ pub struct StartNetworkArgs {
     /// Specify log level
     #[clap(short = 'l', long, default_value = "warn")]
     pub log_level: String,
+
+    /// Supervise services and restart them when they crash (native mode only)
+    #[clap(long, default_value_t = false)]
+    pub supervise: bool,
+
+    /// Run the supervisor as a detached watchdog instead of in the foreground
+    #[clap(long, default_value_t = false, requires = "supervise")]
+    pub detach: bool,
+
+    /// Maximum number of restarts per service before giving up
+    #[clap(long, default_value_t = 5, requires = "supervise")]
+    pub max_restarts: u32,
+
+    /// Delay in seconds before the first restart, doubled on every further restart
+    #[clap(long, default_value_t = 2, requires = "supervise")]
+    pub restart_backoff_secs: u64,
+}
+
+#[derive(Subcommand)]
+pub enum TopologyCommand {
+    /// Validate a topology file and report all problems found
+    Validate(ValidateTopologyArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ValidateTopologyArgs {
+    /// Path to the (JSON) topology file
+    pub file: PathBuf,
+}
+
+#[derive(Subcommand)]
+pub enum TxCommand {
+    /// Send a payment
+    Payment(PaymentArgs),
+    /// Delegate the stake of an account
+    Delegate(TxArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct PaymentArgs {
+    /// Amount to send, e.g. `1.5mina` or `1000nanomina`
+    #[clap(long, value_parser = parse_amount)]
+    pub amount: u64,
+
+    #[clap(flatten)]
+    pub tx: TxArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct TxArgs {
+    /// Sending account: a service or key pair name in `network-keypairs`
+    #[clap(long)]
+    pub from: String,
+
+    /// Receiving account or delegate: a name like `--from`, or a public key
+    #[clap(long)]
+    pub to: String,
+
+    /// Transaction fee, e.g. `0.01mina`
+    #[clap(long, value_parser = parse_amount)]
+    pub fee: u64,
+
+    #[clap(long)]
+    pub memo: Option<String>,
+
+    /// Node to send the transaction through, the sender's node if it has a graphql endpoint
+    /// or else the first node which has one by default
+    #[clap(long)]
+    pub node: Option<String>,
+
+    /// Wait until the transaction is included in the best chain
+    #[clap(long)]
+    pub wait: bool,
+
+    /// Timeout in seconds when waiting for inclusion
+    #[clap(long, default_value_t = 600, requires = "wait")]
+    pub timeout: u64,
+
+    #[clap(flatten)]
+    pub network_id: NetworkId,
+}
+
+#[derive(Subcommand)]
+pub enum LoadCommand {
+    /// Send payments (and signed zkApp commands) at a target rate and report on them
+    Run(LoadRunArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct LoadRunArgs {
+    /// Target transactions per second
+    #[clap(long, default_value_t = 1.0, value_parser = parse_tps)]
+    pub tps: f64,
+
+    /// Duration of the run in seconds
+    #[clap(long, default_value_t = 60)]
+    pub duration: u64,
+
+    /// Sending account, all accounts with a key pair in `network-keypairs` by default (repeatable)
+    #[clap(long = "sender")]
+    pub senders: Vec<String>,
+
+    /// Node to send through, all nodes with a graphql endpoint by default (repeatable)
+    #[clap(long = "node")]
+    pub nodes: Vec<String>,
+
+    /// Amount of each payment
+    #[clap(long, default_value = "0.001mina", value_parser = parse_amount)]
+    pub amount: u64,
+
+    /// Fee of each transaction
+    #[clap(long, default_value = "0.01mina", value_parser = parse_amount)]
+    pub fee: u64,
+
+    /// Share of zkApp commands in the transactions, between 0 and 1
+    #[clap(long, default_value_t = 0.0, value_parser = parse_ratio, requires = "zkapp_commands")]
+    pub zkapp_ratio: f64,
+
+    /// Path to a JSON array of signed zkApp commands to send
+    #[clap(long)]
+    pub zkapp_commands: Option<PathBuf>,
+
+    /// Seconds to wait for the inclusion of the transactions after the last one was sent
+    #[clap(long, default_value_t = 300)]
+    pub inclusion_timeout: u64,
+
+    /// Also write the JSON report to this file
+    #[clap(long)]
+    pub report: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub network_id: NetworkId,
+
+    /// Specify log level
+    #[clap(short = 'l', long, default_value = "info")]
+    pub log_level: String,
+}
+
+fn parse_tps(tps: &str) -> Result<f64, String> {
+    match tps.parse::<f64>() {
+        Ok(tps) if tps > 0.0 && tps.is_finite() => Ok(tps),
+        _ => Err(format!("'{tps}' is not a positive number")),
+    }
+}
+
+fn parse_ratio(ratio: &str) -> Result<f64, String> {
+    match ratio.parse::<f64>() {
+        Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(ratio),
+        _ => Err(format!("'{ratio}' is not a number between 0 and 1")),
+    }
+}
+
+fn parse_amount(amount: &str) -> Result<u64, String> {
+    amount_dsl_to_nanomina(amount).map_err(|e| e.to_string())
+}
+
+#[derive(Subcommand)]
+pub enum ChaosCommand {
+    /// Kill and restart random nodes of a running network, planned from a seed
+    Run(ChaosRunArgs),
+    /// Run the actions of a chaos event file again, at the same times
+    Replay(ChaosReplayArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ChaosRunArgs {
+    /// Seed of the run, random by default
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// Role of the nodes to kill (repeatable)
+    #[clap(long = "role", value_enum, default_values = ["block-producer", "snark-worker", "seed"])]
+    pub roles: Vec<Role>,
+
+    /// Node to kill instead of the nodes of the roles (repeatable)
+    #[clap(long = "node")]
+    pub nodes: Vec<String>,
+
+    /// Duration of the run in seconds
+    #[clap(long, default_value_t = 600)]
+    pub duration: u64,
+
+    /// Seconds between two kills: N, uniform:MIN-MAX or exp:MEAN
+    #[clap(long, default_value = "uniform:30-120")]
+    pub interval: Distribution,
+
+    /// Seconds a killed node stays down: N, uniform:MIN-MAX or exp:MEAN
+    #[clap(long, default_value = "uniform:10-60")]
+    pub downtime: Distribution,
+
+    /// Maximum number of nodes down at once
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_down: u64,
+
+    /// Share of the restarts with fresh state, between 0 and 1
+    #[clap(long, default_value_t = 0.0, value_parser = parse_ratio)]
+    pub fresh_state_ratio: f64,
+
+    /// JSON file to log the events of the run to
+    #[clap(long, default_value = "chaos-events.json")]
+    pub events: PathBuf,
+
+    #[clap(flatten)]
+    pub network_id: NetworkId,
+
+    /// Specify log level
+    #[clap(short = 'l', long, default_value = "info")]
+    pub log_level: String,
+}
+
+#[derive(Args, Debug)]
+pub struct ChaosReplayArgs {
+    /// Event file of the run to replay
+    pub file: PathBuf,
+
+    /// JSON file to log the events of the replay to
+    #[clap(long, default_value = "chaos-replay-events.json")]
+    pub events: PathBuf,
+
+    /// Network identifier to replay on, the network of the run by default
+    #[clap(short, long)]
+    pub network_id: Option<String>,
+
+    /// Specify log level
+    #[clap(short = 'l', long, default_value = "info")]
+    pub log_level: String,
+}
+
+#[derive(Subcommand)]
+pub enum ScenarioCommand {
+    /// Run the steps of a scenario file and report their results
+    Run(RunScenarioArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct RunScenarioArgs {
+    /// Path to the (YAML) scenario file
+    pub file: PathBuf,
+
+    /// Network identifier to run the scenario on, the scenario's network by default
+    #[clap(short, long)]
+    pub network_id: Option<String>,
+
+    /// Also write the results as a JUnit XML report to this file
+    #[clap(long)]
+    pub junit: Option<PathBuf>,
+
+    /// Specify log level
+    #[clap(short = 'l', long, default_value = "info")]
+    pub log_level: String,
 }
 
 #[derive(Subcommand)]
@@ -219,10 +842,23 @@ macro_rules! node_id {
 log_level!(StartNetworkArgs);
 log_level!(CreateNetworkArgs);
 log_level!(NodeCommandArgs);
+log_level!(RunScenarioArgs);
+log_level!(LoadRunArgs);
+log_level!(ChaosRunArgs);
+log_level!(ChaosReplayArgs);
 
 network_id!(StartNetworkArgs);
 network_id!(CreateNetworkArgs);
 network_id!(NodeCommandArgs);
+network_id!(SnapshotArgs);
+network_id!(ExportArgs);
+network_id!(WaitArgs);
+network_id!(PartitionArgs);
+network_id!(UpgradeArgs);
+network_id!(ForkArgs);
+network_id!(TxArgs);
+network_id!(LoadRunArgs);
+network_id!(ChaosRunArgs);
 
 node_id!(NodeCommandArgs);
 
@@ -242,6 +878,12 @@ impl DefaultLogLevel for Command {
                 NodeCommand::Start(args) => args.node_args.log_level(),
                 NodeCommand::RunReplayer(args) => args.node_args.log_level(),
             },
+            Command::Topology(_) => "warn",
+            Command::Scenario(ScenarioCommand::Run(args)) => args.log_level(),
+            Command::Tx(_) => "warn",
+            Command::Load(LoadCommand::Run(args)) => args.log_level(),
+            Command::Chaos(ChaosCommand::Run(args)) => args.log_level(),
+            Command::Chaos(ChaosCommand::Replay(args)) => args.log_level(),
         }
     }
 }
@@ -282,6 +924,722 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_network_create_mina_repo() {
+        let args = ["minimina", "network", "create", "-t", "topology.json"];
+        let cli = Cli::parse_from(args.iter().chain(&["--mina-repo", "/src/mina"]));
+        match cli.command {
+            Command::Network(NetworkCommand::Create(args)) => {
+                assert_eq!(args.mina_repo, Some(std::path::PathBuf::from("/src/mina")));
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let without_topology = ["minimina", "network", "create", "--mina-repo", "/src/mina"];
+        assert!(Cli::try_parse_from(without_topology).is_err());
+    }
+
+    #[test]
+    fn test_network_create_ledger_spec() {
+        let cli = Cli::parse_from([
+            "minimina",
+            "network",
+            "create",
+            "--ledger-spec",
+            "spec.json",
+        ]);
+
+        match cli.command {
+            Command::Network(NetworkCommand::Create(args)) => {
+                assert_eq!(args.ledger_spec, Some(PathBuf::from("spec.json")));
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let result = Cli::try_parse_from([
+            "minimina",
+            "network",
+            "create",
+            "--ledger-spec",
+            "spec.json",
+            "-g",
+            "genesis_ledger.json",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_network_create_timing() {
+        let timing = [
+            "--initial-minimum-balance",
+            "1000mina",
+            "--cliff-time",
+            "PT86400S",
+            "--cliff-amount",
+            "100mina",
+            "--vesting-period",
+            "1",
+            "--vesting-increment",
+            "1mina",
+        ];
+        let cli = Cli::parse_from(["minimina", "network", "create"].iter().chain(&timing));
+
+        match cli.command {
+            Command::Network(NetworkCommand::Create(args)) => {
+                let spec = args.timing.timing_spec().unwrap();
+                assert_eq!(spec.cliff_time, Slots::Duration("PT86400S".to_string()));
+                assert_eq!(spec.vesting_period, Slots::Slots(1));
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        // all or nothing
+        let partial = ["minimina", "network", "create", "--cliff-time", "10"];
+        assert!(Cli::try_parse_from(partial).is_err());
+        let partial = ["minimina", "network", "create"].iter().chain(&timing[..8]);
+        assert!(Cli::try_parse_from(partial).is_err());
+        // not a duration
+        let invalid = ["minimina", "network", "create"]
+            .iter()
+            .chain(&timing[..3])
+            .chain(&["1d"])
+            .chain(&timing[4..]);
+        assert!(Cli::try_parse_from(invalid).is_err());
+        // no timing for a given ledger
+        let given = ["minimina", "network", "create", "-g", "ledger.json"]
+            .iter()
+            .chain(&timing);
+        assert!(Cli::try_parse_from(given).is_err());
+    }
+
+    #[test]
+    fn test_network_create_protocol_constants() {
+        let cli = Cli::parse_from([
+            "minimina",
+            "network",
+            "create",
+            "--k",
+            "10",
+            "--slots-per-epoch",
+            "48",
+            "--block-window-duration-ms",
+            "20000",
+            "--fork-state-hash",
+            "3NKSiqFZQmAS12U8qeX4KNo8b4199spwNh7mrSs4Ci1Vacpfix2Q",
+            "--fork-blockchain-length",
+            "100",
+            "--fork-global-slot-since-genesis",
+            "120",
+        ]);
+
+        match cli.command {
+            Command::Network(NetworkCommand::Create(args)) => {
+                let constants = args.protocol.protocol_constants();
+                assert_eq!(constants.k, Some(10));
+                assert_eq!(constants.slots_per_epoch, Some(48));
+                assert_eq!(constants.block_window_duration_ms(), 20_000);
+                assert_eq!(constants.delta, None);
+                assert_eq!(constants.fork.unwrap().global_slot_since_genesis, 120);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = ["minimina", "network", "create", "--k", "0"];
+        assert!(Cli::try_parse_from(args).is_err());
+        let args = ["minimina", "network", "create", "--fork-state-hash", "3N"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_network_create_ledger_from() {
+        let cli = Cli::parse_from([
+            "minimina",
+            "network",
+            "create",
+            "--ledger-from",
+            "staking_ledger.json",
+            "--ledger-sample",
+            "100",
+            "--replace-key",
+            "B62qa",
+            "--replace-key",
+            "B62qb",
+        ]);
+
+        match cli.command {
+            Command::Network(NetworkCommand::Create(args)) => {
+                assert_eq!(args.ledger_subset(), Subset::Sample(100));
+                assert_eq!(args.replace_key, ["B62qa", "B62qb"]);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = ["minimina", "network", "create", "--ledger-top", "10"];
+        assert!(Cli::try_parse_from(args).is_err());
+        let args = [
+            "minimina",
+            "network",
+            "create",
+            "--ledger-from",
+            "staking_ledger.json",
+            "--ledger-spec",
+            "spec.json",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_network_snapshot_restore_commands() {
+        let cli = Cli::parse_from(["minimina", "network", "snapshot", "--name", "epoch-2"]);
+        match cli.command {
+            Command::Network(NetworkCommand::Snapshot(args)) => {
+                assert_eq!(args.name, "epoch-2");
+                assert_eq!(args.network_id(), "default");
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = ["minimina", "network", "restore", "--name", "epoch-2"];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Restore(args)) => {
+                assert_eq!(args.name, "epoch-2");
+                assert_eq!(args.network_id, None);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = [
+            "minimina", "network", "restore", "--name", "a", "-n", "copy",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Restore(args)) => {
+                assert_eq!(args.network_id.as_deref(), Some("copy"));
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+    }
+
+    #[test]
+    fn test_network_clone_command() {
+        let args = ["minimina", "network", "clone", "--from", "a", "--to", "b"];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Clone(args)) => {
+                assert_eq!(args.from, "a");
+                assert_eq!(args.to, "b");
+                assert!(!args.reset_genesis_timestamp);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = [
+            "minimina",
+            "network",
+            "clone",
+            "--from",
+            "a",
+            "--to",
+            "b",
+            "--reset-genesis-timestamp",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Clone(args)) => {
+                assert!(args.reset_genesis_timestamp);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = ["minimina", "network", "clone", "--from", "a"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_network_export_import_commands() {
+        let args = ["minimina", "network", "export", "-n", "test"];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Export(args)) => {
+                assert_eq!(args.network_id(), "test");
+                assert_eq!(args.output(), PathBuf::from("test.tar.gz"));
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = ["minimina", "network", "export", "-o", "/tmp/bundle.tar.gz"];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Export(args)) => {
+                assert_eq!(args.output(), PathBuf::from("/tmp/bundle.tar.gz"));
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = ["minimina", "network", "import", "--file", "test.tar.gz"];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Import(args)) => {
+                assert_eq!(args.file, PathBuf::from("test.tar.gz"));
+                assert_eq!(args.network_id, None);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        assert!(Cli::try_parse_from(["minimina", "network", "import"]).is_err());
+    }
+
+    #[test]
+    fn test_network_wait_command() {
+        let args = [
+            "minimina",
+            "network",
+            "wait",
+            "--until",
+            "height>=5",
+            "--node",
+            "mina-bp-1",
+            "--node",
+            "mina-bp-2",
+            "-n",
+            "test",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Wait(args)) => {
+                assert_eq!(args.until, Condition::Height(5));
+                assert_eq!(args.nodes, ["mina-bp-1", "mina-bp-2"]);
+                assert_eq!(args.timeout, 600);
+                assert_eq!(args.network_id(), "test");
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = [
+            "minimina",
+            "network",
+            "wait",
+            "--until",
+            "synced",
+            "--timeout",
+            "60",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Wait(args)) => {
+                assert_eq!(args.until, Condition::Synced);
+                assert!(args.nodes.is_empty());
+                assert_eq!(args.timeout, 60);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = ["minimina", "network", "wait", "--until", "height>5"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_network_partition_commands() {
+        let args = [
+            "minimina",
+            "network",
+            "partition",
+            "--groups",
+            "mina-bp-1,mina-seed-1|mina-bp-2",
+            "--link",
+            "mina-bp-1:mina-seed-1,latency=200ms,loss=5%",
+            "-n",
+            "test",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Partition(args)) => {
+                let groups = args.groups.as_ref().unwrap();
+                assert_eq!(groups.0.len(), 2);
+                assert_eq!(groups.0[1], ["mina-bp-2"]);
+                assert_eq!(args.links.len(), 1);
+                assert_eq!(args.links[0].latency_ms, Some(200));
+                assert_eq!(args.network_id(), "test");
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = [
+            "minimina",
+            "network",
+            "partition",
+            "--link",
+            "mina-bp-1:mina-bp-2,bandwidth=1mbit",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Partition(args)) => {
+                assert!(args.groups.is_none());
+                assert_eq!(args.links[0].bandwidth_kbit, Some(1_000));
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        assert!(Cli::try_parse_from(["minimina", "network", "partition"]).is_err());
+        let args = ["minimina", "network", "partition", "--groups", "a,b"];
+        assert!(Cli::try_parse_from(args).is_err());
+
+        match Cli::parse_from(["minimina", "network", "heal", "-n", "test"]).command {
+            Command::Network(NetworkCommand::Heal(args)) => assert_eq!(args.network_id, "test"),
+            _ => panic!("Unexpected command parsed"),
+        }
+    }
+
+    #[test]
+    fn test_network_upgrade_command() {
+        let args = [
+            "minimina",
+            "network",
+            "upgrade",
+            "--image",
+            "mina-daemon:3.0.1",
+            "--role",
+            "block_producer",
+            "--role",
+            "seed",
+            "--batch",
+            "2",
+            "--wait-synced",
+            "-n",
+            "test",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Upgrade(args)) => {
+                assert_eq!(args.image.as_deref(), Some("mina-daemon:3.0.1"));
+                assert_eq!(args.roles, [Role::BlockProducer, Role::Seed]);
+                assert_eq!(args.batch, 2);
+                assert!(args.wait_synced);
+                assert_eq!(args.timeout, 600);
+                assert_eq!(args.network_id(), "test");
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = ["minimina", "network", "upgrade", "--bin", "/opt/mina/bin"];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Upgrade(args)) => {
+                assert_eq!(args.bin, Some(PathBuf::from("/opt/mina/bin")));
+                assert!(args.roles.is_empty());
+                assert_eq!(args.batch, 1);
+                assert!(!args.wait_synced);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        for args in [
+            ["minimina", "network", "upgrade"].as_slice(),
+            &[
+                "minimina", "network", "upgrade", "--image", "x", "--batch", "0",
+            ],
+            &[
+                "minimina", "network", "upgrade", "--image", "x", "--bin", "y",
+            ],
+            &[
+                "minimina",
+                "network",
+                "upgrade",
+                "--image",
+                "x",
+                "--timeout",
+                "5",
+            ],
+            &[
+                "minimina",
+                "network",
+                "upgrade",
+                "--image",
+                "x",
+                "--role",
+                "seed",
+                "--node",
+                "mina-bp-1",
+            ],
+        ] {
+            assert!(Cli::try_parse_from(args).is_err());
+        }
+    }
+
+    #[test]
+    fn test_network_fork_command() {
+        let args = [
+            "minimina",
+            "network",
+            "fork",
+            "--successor",
+            "next",
+            "--slot",
+            "100",
+            "--image",
+            "mina-daemon:fork",
+            "-n",
+            "test",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Network(NetworkCommand::Fork(args)) => {
+                assert_eq!(args.successor, "next");
+                assert_eq!(args.node, None);
+                assert_eq!(args.slot, Some(100));
+                assert_eq!(args.timeout, 3600);
+                assert_eq!(args.image.as_deref(), Some("mina-daemon:fork"));
+                assert_eq!(args.network_id(), "test");
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        for args in [
+            ["minimina", "network", "fork"].as_slice(),
+            &[
+                "minimina",
+                "network",
+                "fork",
+                "--successor",
+                "next",
+                "--timeout",
+                "5",
+            ],
+            &[
+                "minimina",
+                "network",
+                "fork",
+                "--successor",
+                "next",
+                "--image",
+                "x",
+                "--bin",
+                "y",
+            ],
+        ] {
+            assert!(Cli::try_parse_from(args).is_err());
+        }
+    }
+
+    #[test]
+    fn test_tx_commands() {
+        let args = [
+            "minimina",
+            "tx",
+            "payment",
+            "--from",
+            "mina-bp-1",
+            "--to",
+            "mina-bp-2",
+            "--amount",
+            "1.5mina",
+            "--fee",
+            "0.01mina",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Tx(TxCommand::Payment(args)) => {
+                assert_eq!(args.amount, 1_500_000_000);
+                assert_eq!(args.tx.fee, 10_000_000);
+                assert_eq!(args.tx.from, "mina-bp-1");
+                assert_eq!(args.tx.node, None);
+                assert!(!args.tx.wait);
+                assert_eq!(args.tx.network_id(), "default");
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = [
+            "minimina",
+            "tx",
+            "delegate",
+            "--from",
+            "whale-0",
+            "--to",
+            "mina-bp-1",
+            "--fee",
+            "10000000nanomina",
+            "--node",
+            "mina-seed-1",
+            "--wait",
+            "--timeout",
+            "60",
+            "-n",
+            "test",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Tx(TxCommand::Delegate(args)) => {
+                assert_eq!(args.fee, 10_000_000);
+                assert_eq!(args.node.as_deref(), Some("mina-seed-1"));
+                assert!(args.wait);
+                assert_eq!(args.timeout, 60);
+                assert_eq!(args.network_id(), "test");
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = [
+            "minimina", "tx", "payment", "--from", "a", "--to", "b", "--amount", "1.5", "--fee",
+            "0.01mina",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_load_run_command() {
+        let cli = Cli::parse_from(["minimina", "load", "run"]);
+        assert_eq!(cli.command.log_level(), "info");
+        match cli.command {
+            Command::Load(LoadCommand::Run(args)) => {
+                assert_eq!(args.tps, 1.0);
+                assert_eq!(args.duration, 60);
+                assert!(args.senders.is_empty());
+                assert_eq!(args.amount, 1_000_000);
+                assert_eq!(args.fee, 10_000_000);
+                assert_eq!(args.zkapp_ratio, 0.0);
+                assert_eq!(args.network_id(), "default");
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = [
+            "minimina",
+            "load",
+            "run",
+            "--tps",
+            "2.5",
+            "--duration",
+            "30",
+            "--sender",
+            "whale-0",
+            "--sender",
+            "whale-1",
+            "--node",
+            "mina-bp-1",
+            "--zkapp-ratio",
+            "0.2",
+            "--zkapp-commands",
+            "zkapps.json",
+            "--report",
+            "load.json",
+            "-n",
+            "test",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Load(LoadCommand::Run(args)) => {
+                assert_eq!(args.tps, 2.5);
+                assert_eq!(args.senders, ["whale-0", "whale-1"]);
+                assert_eq!(args.nodes, ["mina-bp-1"]);
+                assert_eq!(args.zkapp_ratio, 0.2);
+                assert_eq!(args.zkapp_commands, Some(PathBuf::from("zkapps.json")));
+                assert_eq!(args.report, Some(PathBuf::from("load.json")));
+                assert_eq!(args.network_id(), "test");
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        for args in [
+            ["minimina", "load", "run", "--tps", "0"].as_slice(),
+            &["minimina", "load", "run", "--zkapp-ratio", "0.5"],
+            &[
+                "minimina",
+                "load",
+                "run",
+                "--zkapp-ratio",
+                "2",
+                "--zkapp-commands",
+                "z.json",
+            ],
+        ] {
+            assert!(Cli::try_parse_from(args).is_err());
+        }
+    }
+
+    #[test]
+    fn test_chaos_commands() {
+        let args = ["minimina", "chaos", "run", "--seed", "42", "-n", "test"];
+        let cli = Cli::parse_from(args);
+        assert_eq!(cli.command.log_level(), "info");
+        match cli.command {
+            Command::Chaos(ChaosCommand::Run(args)) => {
+                assert_eq!(args.seed, Some(42));
+                assert_eq!(
+                    args.roles,
+                    [Role::BlockProducer, Role::SnarkWorker, Role::Seed]
+                );
+                assert_eq!(args.interval.to_string(), "uniform:30-120");
+                assert_eq!(args.max_down, 1);
+                assert_eq!(args.events, PathBuf::from("chaos-events.json"));
+                assert_eq!(args.network_id(), "test");
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = [
+            "minimina",
+            "chaos",
+            "run",
+            "--role",
+            "seed",
+            "--interval",
+            "exp:60",
+            "--downtime",
+            "30",
+            "--max-down",
+            "2",
+            "--fresh-state-ratio",
+            "0.25",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Chaos(ChaosCommand::Run(args)) => {
+                assert_eq!(args.seed, None);
+                assert_eq!(args.roles, [Role::Seed]);
+                assert_eq!(args.interval, Distribution::Exponential { mean: 60.0 });
+                assert_eq!(args.downtime, Distribution::Fixed(30.0));
+                assert_eq!(args.max_down, 2);
+                assert_eq!(args.fresh_state_ratio, 0.25);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        for args in [
+            ["minimina", "chaos", "run", "--role", "archive"].as_slice(),
+            &["minimina", "chaos", "run", "--max-down", "0"],
+            &["minimina", "chaos", "run", "--interval", "uniform:60-10"],
+            &["minimina", "chaos", "run", "--fresh-state-ratio", "2"],
+        ] {
+            assert!(Cli::try_parse_from(args).is_err());
+        }
+
+        match Cli::parse_from(["minimina", "chaos", "replay", "chaos-events.json"]).command {
+            Command::Chaos(ChaosCommand::Replay(args)) => {
+                assert_eq!(args.file, PathBuf::from("chaos-events.json"));
+                assert_eq!(args.events, PathBuf::from("chaos-replay-events.json"));
+                assert_eq!(args.network_id, None);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+    }
+
+    #[test]
+    fn test_scenario_run_command() {
+        let args = ["minimina", "scenario", "run", "payments.yaml"];
+        let cli = Cli::parse_from(args);
+        assert_eq!(cli.command.log_level(), "info");
+        match cli.command {
+            Command::Scenario(ScenarioCommand::Run(args)) => {
+                assert_eq!(args.file, PathBuf::from("payments.yaml"));
+                assert_eq!(args.network_id, None);
+                assert_eq!(args.junit, None);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = [
+            "minimina",
+            "scenario",
+            "run",
+            "payments.yaml",
+            "-n",
+            "ci",
+            "--junit",
+            "report.xml",
+        ];
+        match Cli::parse_from(args).command {
+            Command::Scenario(ScenarioCommand::Run(args)) => {
+                assert_eq!(args.network_id.as_deref(), Some("ci"));
+                assert_eq!(args.junit, Some(PathBuf::from("report.xml")));
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+    }
+
     #[test]
     fn test_network_delete_command() {
         let args = vec!["minimina", "network", "delete", "--network-id", "test"];
@@ -322,6 +1680,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_network_start_supervise_command() {
+        let args = vec![
+            "minimina",
+            "network",
+            "start",
+            "--network-id",
+            "test",
+            "--supervise",
+            "--detach",
+            "--max-restarts",
+            "3",
+        ];
+
+        let cli = Cli::parse_from(args);
+
+        match cli.command {
+            Command::Network(NetworkCommand::Start(args)) => {
+                assert!(args.supervise);
+                assert!(args.detach);
+                assert_eq!(args.max_restarts, 3);
+                assert_eq!(args.restart_backoff_secs, 2);
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+
+        let args = vec!["minimina", "network", "start", "--detach"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
     #[test]
     fn test_network_stop_command() {
         let args = vec!["minimina", "network", "stop", "--network-id", "test"];
@@ -336,6 +1724,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_topology_validate_command() {
+        let args = vec!["minimina", "topology", "validate", "/path/to/topology.json"];
+
+        let cli = Cli::parse_from(args);
+
+        match cli.command {
+            Command::Topology(TopologyCommand::Validate(args)) => {
+                assert_eq!(args.file, PathBuf::from("/path/to/topology.json"));
+            }
+            _ => panic!("Unexpected command parsed"),
+        }
+    }
+
     #[test]
     fn test_node_start_command() {
         let args = vec!["minimina", "node", "start", "--node-id", "test"];
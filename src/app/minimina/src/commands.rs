@@ -0,0 +1,1189 @@
+//! # Commands Module
+//!
+//! Network and node command flows written once against the [`Backend`] trait,
+//! so they behave the same in docker and native mode.
+//! Printing results and exiting on errors is left to `main`.
+
+use crate::{
+    backend::{self, Backend},
+    bundle,
+    cli::ExecutionMode,
+    directory_manager::{DirectoryManager, SERVICES_JSON},
+    docker::manager::ContainerState,
+    faults::Faults,
+    genesis_ledger::{self, default},
+    native::port_manager,
+    output::{self, network},
+    service::{ServiceConfig, ServiceType},
+    snapshot::{self, Manifest, Staging},
+};
+use log::{error, info, warn};
+use std::{
+    collections::HashSet,
+    io::{self, Result},
+    path::Path,
+};
+
+/// Prepares the backend for the generated `services` and saves the network's info.
+/// Services binding the same port are rejected before anything is created.
+pub fn create_network(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+    services: &[ServiceConfig],
+) -> Result<network::Create> {
+    port_manager::check_port_conflicts(services)?;
+    let mut services = services.to_vec();
+    ServiceConfig::name_archive_dbs(&mut services, network_id);
+    let services = services.as_slice();
+    backend.create(services)?;
+
+    // for each archive node we need to:
+    //  - create input file for replayer (for run-replayer command)
+    //  - create database and apply schema scripts
+    for archive_node in ServiceConfig::get_archive_nodes(services) {
+        default::LedgerGenerator::generate_replayer_input(
+            &directory_manager.network_path(network_id),
+            &archive_node.service_name,
+        )?;
+        backend.setup_archive(archive_node)?;
+    }
+
+    // generate network.json and services.json
+    if let Err(e) = directory_manager.save_network_info(network_id, services) {
+        error!("Error generating network.json: {e}")
+    }
+
+    if let Err(e) = directory_manager.save_services_info(network_id, services) {
+        error!("Error generating services.json: {e}")
+    }
+
+    output::generate_network_info(services, network_id)
+}
+
+/// Starts all services of the network, returning the backend's output
+pub fn start_network(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+) -> Result<String> {
+    let services = directory_manager.get_services_info(network_id)?;
+    backend.start_all(&services)
+}
+
+/// Stops all services of the network.
+/// Network faults go away with the services' network namespaces, so their record is cleared.
+pub fn stop_network(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+) -> Result<()> {
+    backend.stop_all()?;
+    directory_manager.save_faults(network_id, &Faults::default())
+}
+
+pub fn network_status(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+) -> Result<network::Status> {
+    let services = directory_manager.get_services_info(network_id)?;
+    let mut status = backend.status(network_id, &services)?;
+    status.network_dir = directory_manager
+        .network_path(network_id)
+        .display()
+        .to_string();
+    status.faults = directory_manager.faults(network_id)?;
+    Ok(status)
+}
+
+/// Applies `faults` to the running services of the network, replacing the active faults
+pub fn partition_network(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+    faults: &Faults,
+) -> Result<network::Faults> {
+    let services = directory_manager.get_services_info(network_id)?;
+    let names: Vec<String> = services.iter().map(|s| s.service_name.clone()).collect();
+    faults.validate(&names)?;
+
+    // services the active faults apply to are healed, even when left out of the new ones
+    let active = directory_manager.faults(network_id)?.unwrap_or_default();
+    let affected: Vec<ServiceConfig> = services
+        .into_iter()
+        .filter(|service| {
+            let name = service.service_name.as_str();
+            faults.services().contains(name) || active.services().contains(name)
+        })
+        .collect();
+    backend.apply_faults(faults, &affected)?;
+    directory_manager.save_faults(network_id, faults)?;
+    Ok(network::Faults {
+        network_id: network_id.to_string(),
+        faults: faults.clone(),
+    })
+}
+
+/// Removes the network faults from all running services of the network,
+/// including those of a partition which failed half way
+pub fn heal_network(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+) -> Result<network::Faults> {
+    let services = directory_manager.get_services_info(network_id)?;
+    let faults = Faults::default();
+    backend.apply_faults(&faults, &services)?;
+    directory_manager.save_faults(network_id, &faults)?;
+    Ok(network::Faults {
+        network_id: network_id.to_string(),
+        faults,
+    })
+}
+
+/// Tears down the network's services before deleting its directory
+pub fn delete_network(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+) -> Result<()> {
+    backend.destroy()?;
+    directory_manager.delete_network_directory(network_id)
+}
+
+/// Stops the network and saves its directory and backend state as snapshot `name`
+pub fn snapshot_network(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+    name: &str,
+    mode: &ExecutionMode,
+) -> Result<network::Snapshot> {
+    let snapshot_path = directory_manager.snapshot_path(name);
+    if snapshot_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Snapshot '{name}' already exists"),
+        ));
+    }
+
+    let services = directory_manager.get_services_info(network_id)?;
+    info!("Stopping network '{network_id}' to take snapshot '{name}'.");
+    backend.stop_all()?;
+
+    let staging = Staging::new()?;
+    snapshot::copy_dir(
+        &directory_manager.network_path(network_id),
+        &staging.network_dir(),
+    )?;
+    let state = backend.save_state(&services, &staging.state_dir())?;
+    let manifest = Manifest {
+        name: name.to_string(),
+        network_id: network_id.to_string(),
+        mode: mode.clone(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        minimina_version: env!("CARGO_PKG_VERSION").to_string(),
+        services: services.into_iter().map(|s| s.service_name).collect(),
+        state,
+    };
+
+    std::fs::create_dir_all(snapshot_path.parent().expect("Snapshots have a directory"))?;
+    staging.pack(&manifest, &snapshot_path)?;
+
+    Ok(network::Snapshot {
+        network_id: network_id.to_string(),
+        name: name.to_string(),
+        file: snapshot_path.display().to_string(),
+    })
+}
+
+/// Recreates the network of an unpacked snapshot as `network_id`, which must not exist.
+/// The services of a network restored under a new id are pointed at it.
+pub fn restore_network(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    staging: &Staging,
+    manifest: &Manifest,
+    network_id: &str,
+    mode: &ExecutionMode,
+) -> Result<network::Restore> {
+    if &manifest.mode != mode {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Snapshot '{}' was taken in {:?} mode",
+                manifest.name, manifest.mode
+            ),
+        ));
+    }
+    if directory_manager.network_path_exists(network_id) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Network '{network_id}' already exists"),
+        ));
+    }
+
+    let network_path = directory_manager.network_path(network_id);
+    snapshot::copy_dir(&staging.network_dir(), &network_path)?;
+
+    let mut services = directory_manager.get_services_info(network_id)?;
+    if manifest.network_id != network_id {
+        info!(
+            "Restoring network '{}' as '{network_id}'.",
+            manifest.network_id
+        );
+        let peer_list_file = directory_manager.peer_list_file(network_id);
+        ServiceConfig::rename_network(
+            &mut services,
+            &manifest.network_id,
+            network_id,
+            &peer_list_file,
+        )?;
+        if peer_list_file.exists() {
+            directory_manager
+                .create_peer_list_file(network_id, &ServiceConfig::get_seeds(&services))?;
+        }
+        // docker restores the network's own postgres, but native archive databases
+        // are in the local postgres, next to those of the snapshot's network
+        if mode == &ExecutionMode::Native {
+            ServiceConfig::name_archive_dbs(&mut services, network_id);
+        }
+        directory_manager.save_services_info(network_id, &services)?;
+        directory_manager.save_network_info(network_id, &services)?;
+    }
+
+    backend.create(&services)?;
+    backend.load_state(&services, &staging.state_dir(), &manifest.state)?;
+
+    Ok(network::Restore {
+        network_id: network_id.to_string(),
+        name: manifest.name.clone(),
+    })
+}
+
+/// Creates network `to` from the keys, genesis ledger and topology of network `from`.
+/// The services' ports are shifted clear of those of every other network.
+pub fn clone_network(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    from: &str,
+    to: &str,
+    reset_genesis_timestamp: bool,
+) -> Result<network::Create> {
+    let services = copy_network(directory_manager, from, to)?;
+    if reset_genesis_timestamp {
+        let genesis_ledger_path = directory_manager.genesis_ledger_path(to);
+        directory_manager.overwrite_genesis_timestamp(to, &genesis_ledger_path)?;
+    }
+
+    create_network(backend, directory_manager, to, &services)
+}
+
+/// Copies the inputs of network `from` to the directory of network `to`, which must not exist,
+/// returning the services of `to` with ports shifted clear of those of every other network
+pub fn copy_network(
+    directory_manager: &DirectoryManager,
+    from: &str,
+    to: &str,
+) -> Result<Vec<ServiceConfig>> {
+    if directory_manager.network_path_exists(to) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Network '{to}' already exists"),
+        ));
+    }
+    let mut services = directory_manager.get_services_info(from)?;
+
+    let used_ports: HashSet<u16> = directory_manager
+        .list_network_directories()?
+        .iter()
+        .filter_map(|network_id| directory_manager.get_services_info(network_id).ok())
+        .flatten()
+        .map(|service| port_manager::service_ports(&service))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    let offset = port_manager::free_port_offset(&services, &used_ports)?;
+    info!("Copying network '{from}' as '{to}' with ports shifted by {offset}.");
+    for service in services.iter_mut() {
+        service.shift_ports(offset)?;
+    }
+
+    directory_manager.copy_network_inputs(from, &directory_manager.network_path(to))?;
+    let peer_list_file = directory_manager.peer_list_file(to);
+    ServiceConfig::rename_network(&mut services, from, to, &peer_list_file)?;
+    if directory_manager.peer_list_file(from).exists() {
+        directory_manager.create_peer_list_file(to, &ServiceConfig::get_seeds(&services))?;
+    }
+    Ok(services)
+}
+
+/// Saves the inputs of the network as a bundle at `path`, with paths relative
+/// to the network directory so that it can be imported anywhere
+pub fn export_network(
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+    path: &Path,
+) -> Result<network::Export> {
+    let mut services = directory_manager.get_services_info(network_id)?;
+    let staging = Staging::new()?;
+    directory_manager.copy_network_inputs(network_id, &staging.network_dir())?;
+
+    bundle::relativize_paths(&mut services, &directory_manager.network_path(network_id));
+    std::fs::write(
+        staging.network_dir().join(SERVICES_JSON),
+        serde_json::to_string_pretty(&services)?,
+    )?;
+    staging.pack(&bundle::Manifest::new(network_id, &services), path)?;
+
+    Ok(network::Export {
+        network_id: network_id.to_string(),
+        file: path.display().to_string(),
+    })
+}
+
+/// Creates network `network_id`, which must not exist, from an unpacked bundle
+pub fn import_network(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    staging: &Staging,
+    manifest: &bundle::Manifest,
+    network_id: &str,
+) -> Result<network::Create> {
+    if directory_manager.network_path_exists(network_id) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Network '{network_id}' already exists"),
+        ));
+    }
+    if manifest.minimina_version != env!("CARGO_PKG_VERSION") {
+        warn!(
+            "Bundle was exported by minimina {}, this is {}.",
+            manifest.minimina_version,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+    info!("Bundle uses images: {}", manifest.images.join(", "));
+
+    let network_path = directory_manager.network_path(network_id);
+    snapshot::copy_dir(&staging.network_dir(), &network_path)?;
+    let mut services = directory_manager.get_services_info(network_id)?;
+    bundle::resolve_paths(&mut services, &network_path);
+
+    let peer_list_file = directory_manager.peer_list_file(network_id);
+    if manifest.network_id != network_id {
+        ServiceConfig::rename_network(
+            &mut services,
+            &manifest.network_id,
+            network_id,
+            &peer_list_file,
+        )?;
+    }
+    if services.iter().any(|s| s.peer_list_file.is_some()) {
+        directory_manager
+            .create_peer_list_file(network_id, &ServiceConfig::get_seeds(&services))?;
+    }
+
+    create_network(backend, directory_manager, network_id, &services)
+}
+
+/// Starts a single node, optionally with fresh state and imported accounts,
+/// returning the backend's output
+pub fn start_node(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    node_id: &str,
+    network_id: &str,
+    fresh_state: bool,
+    import_accounts: bool,
+) -> Result<String> {
+    let service = find_service(directory_manager, node_id, network_id)?;
+
+    let mut running = match backend.service_state(node_id)? {
+        Some(ContainerState::Running) => {
+            if !fresh_state {
+                warn!("Node '{node_id}' is already running in network '{network_id}'.");
+            }
+            true
+        }
+        Some(ContainerState::Created) => {
+            info!("Starting node '{node_id}' in network '{network_id}' for the first time.");
+            false
+        }
+        Some(state) => {
+            info!("Node '{node_id}' is {state} in network '{network_id}'.");
+            false
+        }
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Node '{node_id}' does not exist in network '{network_id}'."),
+            ))
+        }
+    };
+
+    if fresh_state {
+        info!("Starting node '{node_id}' in network '{network_id}' with fresh state.");
+        backend.reset_service(&service)?;
+        running = false;
+    }
+
+    if import_accounts {
+        warn!("Importing accounts for node '{node_id}' in network '{network_id}'. This can take a moment...");
+        for account_file in directory_manager.get_network_keypair_files(network_id)? {
+            backend
+                .import_account(&service, &account_file)
+                .map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!(
+                            "Failed to import account from file '{account_file}' \
+                             for node '{node_id}' on network '{network_id}': {e}"
+                        ),
+                    )
+                })?;
+            info!(
+                "Successfully imported account from file '{account_file}' \
+                 for node '{node_id}' on network '{network_id}'",
+            );
+        }
+    }
+
+    if running {
+        Ok(String::new())
+    } else {
+        let output = backend.start_service(&service)?;
+        reapply_faults(backend, directory_manager, network_id, &[node_id])?;
+        Ok(output)
+    }
+}
+
+/// Stops node `node_id`, failing with `NotFound` if the network has no such node
+pub fn stop_node(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    node_id: &str,
+    network_id: &str,
+) -> Result<String> {
+    let services = directory_manager.get_services_info(network_id)?;
+    if !services
+        .iter()
+        .any(|service| service.service_name == node_id)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Node '{node_id}' does not exist in network '{network_id}'."),
+        ));
+    }
+    backend.stop_service(node_id)
+}
+
+/// Re-applies the network's recorded faults once `restarted` services are back.
+/// A restarted service has a new network namespace without its faults, and maybe a new IP
+/// address which the rules of its peers must follow, so every service of the faults is
+/// updated.
+pub fn reapply_faults(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+    restarted: &[&str],
+) -> Result<()> {
+    let Some(faults) = directory_manager.faults(network_id)? else {
+        return Ok(());
+    };
+    let names = faults.services();
+    if !restarted.iter().any(|name| names.contains(name)) {
+        return Ok(());
+    }
+    info!("Re-applying the network faults of network '{network_id}'.");
+    let affected: Vec<ServiceConfig> = directory_manager
+        .get_services_info(network_id)?
+        .into_iter()
+        .filter(|service| names.contains(service.service_name.as_str()))
+        .collect();
+    backend.apply_faults(&faults, &affected).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!(
+                "Failed to re-apply the network faults, run 'network partition' again \
+                 or 'network heal': {e}"
+            ),
+        )
+    })
+}
+
+pub fn node_logs(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    node_id: &str,
+    network_id: &str,
+) -> Result<String> {
+    let service = find_service(directory_manager, node_id, network_id)?;
+    backend.logs(&service)
+}
+
+/// Dumps the database of archive node `node_id` as SQL inserts
+pub fn dump_archive_data(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    node_id: &str,
+    network_id: &str,
+) -> Result<String> {
+    let archive_node = find_archive_node(directory_manager, node_id, network_id)?;
+    let output = backend::ensure_success(backend.exec(
+        "postgres",
+        &[
+            "pg_dump",
+            "--insert",
+            "-U",
+            "postgres",
+            &archive_node.archive_db(),
+        ],
+    ))?;
+    Ok(String::from_utf8_lossy(&output.stdout).into())
+}
+
+/// Runs the replayer of archive node `node_id` from `start_slot` since genesis,
+/// returning its logs
+pub fn run_replayer(
+    backend: &dyn Backend,
+    directory_manager: &DirectoryManager,
+    node_id: &str,
+    network_id: &str,
+    start_slot: u64,
+) -> Result<String> {
+    let archive_node = find_archive_node(directory_manager, node_id, network_id)?;
+    genesis_ledger::set_slot_since_genesis(
+        &directory_manager.network_path(network_id),
+        node_id,
+        start_slot,
+    )
+    .map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to set slot since genesis to '{start_slot}': {e}"),
+        )
+    })?;
+    let output = backend::ensure_success(backend.run_replayer(&archive_node))?;
+    Ok(String::from_utf8_lossy(&output.stdout).into())
+}
+
+fn find_archive_node(
+    directory_manager: &DirectoryManager,
+    node_id: &str,
+    network_id: &str,
+) -> Result<ServiceConfig> {
+    directory_manager
+        .get_services_info(network_id)?
+        .into_iter()
+        .find(|service| {
+            service.service_type == ServiceType::ArchiveNode && service.service_name == node_id
+        })
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Node '{node_id}' is not an archive node in '{network_id}' network."),
+            )
+        })
+}
+
+/// Looks up `node_id` in the network's services.
+/// Nodes which are not services of the topology, e.g. postgres, get a bare config.
+fn find_service(
+    directory_manager: &DirectoryManager,
+    node_id: &str,
+    network_id: &str,
+) -> Result<ServiceConfig> {
+    let services = directory_manager.get_services_info(network_id)?;
+    Ok(services
+        .into_iter()
+        .find(|service| service.service_name == node_id)
+        .unwrap_or_else(|| ServiceConfig {
+            service_name: node_id.to_string(),
+            ..Default::default()
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::mock::{service, MockBackend},
+        directory_manager::NETWORK_KEYPAIRS,
+    };
+    use std::{fs, path::PathBuf};
+    use tempdir::TempDir;
+
+    fn setup_network(tempdir: &TempDir, services: &[ServiceConfig]) -> DirectoryManager {
+        let directory_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        directory_manager.generate_dir_structure("test").unwrap();
+        directory_manager
+            .save_services_info("test", services)
+            .unwrap();
+        directory_manager
+    }
+
+    #[test]
+    fn test_create_network_with_archives() {
+        let tempdir =
+            TempDir::new("test_create_network").expect("Cannot create temporary directory");
+        let directory_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        directory_manager.generate_dir_structure("test").unwrap();
+        fs::write(
+            directory_manager.genesis_ledger_path("test"),
+            r#"{"genesis": {"genesis_state_timestamp": "2024-01-01T00:00:00Z"},
+                "ledger": {"accounts": []}}"#,
+        )
+        .unwrap();
+        let services = vec![
+            service("mina-seed-1", ServiceType::Seed),
+            service("mina-archive-1", ServiceType::ArchiveNode),
+            service("mina-archive-2", ServiceType::ArchiveNode),
+        ];
+        let backend = MockBackend::default();
+
+        let create = create_network(&backend, &directory_manager, "test", &services).unwrap();
+
+        assert_eq!(create.nodes.len(), 3);
+        assert_eq!(
+            backend.calls(),
+            [
+                "create 3",
+                "setup_archive mina-archive-1",
+                "setup_archive mina-archive-2"
+            ]
+        );
+        assert_eq!(
+            directory_manager.get_services_info("test").unwrap().len(),
+            3
+        );
+        for archive in ["mina-archive-1", "mina-archive-2"] {
+            assert!(directory_manager
+                .network_path("test")
+                .join(format!("{archive}_replayer_input.json"))
+                .exists());
+        }
+    }
+
+    #[test]
+    fn test_archive_node_commands() {
+        let tempdir =
+            TempDir::new("test_archive_commands").expect("Cannot create temporary directory");
+        let mut services = vec![
+            service("mina-seed-1", ServiceType::Seed),
+            service("mina-archive", ServiceType::ArchiveNode),
+        ];
+        ServiceConfig::name_archive_dbs(&mut services, "test");
+        let directory_manager = setup_network(&tempdir, &services);
+        fs::write(
+            directory_manager.genesis_ledger_path("test"),
+            r#"{"genesis": {"genesis_state_timestamp": "2024-01-01T00:00:00Z"},
+                "ledger": {"accounts": []}}"#,
+        )
+        .unwrap();
+        let network_path = directory_manager.network_path("test");
+        default::LedgerGenerator::generate_replayer_input(&network_path, "mina-archive").unwrap();
+        let backend = MockBackend::default();
+
+        dump_archive_data(&backend, &directory_manager, "mina-archive", "test").unwrap();
+        run_replayer(&backend, &directory_manager, "mina-archive", "test", 42).unwrap();
+        assert_eq!(
+            backend.calls(),
+            [
+                "exec postgres pg_dump --insert -U postgres archive_test_mina_archive",
+                "run_replayer mina-archive",
+            ]
+        );
+        let replayer_input =
+            fs::read_to_string(network_path.join("mina-archive_replayer_input.json")).unwrap();
+        assert!(replayer_input.contains(r#""start_slot_since_genesis": 42"#));
+
+        let err = run_replayer(&backend, &directory_manager, "mina-seed-1", "test", 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_create_network_rejects_port_conflicts() {
+        let tempdir =
+            TempDir::new("test_create_network_ports").expect("Cannot create temporary directory");
+        let directory_manager = setup_network(&tempdir, &[]);
+        let services = vec![
+            ServiceConfig {
+                client_port: Some(7070),
+                ..service("mina-seed-1", ServiceType::Seed)
+            },
+            ServiceConfig {
+                client_port: Some(7072),
+                ..service("mina-bp-1", ServiceType::BlockProducer)
+            },
+        ];
+        let backend = MockBackend::default();
+
+        let err = create_network(&backend, &directory_manager, "test", &services).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_and_restore_network() {
+        let tempdir =
+            TempDir::new("test_snapshot_network").expect("Cannot create temporary directory");
+        let services = vec![
+            ServiceConfig {
+                libp2p_peerid: Some("12D3KooW".to_string()),
+                peer_list_file: Some(tempdir.path().join("test/peer_list_file.txt")),
+                ..service("mina-seed-1", ServiceType::Seed)
+            },
+            ServiceConfig {
+                peers: Some(vec![
+                    "/dns4/mina-seed-1-test/tcp/3102/p2p/12D3KooW".to_string()
+                ]),
+                ..service("mina-bp-1", ServiceType::BlockProducer)
+            },
+        ];
+        let directory_manager = setup_network(&tempdir, &services);
+        directory_manager
+            .create_peer_list_file("test", &ServiceConfig::get_seeds(&services))
+            .unwrap();
+        let backend = MockBackend::default();
+
+        let snapshot = snapshot_network(
+            &backend,
+            &directory_manager,
+            "test",
+            "epoch-2",
+            &ExecutionMode::Docker,
+        )
+        .unwrap();
+
+        assert_eq!(backend.calls(), ["stop_all", "save_state 2"]);
+        assert!(directory_manager.snapshot_path("epoch-2").exists());
+        assert_eq!(
+            snapshot.file,
+            directory_manager
+                .snapshot_path("epoch-2")
+                .display()
+                .to_string()
+        );
+        let err = snapshot_network(
+            &backend,
+            &directory_manager,
+            "test",
+            "epoch-2",
+            &ExecutionMode::Docker,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        let (staging, manifest) =
+            Staging::unpack::<Manifest>(&directory_manager.snapshot_path("epoch-2")).unwrap();
+        assert_eq!(manifest.network_id, "test");
+        assert_eq!(manifest.services, ["mina-seed-1", "mina-bp-1"]);
+        assert_eq!(manifest.state, ["volume.tar.gz"]);
+
+        // restoring needs the same mode and a new network
+        let restore = |network_id: &str, mode: &ExecutionMode| {
+            restore_network(
+                &backend,
+                &directory_manager,
+                &staging,
+                &manifest,
+                network_id,
+                mode,
+            )
+        };
+        let err = restore("copy", &ExecutionMode::Native).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = restore("test", &ExecutionMode::Docker).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        let restored = restore("copy", &ExecutionMode::Docker).unwrap();
+        assert_eq!(restored.name, "epoch-2");
+        assert_eq!(
+            backend.calls()[2..],
+            ["create 2".to_string(), "load_state 2".to_string()]
+        );
+        let services = directory_manager.get_services_info("copy").unwrap();
+        assert_eq!(
+            services[1].peers.as_deref().unwrap(),
+            ["/dns4/mina-seed-1-copy/tcp/3102/p2p/12D3KooW"]
+        );
+        assert_eq!(
+            services[0].peer_list_file,
+            Some(directory_manager.peer_list_file("copy"))
+        );
+        let peer_list = fs::read_to_string(directory_manager.peer_list_file("copy")).unwrap();
+        assert!(peer_list.starts_with("/dns4/mina-seed-1-copy/"));
+        assert!(directory_manager
+            .network_path("copy")
+            .join(NETWORK_KEYPAIRS)
+            .exists());
+    }
+
+    #[test]
+    fn test_restore_native_network_renames_archive_dbs() {
+        let tempdir =
+            TempDir::new("test_restore_native").expect("Cannot create temporary directory");
+        let mut services = vec![service("mina-archive", ServiceType::ArchiveNode)];
+        ServiceConfig::name_archive_dbs(&mut services, "test");
+        let directory_manager = setup_network(&tempdir, &services);
+        let backend = MockBackend::default();
+        snapshot_network(
+            &backend,
+            &directory_manager,
+            "test",
+            "epoch-2",
+            &ExecutionMode::Native,
+        )
+        .unwrap();
+        let (staging, manifest) =
+            Staging::unpack::<Manifest>(&directory_manager.snapshot_path("epoch-2")).unwrap();
+
+        restore_network(
+            &backend,
+            &directory_manager,
+            &staging,
+            &manifest,
+            "copy",
+            &ExecutionMode::Native,
+        )
+        .unwrap();
+        // the databases of the snapshot's network are left alone
+        let services = directory_manager.get_services_info("copy").unwrap();
+        assert_eq!(services[0].archive_db(), "archive_copy_mina_archive");
+        let services = directory_manager.get_services_info("test").unwrap();
+        assert_eq!(services[0].archive_db(), "archive_test_mina_archive");
+    }
+
+    #[test]
+    fn test_clone_network() {
+        let tempdir =
+            TempDir::new("test_clone_network").expect("Cannot create temporary directory");
+        let services = vec![
+            ServiceConfig {
+                client_port: Some(3100),
+                libp2p_peerid: Some("12D3KooW".to_string()),
+                peer_list_file: Some(tempdir.path().join("test/peer_list_file.txt")),
+                ..service("mina-seed-1", ServiceType::Seed)
+            },
+            ServiceConfig {
+                client_port: Some(4100),
+                peers: Some(vec![
+                    "/dns4/mina-seed-1-test/tcp/3102/p2p/12D3KooW".to_string()
+                ]),
+                ..service("mina-bp-1", ServiceType::BlockProducer)
+            },
+        ];
+        let directory_manager = setup_network(&tempdir, &services);
+        directory_manager
+            .create_peer_list_file("test", &ServiceConfig::get_seeds(&services))
+            .unwrap();
+        let key_path = directory_manager
+            .network_path("test")
+            .join(NETWORK_KEYPAIRS)
+            .join("mina-bp-1.json");
+        fs::write(&key_path, "key").unwrap();
+        fs::write(directory_manager.genesis_ledger_path("test"), "{}").unwrap();
+        let backend = MockBackend::default();
+
+        // ports 4100.. of the source network are taken, the clone moves 2000 up
+        let network = clone_network(&backend, &directory_manager, "test", "copy", false).unwrap();
+        assert_eq!(network.network_id, "copy");
+        assert_eq!(backend.calls(), ["create 2"]);
+        let cloned = directory_manager.get_services_info("copy").unwrap();
+        assert_eq!(cloned[0].client_port, Some(5100));
+        assert_eq!(cloned[1].client_port, Some(6100));
+        assert_eq!(
+            cloned[1].peers.as_deref().unwrap(),
+            ["/dns4/mina-seed-1-copy/tcp/5102/p2p/12D3KooW"]
+        );
+        let peer_list = fs::read_to_string(directory_manager.peer_list_file("copy")).unwrap();
+        assert_eq!(peer_list, "/dns4/mina-seed-1-copy/tcp/5102/p2p/12D3KooW\n");
+        let network_path = directory_manager.network_path("copy");
+        assert_eq!(
+            fs::read_to_string(network_path.join(NETWORK_KEYPAIRS).join("mina-bp-1.json")).unwrap(),
+            "key"
+        );
+        assert!(network_path.join("network.json").exists());
+
+        let err = clone_network(&backend, &directory_manager, "test", "copy", false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn test_export_and_import_network() {
+        let tempdir =
+            TempDir::new("test_export_network").expect("Cannot create temporary directory");
+        let services = vec![
+            ServiceConfig {
+                docker_image: Some("mina-daemon:3.0.0".to_string()),
+                libp2p_peerid: Some("12D3KooW".to_string()),
+                peer_list_file: Some(tempdir.path().join("test/peer_list_file.txt")),
+                ..service("mina-seed-1", ServiceType::Seed)
+            },
+            ServiceConfig {
+                docker_image: Some("mina-daemon:3.0.0".to_string()),
+                private_key_path: Some(PathBuf::from("/home/lucy/keys/bp-1.key")),
+                peers: Some(vec![
+                    "/dns4/mina-seed-1-test/tcp/3102/p2p/12D3KooW".to_string()
+                ]),
+                ..service("mina-bp-1", ServiceType::BlockProducer)
+            },
+        ];
+        let directory_manager = setup_network(&tempdir, &services);
+        directory_manager
+            .create_peer_list_file("test", &ServiceConfig::get_seeds(&services))
+            .unwrap();
+        let bundle_path = tempdir.path().join("test.tar.gz");
+
+        let export = export_network(&directory_manager, "test", &bundle_path).unwrap();
+        assert_eq!(export.file, bundle_path.display().to_string());
+
+        let (staging, manifest) = Staging::unpack::<bundle::Manifest>(&bundle_path).unwrap();
+        assert_eq!(manifest.network_id, "test");
+        assert_eq!(manifest.images, ["mina-daemon:3.0.0"]);
+        let bundled = fs::read_to_string(staging.network_dir().join(SERVICES_JSON)).unwrap();
+        assert!(!bundled.contains(&tempdir.path().display().to_string()));
+        assert!(!staging.network_dir().join("peer_list_file.txt").exists());
+
+        // imported on another machine, i.e. under another base path
+        let other_home = TempDir::new("test_import_network").unwrap();
+        let other = DirectoryManager::_new_with_base_path(other_home.path().into());
+        let backend = MockBackend::default();
+        let network = import_network(&backend, &other, &staging, &manifest, "copy").unwrap();
+        assert_eq!(network.network_id, "copy");
+        assert_eq!(backend.calls(), ["create 2"]);
+
+        let imported = other.get_services_info("copy").unwrap();
+        assert_eq!(
+            imported[0].peer_list_file,
+            Some(other.peer_list_file("copy"))
+        );
+        assert_eq!(
+            imported[1].private_key_path,
+            Some(
+                other
+                    .network_path("copy")
+                    .join(NETWORK_KEYPAIRS)
+                    .join("mina-bp-1.json")
+            )
+        );
+        assert_eq!(
+            imported[1].peers.as_deref().unwrap(),
+            ["/dns4/mina-seed-1-copy/tcp/3102/p2p/12D3KooW"]
+        );
+        let peer_list = fs::read_to_string(other.peer_list_file("copy")).unwrap();
+        assert!(peer_list.starts_with("/dns4/mina-seed-1-copy/"));
+
+        let err = import_network(&backend, &other, &staging, &manifest, "copy").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn test_start_node_fresh_state_with_accounts() {
+        let tempdir =
+            TempDir::new("test_start_node_fresh_state").expect("Cannot create temporary directory");
+        let directory_manager = setup_network(
+            &tempdir,
+            &[service("mina-bp-1", ServiceType::BlockProducer)],
+        );
+        let keypairs = directory_manager
+            .network_path("test")
+            .join(NETWORK_KEYPAIRS);
+        fs::write(keypairs.join("mina-bp-1.json"), "").unwrap();
+        fs::write(keypairs.join("mina-bp-1.json.pub"), "").unwrap();
+        let backend = MockBackend::with_state("mina-bp-1", ContainerState::Running);
+
+        let stdout = start_node(
+            &backend,
+            &directory_manager,
+            "mina-bp-1",
+            "test",
+            true,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(stdout, "started");
+        assert_eq!(
+            backend.calls(),
+            [
+                "reset_service mina-bp-1",
+                "import_account mina-bp-1 mina-bp-1.json",
+                "start_service mina-bp-1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_start_node_already_running() {
+        let tempdir = TempDir::new("test_start_node_already_running")
+            .expect("Cannot create temporary directory");
+        let directory_manager = setup_network(
+            &tempdir,
+            &[service("mina-bp-1", ServiceType::BlockProducer)],
+        );
+        let backend = MockBackend::with_state("mina-bp-1", ContainerState::Running);
+
+        start_node(
+            &backend,
+            &directory_manager,
+            "mina-bp-1",
+            "test",
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn test_start_node_unknown() {
+        let tempdir =
+            TempDir::new("test_start_node_unknown").expect("Cannot create temporary directory");
+        let directory_manager = setup_network(&tempdir, &[]);
+        let backend = MockBackend::default();
+
+        let err = start_node(
+            &backend,
+            &directory_manager,
+            "mina-bp-9",
+            "test",
+            false,
+            false,
+        )
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn test_stop_node() {
+        let tempdir = TempDir::new("test_stop_node").expect("Cannot create temporary directory");
+        let directory_manager = setup_network(
+            &tempdir,
+            &[service("mina-bp-1", ServiceType::BlockProducer)],
+        );
+        let backend = MockBackend::default();
+
+        stop_node(&backend, &directory_manager, "mina-bp-1", "test").unwrap();
+        let err = stop_node(&backend, &directory_manager, "mina-bp-9", "test").unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(backend.calls(), ["stop_service mina-bp-1"]);
+    }
+
+    #[test]
+    fn test_node_logs_uses_service_config() {
+        let tempdir = TempDir::new("test_node_logs").expect("Cannot create temporary directory");
+        let directory_manager = setup_network(
+            &tempdir,
+            &[service("uptime", ServiceType::UptimeServiceBackend)],
+        );
+        let backend = MockBackend::default();
+
+        let logs = node_logs(&backend, &directory_manager, "uptime", "test").unwrap();
+        assert_eq!(logs, "logs of UptimeServiceBackend");
+
+        // containers outside the topology, e.g. postgres, still have logs
+        let logs = node_logs(&backend, &directory_manager, "postgres", "test").unwrap();
+        assert_eq!(logs, "logs of BlockProducer");
+    }
+
+    #[test]
+    fn test_network_status_and_delete() {
+        let tempdir =
+            TempDir::new("test_network_status").expect("Cannot create temporary directory");
+        let directory_manager = setup_network(&tempdir, &[]);
+        let backend = MockBackend::default();
+
+        let status = network_status(&backend, &directory_manager, "test").unwrap();
+        assert_eq!(
+            status.network_dir,
+            directory_manager.network_path("test").display().to_string()
+        );
+
+        delete_network(&backend, &directory_manager, "test").unwrap();
+        assert_eq!(backend.calls(), ["status", "destroy"]);
+        assert!(!directory_manager.network_path_exists("test"));
+    }
+
+    #[test]
+    fn test_partition_and_heal_network() {
+        let tempdir =
+            TempDir::new("test_partition_network").expect("Cannot create temporary directory");
+        let directory_manager = setup_network(
+            &tempdir,
+            &[
+                service("mina-seed-1", ServiceType::Seed),
+                service("mina-bp-1", ServiceType::BlockProducer),
+                service("mina-bp-2", ServiceType::BlockProducer),
+            ],
+        );
+        let backend = MockBackend::with_state("mina-bp-1", ContainerState::Exited);
+
+        let faults = Faults {
+            groups: vec![vec!["mina-bp-1".into()], vec!["mina-bp-2".into()]],
+            links: vec![],
+        };
+        let partition = partition_network(&backend, &directory_manager, "test", &faults).unwrap();
+        assert_eq!(partition.faults, faults);
+        let status = network_status(&backend, &directory_manager, "test").unwrap();
+        assert_eq!(status.faults, Some(faults));
+
+        // replacing the partition also heals the services it leaves out
+        let faults = Faults {
+            groups: vec![],
+            links: vec!["mina-seed-1:mina-bp-1,latency=100ms".parse().unwrap()],
+        };
+        partition_network(&backend, &directory_manager, "test", &faults).unwrap();
+        assert_eq!(
+            directory_manager.faults("test").unwrap().as_ref(),
+            Some(&faults)
+        );
+
+        let unknown = Faults {
+            groups: vec![vec!["mina-bp-1".into()], vec!["mina-bp-3".into()]],
+            links: vec![],
+        };
+        let err = partition_network(&backend, &directory_manager, "test", &unknown).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let healed = heal_network(&backend, &directory_manager, "test").unwrap();
+        assert!(healed.faults.is_empty());
+        assert_eq!(directory_manager.faults("test").unwrap(), None);
+
+        // a restarted service and its peers get the faults again
+        partition_network(&backend, &directory_manager, "test", &faults).unwrap();
+        start_node(
+            &backend,
+            &directory_manager,
+            "mina-bp-1",
+            "test",
+            false,
+            false,
+        )
+        .unwrap();
+        stop_network(&backend, &directory_manager, "test").unwrap();
+        assert_eq!(directory_manager.faults("test").unwrap(), None);
+
+        assert_eq!(
+            backend.calls(),
+            [
+                "apply_faults 2 0 mina-bp-1,mina-bp-2",
+                "status",
+                "apply_faults 0 1 mina-seed-1,mina-bp-1,mina-bp-2",
+                "apply_faults 0 0 mina-seed-1,mina-bp-1,mina-bp-2",
+                "apply_faults 0 1 mina-seed-1,mina-bp-1",
+                "start_service mina-bp-1",
+                "apply_faults 0 1 mina-seed-1,mina-bp-1",
+                "stop_all",
+            ]
+        );
+    }
+}
@@ -9,8 +9,13 @@
 //! - `docker-compose.yml`: Contains the docker compose file for the network.
 //! - `network.json`: Contains the network topology representation in JSON format.
 //! - `peer_list_file.txt`: Contains the list of libp2p peers for the network.
+//! - `faults.json`: Contains the network faults active on the running network, if any.
+//!
+//! Network snapshots are kept apart from the networks, in `~/.minimina/.snapshots/{name}.tar.gz`,
+//! and so are the builds of `git_build` refs, in `~/.minimina/.builds/{commit}`.
 
-use crate::genesis_ledger::GENESIS_LEDGER_JSON;
+use crate::faults::Faults;
+use crate::genesis_ledger::{BLOCK_WINDOW_DURATION_MS, GENESIS_LEDGER_JSON};
 use crate::output;
 use crate::service::ServiceConfig;
 use dirs::home_dir;
@@ -23,9 +28,28 @@ use std::{
     path::{Path, PathBuf},
 };
 
+pub const SERVICES_JSON: &str = "services.json";
+pub const FAULTS_JSON: &str = "faults.json";
+pub const PEER_LIST_FILE: &str = "peer_list_file.txt";
+pub const UPTIME_SERVICE_CONFIG: &str = "uptime_service_config";
 pub const NETWORK_KEYPAIRS: &str = "network-keypairs";
-const LIBP2P_KEYPAIRS: &str = "libp2p-keypairs";
+pub const LIBP2P_KEYPAIRS: &str = "libp2p-keypairs";
 const MINIMINA_HOME: &str = "MINIMINA_HOME";
+const SNAPSHOTS: &str = ".snapshots";
+const BUILDS: &str = ".builds";
+/// Network directory entries written for, or by, a running network
+const GENERATED_ENTRIES: [&str; 10] = [
+    "docker-compose.yaml",
+    "network.json",
+    SERVICES_JSON,
+    FAULTS_JSON,
+    PEER_LIST_FILE,
+    "processes.json",
+    "supervisor.pid",
+    "logs",
+    "config-directory",
+    "uptime-storage",
+];
 
 #[derive(Clone)]
 pub struct DirectoryManager {
@@ -115,17 +139,86 @@ impl DirectoryManager {
             let entry = entry?;
             if entry.file_type()?.is_dir() {
                 if let Some(network_id) = entry.file_name().to_str() {
-                    networks.push(network_id.to_string());
+                    // hidden directories, e.g. the snapshots, aren't networks
+                    if !network_id.starts_with('.') {
+                        networks.push(network_id.to_string());
+                    }
                 }
             }
         }
         Ok(networks)
     }
 
+    /// Copies the keys, genesis ledger, topology and other inputs of the network
+    /// to directory `dest`, leaving out what is generated for or by the running network
+    pub fn copy_network_inputs(&self, network_id: &str, dest: &Path) -> Result<()> {
+        fs::create_dir_all(dest)?;
+        for entry in fs::read_dir(self.network_path(network_id))? {
+            let entry = entry?;
+            if !GENERATED_ENTRIES.contains(&entry.file_name().to_str().unwrap_or_default()) {
+                copy_recursively(&entry.path(), &dest.join(entry.file_name()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the path of snapshot `name`
+    pub fn snapshot_path(&self, name: &str) -> PathBuf {
+        self.base_path
+            .join(SNAPSHOTS)
+            .join(format!("{name}.tar.gz"))
+    }
+
+    /// Returns the directory of the builds of `git_build` refs, shared by all networks
+    pub fn builds_path(&self) -> PathBuf {
+        self.base_path.join(BUILDS)
+    }
+
     pub fn get_network_keypair_files(&self, network_id: &str) -> Result<Vec<String>> {
         self.get_files_in_network_subdir(network_id, NETWORK_KEYPAIRS, Some(".pub"))
     }
 
+    /// Returns the file in `network-keypairs` and the public key of account `name`:
+    /// a generated key pair `<name>` with its `<name>.pub`, or the `<name>.json`
+    /// copied for a service of the topology
+    pub fn network_keypair(&self, network_id: &str, name: &str) -> Result<(String, String)> {
+        let keypairs = self.network_path(network_id).join(NETWORK_KEYPAIRS);
+        if let Ok(public_key) = fs::read_to_string(keypairs.join(format!("{name}.pub"))) {
+            return Ok((name.to_string(), public_key.trim().to_string()));
+        }
+
+        let account_file = format!("{name}.json");
+        let public_key = self
+            .get_services_info(network_id)?
+            .into_iter()
+            .find(|service| service.service_name == name)
+            .and_then(|service| service.public_key);
+        match public_key {
+            Some(public_key) if keypairs.join(&account_file).exists() => {
+                Ok((account_file, public_key))
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No key pair for account '{name}' in network '{network_id}'"),
+            )),
+        }
+    }
+
+    /// Names of the accounts with a key pair in `network-keypairs`, see [`Self::network_keypair`]
+    pub fn network_keypair_names(&self, network_id: &str) -> Result<Vec<String>> {
+        let keypairs = self.network_path(network_id).join(NETWORK_KEYPAIRS);
+        let mut names = std::collections::BTreeSet::new();
+        for entry in fs::read_dir(keypairs)? {
+            let file_name = entry?.file_name();
+            let file_name = file_name.to_string_lossy();
+            let name = file_name.trim_end_matches(".pub").trim_end_matches(".json");
+            if self.network_keypair(network_id, name).is_ok() {
+                names.insert(name.to_string());
+            }
+        }
+        Ok(names.into_iter().collect())
+    }
+
     pub fn _get_libp2p_keypair_files(&self, network_id: &str) -> Result<Vec<String>> {
         self.get_files_in_network_subdir(network_id, LIBP2P_KEYPAIRS, Some(".peerid"))
     }
@@ -205,14 +298,17 @@ impl DirectoryManager {
         Ok(())
     }
 
+    /// Copy the config files of an uptime service backend to `uptime_service_config/<service name>`
     pub fn copy_uptime_service_config(
         &self,
         network_id: &str,
         service: &ServiceConfig,
     ) -> Result<()> {
         info!("Copying uptime service config for {}", service.service_name);
-        let uptime_service_config_path =
-            self.network_path(network_id).join("uptime_service_config");
+        let uptime_service_config_path = self
+            .network_path(network_id)
+            .join(UPTIME_SERVICE_CONFIG)
+            .join(&service.service_name);
         fs::create_dir_all(&uptime_service_config_path)?;
         if let Some(uptime_service_backend_app_config) = &service.uptime_service_backend_app_config
         {
@@ -258,7 +354,7 @@ impl DirectoryManager {
     }
 
     pub fn peer_list_file(&self, network_id: &str) -> PathBuf {
-        self.network_path(network_id).join("peer_list_file.txt")
+        self.network_path(network_id).join(PEER_LIST_FILE)
     }
 
     pub fn create_peer_list_file(&self, network_id: &str, peers: &[&ServiceConfig]) -> Result<()> {
@@ -269,7 +365,7 @@ impl DirectoryManager {
 
         for peer in peers {
             let peer_hostname = format!("{}-{}", peer.service_name, network_id);
-            let external_port = peer.client_port.unwrap() + 2;
+            let external_port = peer.daemon_ports()?.external;
             let libp2p_key = peer.libp2p_peerid.clone().unwrap();
             writeln!(
                 file,
@@ -307,8 +403,16 @@ impl DirectoryManager {
             Some(k) => k.to_string().parse::<u32>().unwrap(),
             None => 20_u32,
         };
+        // k / 2 slots, of the network's slot duration if the runtime config sets one
+        let block_window_duration_ms = json
+            .get("proof")
+            .and_then(|proof| proof.get("block_window_duration_ms"))
+            .and_then(|ms| ms.as_u64())
+            .unwrap_or(BLOCK_WINDOW_DURATION_MS);
         let cutoff = Local::now()
-            .checked_sub_signed(Duration::minutes((k / 2 * 3) as i64))
+            .checked_sub_signed(Duration::milliseconds(
+                (k / 2) as i64 * block_window_duration_ms as i64,
+            ))
             .unwrap();
 
         // if we're outside of the first half of the first transition frontier,
@@ -360,7 +464,7 @@ impl DirectoryManager {
 
     pub fn save_network_info(&self, network_id: &str, services: &[ServiceConfig]) -> Result<()> {
         let network_file_path = self.network_file_path(network_id);
-        let contents = format!("{}", output::generate_network_info(services, network_id));
+        let contents = format!("{}", output::generate_network_info(services, network_id)?);
         fs::write(network_file_path, contents)
     }
 
@@ -371,7 +475,7 @@ impl DirectoryManager {
 
     /// Returns the services file path for the given network
     pub fn services_file_path(&self, network_id: &str) -> PathBuf {
-        self.network_path(network_id).join("services.json")
+        self.network_path(network_id).join(SERVICES_JSON)
     }
 
     pub fn save_services_info(&self, network_id: &str, services: &[ServiceConfig]) -> Result<()> {
@@ -387,12 +491,49 @@ impl DirectoryManager {
         Ok(services)
     }
 
+    /// Faults active on the network, `None` if there are none
+    pub fn faults(&self, network_id: &str) -> Result<Option<Faults>> {
+        let faults_file_path = self.network_path(network_id).join(FAULTS_JSON);
+        if !faults_file_path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(faults_file_path)?;
+        Ok(Some(serde_json::from_str(&contents)?))
+    }
+
+    /// Records the faults active on the network, removing the record when there are none
+    pub fn save_faults(&self, network_id: &str, faults: &Faults) -> Result<()> {
+        let faults_file_path = self.network_path(network_id).join(FAULTS_JSON);
+        if faults.is_empty() {
+            if faults_file_path.exists() {
+                fs::remove_file(faults_file_path)?;
+            }
+            return Ok(());
+        }
+        fs::write(faults_file_path, serde_json::to_string_pretty(faults)?)
+    }
+
     /// Returns the topology file path for the given network
     pub fn topology_file_path(&self, network_id: &str) -> PathBuf {
         self.network_path(network_id).join("topology.json")
     }
 }
 
+/// Copies the file or directory at `src` to `dest`, keeping permissions
+fn copy_recursively(src: &Path, dest: &Path) -> Result<()> {
+    if src.is_dir() {
+        fs::create_dir_all(dest)?;
+        fs::set_permissions(dest, fs::metadata(src)?.permissions())?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(src, dest).map(|_| ())
+    }
+}
+
 fn set_key_file_permissions(file: &Path) -> Result<()> {
     fs::set_permissions(file, fs::Permissions::from_mode(0o600))?;
     Ok(())
@@ -460,11 +601,15 @@ mod tests {
             dir_manager.create_network_directory(network_id).unwrap();
         }
 
+        // Snapshots are not networks
+        fs::create_dir_all(dir_manager.snapshot_path("snapshot").parent().unwrap()).unwrap();
+
         // Check that all network directories are listed
         let listed_networks = dir_manager.list_network_directories().unwrap();
         for network_id in &network_ids {
             assert!(listed_networks.contains(&network_id.to_string()));
         }
+        assert_eq!(listed_networks.len(), network_ids.len());
 
         // Clean up
         for network_id in &network_ids {
@@ -572,6 +717,21 @@ mod tests {
         fs::write(genesis_ledger_path.clone(), recent_genesis.clone())?;
         assert!(dir_manager.check_genesis_timestamp(network_id).is_ok());
 
+        // with 6s slots the recent timestamp is outdated too
+        let fast_genesis = format!(
+            "{{
+                \"genesis\": {{
+                    \"k\": {k},
+                    \"genesis_state_timestamp\": \"{recent_time}\"
+                }},
+                \"proof\": {{
+                    \"block_window_duration_ms\": 6000
+                }}
+            }}",
+        );
+        fs::write(genesis_ledger_path.clone(), fast_genesis)?;
+        assert!(dir_manager.check_genesis_timestamp(network_id).is_err());
+
         dir_manager.delete_network_directory(network_id)?;
 
         Ok(())
@@ -685,6 +845,43 @@ mod tests {
         dir_manager.delete_network_directory(network_id).unwrap();
     }
 
+    #[test]
+    fn test_network_keypairs() {
+        let tempdir =
+            TempDir::new("test_network_keypairs").expect("Cannot create temporary directory");
+        let dir_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        dir_manager.generate_dir_structure("test").unwrap();
+        let services = vec![ServiceConfig {
+            service_name: "mina-bp-1".to_string(),
+            public_key: Some("B62qbp1".to_string()),
+            ..Default::default()
+        }];
+        dir_manager.save_services_info("test", &services).unwrap();
+        let keypairs = dir_manager.network_path("test").join(NETWORK_KEYPAIRS);
+        for (file, content) in [
+            ("mina-bp-1.json", "{}"),
+            ("whale-0", "{}"),
+            ("whale-0.pub", "B62qwhale0\n"),
+            ("orphan.json", "{}"),
+        ] {
+            fs::write(keypairs.join(file), content).unwrap();
+        }
+
+        assert_eq!(
+            dir_manager.network_keypair("test", "whale-0").unwrap(),
+            ("whale-0".to_string(), "B62qwhale0".to_string())
+        );
+        assert_eq!(
+            dir_manager.network_keypair("test", "mina-bp-1").unwrap(),
+            ("mina-bp-1.json".to_string(), "B62qbp1".to_string())
+        );
+        assert!(dir_manager.network_keypair("test", "orphan").is_err());
+        assert_eq!(
+            dir_manager.network_keypair_names("test").unwrap(),
+            ["mina-bp-1", "whale-0"]
+        );
+    }
+
     #[test]
     fn test_copy_uptime_service_config() {
         let tempdir = TempDir::new("test_copy_uptime_service_config")
@@ -703,24 +900,17 @@ mod tests {
             )),
             ..Default::default()
         }];
-        let uptime_service = ServiceConfig::get_uptime_service_backend(&services).unwrap();
+        let uptime_service = ServiceConfig::get_uptime_service_backends(&services)[0];
         dir_manager.create_network_directory(network_id).unwrap();
         let res = dir_manager.copy_uptime_service_config(network_id, uptime_service);
         assert!(res.is_ok());
-        assert!(dir_manager
-            .network_path(network_id)
-            .join("uptime_service_config")
-            .exists());
-        assert!(dir_manager
-            .network_path(network_id)
-            .join("uptime_service_config")
-            .join("app_config.json")
-            .exists());
-        assert!(dir_manager
+        let config_path = dir_manager
             .network_path(network_id)
             .join("uptime_service_config")
-            .join("minasheets.json")
-            .exists());
+            .join("test_service1");
+        assert!(config_path.exists());
+        assert!(config_path.join("app_config.json").exists());
+        assert!(config_path.join("minasheets.json").exists());
         dir_manager.delete_network_directory(network_id).unwrap();
     }
 }
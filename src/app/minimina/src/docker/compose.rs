@@ -3,12 +3,13 @@
 //! This module facilitates the generation contents of `docker-compose.yaml` for
 //! deploying various Mina services in a Docker environment.
 
-use crate::service::{ServiceConfig, ServiceType};
+use crate::service::{ServiceConfig, ServiceType, UPTIME_SERVICE_PORT};
 use log::debug;
 use serde::ser::{SerializeStruct, Serializer};
 use serde::Serialize;
 use serde_yaml;
 use std::collections::HashMap;
+use std::io;
 use std::path::Path;
 
 #[derive(Serialize)]
@@ -70,11 +71,12 @@ struct Service {
 }
 
 pub const CONFIG_DIRECTORY: &str = "config-directory";
-const POSTGRES_DATA: &str = "postgres-data";
+/// Volume of the postgres data shared by the archive nodes
+pub const POSTGRES_DATA: &str = "postgres-data";
 const RAYON_NUM_THREADS: u32 = 2;
 
 impl DockerCompose {
-    pub fn generate(configs: &[ServiceConfig], network_path: &Path) -> String {
+    pub fn generate(configs: &[ServiceConfig], network_path: &Path) -> io::Result<String> {
         let network_path_string = network_path
             .to_str()
             .expect("Failed to convert network path to str");
@@ -87,87 +89,61 @@ impl DockerCompose {
             acc
         });
 
-        let uptime_service_hostname = if let Some(uptime_service_backend) =
-            ServiceConfig::get_uptime_service_backend(configs)
-        {
-            let uptime_service_name = format!(
-                "{}-{network_name}",
-                uptime_service_backend.service_name.clone()
-            );
-            Some(uptime_service_name)
-        } else {
-            None
-        };
-
-        let mut services: HashMap<String, Service> = configs
-            .iter()
-            .filter_map(|config| {
-                match config.service_type {
-                    // We'll handle ArchiveNode outside of this map operation
-                    // because it requires adding additional services: postgres, mina-archive-service
-                    ServiceType::ArchiveNode => None,
-                    // We'll handle UptimeServiceBackend outside of this map operation
-                    // because it has different shape than other daemon services
-                    ServiceType::UptimeServiceBackend => None,
-                    _ => {
-                        let service_name =
-                            format!("{}-{network_name}", config.service_name.clone());
-                        let service = Service {
-                            merge: Some("*default-attributes"),
-                            container_name: service_name.clone(),
-                            entrypoint: Some(vec!["mina".to_string()]),
-                            volumes: Some(vec![
-                                format!("{network_path_string}:/local-network"),
-                                format!("{service_name}:/{CONFIG_DIRECTORY}"),
-                            ]),
-                            image: config
-                                .docker_image
-                                .clone()
-                                .expect("Failed to get mina daemon docker image"),
-                            command: Some(match config.service_type {
-                                ServiceType::Seed => config.generate_seed_command(),
-                                ServiceType::BlockProducer => config
-                                    .generate_block_producer_command(
-                                        uptime_service_hostname.clone(),
-                                    ),
-                                ServiceType::SnarkCoordinator => {
-                                    config.generate_snark_coordinator_command()
-                                }
-                                ServiceType::SnarkWorker => {
-                                    config.generate_snark_worker_command(network_name.to_string())
-                                }
-                                _ => String::new(),
-                            }),
-                            ports: match config.client_port {
-                                Some(port) => {
-                                    let gql_port = port + 1;
-                                    let external_port = port + 2;
-                                    Some(vec![
-                                        format!("{}:{}", gql_port, gql_port),
-                                        port.to_string(),
-                                        external_port.to_string(),
-                                    ])
-                                }
-                                None => None,
-                            },
-                            ..Default::default()
-                        };
-                        Some((
-                            format!("{}-{network_name}", config.service_name.clone()),
-                            service,
-                        ))
-                    }
+        let mut services: HashMap<String, Service> = HashMap::new();
+        for config in configs {
+            match config.service_type {
+                // We'll handle ArchiveNode outside of this loop
+                // because it requires adding additional services: postgres, mina-archive-service
+                ServiceType::ArchiveNode => {}
+                // We'll handle UptimeServiceBackend outside of this loop
+                // because it has different shape than other daemon services
+                ServiceType::UptimeServiceBackend => {}
+                _ => {
+                    let service_name = format!("{}-{network_name}", config.service_name.clone());
+                    let service = Service {
+                        merge: Some("*default-attributes"),
+                        container_name: service_name.clone(),
+                        entrypoint: Some(vec!["mina".to_string()]),
+                        volumes: Some(vec![
+                            format!("{network_path_string}:/local-network"),
+                            format!("{service_name}:/{CONFIG_DIRECTORY}"),
+                        ]),
+                        image: config
+                            .docker_image
+                            .clone()
+                            .expect("Failed to get mina daemon docker image"),
+                        command: Some(match config.service_type {
+                            ServiceType::Seed => config.generate_seed_command()?,
+                            ServiceType::BlockProducer => config.generate_block_producer_command(
+                                config
+                                    .uptime_service_backend
+                                    .as_ref()
+                                    .map(|backend| format!("{backend}-{network_name}")),
+                            )?,
+                            ServiceType::SnarkCoordinator => {
+                                config.generate_snark_coordinator_command()?
+                            }
+                            ServiceType::SnarkWorker => {
+                                config.generate_snark_worker_command(network_name.to_string())
+                            }
+                            _ => String::new(),
+                        }),
+                        ports: Self::daemon_port_mappings(config)?,
+                        ..Default::default()
+                    };
+                    services.insert(service_name, service);
                 }
-            })
-            .collect();
+            }
+        }
 
         // Add ArchiveNode service bits
-        if let Some(archive_config) = ServiceConfig::get_archive_node(configs) {
-            // Add postgres service
+        let archive_nodes = ServiceConfig::get_archive_nodes(configs);
+        let postgres_name = format!("postgres-{network_name}");
+        if !archive_nodes.is_empty() {
+            // Add postgres service, shared by the archive nodes with a database each
             volumes.insert(POSTGRES_DATA.to_string(), None);
             let mut postgres_environment = HashMap::new();
             postgres_environment.insert("POSTGRES_PASSWORD".to_string(), "postgres".to_string());
-            let postgres_name = format!("postgres-{network_name}");
             services.insert(
                 postgres_name.clone(),
                 Service {
@@ -179,7 +155,9 @@ impl DockerCompose {
                     ..Default::default()
                 },
             );
+        }
 
+        for archive_config in archive_nodes {
             // Add archive service
             let archive_node_name =
                 format!("{}-{network_name}", archive_config.service_name.clone());
@@ -189,9 +167,11 @@ impl DockerCompose {
             );
             let archive_port = archive_config.archive_port.unwrap_or(3086);
             let archive_command = format!(
-                "mina-archive run --postgres-uri postgres://postgres:postgres@{}:5432/archive \
+                "mina-archive run --postgres-uri postgres://postgres:postgres@{}:5432/{} \
                 --server-port {}",
-                postgres_name, archive_port
+                postgres_name,
+                archive_config.archive_db(),
+                archive_port
             );
             services.insert(
                 archive_service_name.clone(),
@@ -207,14 +187,14 @@ impl DockerCompose {
                         format!("{}:/local-network", network_path_string),
                     ]),
                     ports: Some(vec![archive_port.to_string()]),
-                    depends_on: Some(vec![postgres_name]),
+                    depends_on: Some(vec![postgres_name.clone()]),
                     ..Default::default()
                 },
             );
 
             // Add archive node
             let archive_command =
-                archive_config.generate_archive_command(archive_service_name.clone());
+                archive_config.generate_archive_command(archive_service_name.clone())?;
             services.insert(
                 archive_node_name.clone(),
                 Service {
@@ -230,30 +210,18 @@ impl DockerCompose {
                         .clone()
                         .expect("Failed to get mina daemon docker image"),
                     command: Some(archive_command),
-                    ports: match archive_config.client_port {
-                        Some(port) => {
-                            let gql_port = port + 1;
-                            let external_port = port + 2;
-                            Some(vec![
-                                format!("{}:{}", gql_port, gql_port),
-                                port.to_string(),
-                                external_port.to_string(),
-                            ])
-                        }
-                        None => None,
-                    },
+                    ports: Self::daemon_port_mappings(archive_config)?,
                     depends_on: Some(vec![archive_service_name]),
                     ..Default::default()
                 },
             );
         }
 
-        // Add UptimeServiceBackend service
-        if let Some(uptime_service_backend) = ServiceConfig::get_uptime_service_backend(configs) {
-            let uptime_service_name = format!(
-                "{}-{network_name}",
-                uptime_service_backend.service_name.clone()
-            );
+        // Add UptimeServiceBackend services
+        let uptime_service_backends = ServiceConfig::get_uptime_service_backends(configs);
+        for uptime_service_backend in &uptime_service_backends {
+            let backend_name = &uptime_service_backend.service_name;
+            let uptime_service_name = format!("{backend_name}-{network_name}");
             let mut uptime_service_env = HashMap::new();
             let app_config = Self::get_filename(
                 uptime_service_backend
@@ -269,12 +237,15 @@ impl DockerCompose {
             );
             uptime_service_env.insert(
                 "CONFIG_FILE".to_string(),
-                format!("/local-network/uptime_service_config/{app_config}"),
+                format!("/local-network/uptime_service_config/{backend_name}/{app_config}"),
             );
             uptime_service_env.insert(
                 "GOOGLE_APPLICATION_CREDENTIALS".to_string(),
-                format!("/local-network/uptime_service_config/{minasheets_config}"),
+                format!("/local-network/uptime_service_config/{backend_name}/{minasheets_config}"),
             );
+            let port = uptime_service_backend
+                .uptime_service_port
+                .unwrap_or(UPTIME_SERVICE_PORT);
 
             services.insert(
                 uptime_service_name.clone(),
@@ -282,14 +253,16 @@ impl DockerCompose {
                     container_name: uptime_service_name.clone(),
                     volumes: Some(vec![
                         format!("{network_path_string}:/local-network"),
-                        format!("{network_path_string}/uptime-storage:/uptime-storage"),
+                        format!(
+                            "{network_path_string}/uptime-storage/{backend_name}:/uptime-storage"
+                        ),
                     ]),
                     environment: Some(uptime_service_env),
                     image: uptime_service_backend
                         .docker_image
                         .clone()
                         .expect("Failed to get uptime_service docker image"),
-                    ports: Some(vec!["8080:8080".to_string()]),
+                    ports: Some(vec![format!("{port}:{UPTIME_SERVICE_PORT}")]),
                     ..Default::default()
                 },
             );
@@ -301,12 +274,10 @@ impl DockerCompose {
                 environment: Environment {
                     mina_privkey_pass: "naughty blue worm".to_string(),
                     mina_libp2p_pass: "naughty blue worm".to_string(),
-                    uptime_privkey_pass: if ServiceConfig::get_uptime_service_backend(configs)
-                        .is_some()
-                    {
-                        Some("naughty blue worm".to_string())
-                    } else {
+                    uptime_privkey_pass: if uptime_service_backends.is_empty() {
                         None
+                    } else {
+                        Some("naughty blue worm".to_string())
                     },
                     mina_client_trustlist: "0.0.0.0/0".to_string(),
                     rayon_num_threads: RAYON_NUM_THREADS,
@@ -319,7 +290,7 @@ impl DockerCompose {
         let yaml_output = serde_yaml::to_string(&compose).unwrap();
         let generated_file = Self::post_process_yaml(yaml_output);
         debug!("Generated docker-compose.yaml: {}", generated_file);
-        generated_file
+        Ok(generated_file)
     }
 
     // fix the format of the yaml output
@@ -337,6 +308,19 @@ impl DockerCompose {
         .replace("null", "")
     }
 
+    // publish the rest port on the host, the client and external ports on ephemeral host ports
+    fn daemon_port_mappings(config: &ServiceConfig) -> io::Result<Option<Vec<String>>> {
+        if config.client_port.is_none() {
+            return Ok(None);
+        }
+        let ports = config.daemon_ports()?;
+        Ok(Some(vec![
+            format!("{}:{}", ports.rest, ports.rest),
+            ports.client.to_string(),
+            ports.external.to_string(),
+        ]))
+    }
+
     fn get_filename(path: &Path) -> String {
         path.file_name()
             .expect("Failed to get filename")
@@ -394,7 +378,7 @@ mod tests {
             },
         ];
         let network_path = Path::new("/not-a-real-path");
-        let docker_compose = DockerCompose::generate(&configs, network_path);
+        let docker_compose = DockerCompose::generate(&configs, network_path).unwrap();
         println!("{:?}", docker_compose);
         assert!(docker_compose.contains("seed"));
         assert!(docker_compose.contains("block-producer"));
@@ -431,7 +415,7 @@ mod tests {
             },
         ];
         let network_path = Path::new("/not-a-real-path");
-        let docker_compose = DockerCompose::generate(&configs, network_path);
+        let docker_compose = DockerCompose::generate(&configs, network_path).unwrap();
         println!("{}", docker_compose);
         assert!(docker_compose.contains("seed"));
         assert!(docker_compose.contains("block-producer"));
@@ -457,8 +441,8 @@ mod tests {
         let contents = std::fs::read_to_string(file)?;
         let topology: Topology = serde_json::from_str(&contents)?;
         let peers_file = dir_manager.peer_list_file(network_id);
-        let services = topology.services(&peers_file);
-        let compose_contents = DockerCompose::generate(&services, &network_path);
+        let services = topology.services(&peers_file)?;
+        let compose_contents = DockerCompose::generate(&services, &network_path)?;
 
         assert!(compose_contents.contains("snark-node"));
         assert!(compose_contents.contains("archive-node"));
@@ -485,10 +469,11 @@ mod tests {
             docker_image: Some("archive-image".into()),
             archive_docker_image: Some("archive-service-image".into()),
             archive_port: Some(8304),
+            archive_db: Some("archive_mina_archive777".into()),
             ..Default::default()
         }];
         let network_path = Path::new("/not-a-real-path/network-id");
-        let docker_compose = DockerCompose::generate(&configs, network_path);
+        let docker_compose = DockerCompose::generate(&configs, network_path).unwrap();
         println!("{}", docker_compose);
         assert!(docker_compose.contains("mina-archive777-network-id"));
         assert!(docker_compose.contains("mina-archive777-service-network-id"));
@@ -496,5 +481,75 @@ mod tests {
         assert!(docker_compose.contains("postgres-data"));
         assert!(docker_compose.contains("/data"));
         assert!(docker_compose.contains("-archive-address mina-archive777-service-network-id:8304"));
+        assert!(docker_compose.contains("postgres-network-id:5432/archive_mina_archive777"));
+    }
+
+    #[test]
+    fn test_generate_multiple_uptime_service_backends() {
+        let config = "./tests/data/uptime_service_network/uptime_service_config_test";
+        let backend = |name: &str, port: u16| ServiceConfig {
+            service_name: name.to_string(),
+            service_type: ServiceType::UptimeServiceBackend,
+            docker_image: Some("uptime-image".into()),
+            uptime_service_backend_app_config: Some(format!("{config}/app_config.json").into()),
+            uptime_service_backend_minasheets: Some(format!("{config}/minasheets.json").into()),
+            uptime_service_port: Some(port),
+            ..Default::default()
+        };
+        let block_producer = |name: &str, backend: Option<&str>| ServiceConfig {
+            service_name: name.to_string(),
+            service_type: ServiceType::BlockProducer,
+            docker_image: Some("bp-image".into()),
+            client_port: Some(8000),
+            public_key_path: Some("key.pub".into()),
+            uptime_service_backend: backend.map(str::to_string),
+            ..Default::default()
+        };
+        let configs = vec![
+            backend("uptime-a", 8080),
+            backend("uptime-b", 8081),
+            block_producer("bp-a", Some("uptime-a")),
+            block_producer("bp-b", Some("uptime-b")),
+            block_producer("bp-none", None),
+        ];
+        let network_path = Path::new("/not-a-real-path/network-id");
+        let docker_compose = DockerCompose::generate(&configs, network_path).unwrap();
+
+        assert!(docker_compose.contains("8080:8080"));
+        assert!(docker_compose.contains("8081:8080"));
+        assert!(docker_compose.contains("/uptime_service_config/uptime-b/app_config.json"));
+        assert!(docker_compose.contains("/uptime-storage/uptime-a:/uptime-storage"));
+        assert!(docker_compose.contains("-uptime-url http://uptime-a-network-id:8080/v1/submit"));
+        assert!(docker_compose.contains("-uptime-url http://uptime-b-network-id:8080/v1/submit"));
+        assert_eq!(docker_compose.matches("-uptime-url").count(), 2);
+    }
+
+    #[test]
+    fn test_generate_multiple_archives() {
+        let archive = |name: &str, client_port: u16, archive_port: u16| ServiceConfig {
+            service_name: name.to_string(),
+            service_type: ServiceType::ArchiveNode,
+            client_port: Some(client_port),
+            docker_image: Some("archive-image".into()),
+            archive_docker_image: Some("archive-service-image".into()),
+            archive_port: Some(archive_port),
+            ..Default::default()
+        };
+        let mut configs = vec![
+            archive("archive-1", 8000, 3086),
+            archive("archive-2", 8005, 3087),
+        ];
+        ServiceConfig::name_archive_dbs(&mut configs, "network-id");
+        let network_path = Path::new("/not-a-real-path/network-id");
+        let docker_compose = DockerCompose::generate(&configs, network_path).unwrap();
+
+        assert_eq!(
+            docker_compose.matches("container_name: postgres-").count(),
+            1
+        );
+        assert!(docker_compose.contains("postgres-network-id:5432/archive_network_id_archive_1"));
+        assert!(docker_compose.contains("postgres-network-id:5432/archive_network_id_archive_2"));
+        assert!(docker_compose.contains("-archive-address archive-1-service-network-id:3086"));
+        assert!(docker_compose.contains("-archive-address archive-2-service-network-id:3087"));
     }
 }
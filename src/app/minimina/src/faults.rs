@@ -0,0 +1,419 @@
+//! # Faults Module
+//!
+//! Network faults injected between the services of a network:
+//! - partitions: groups of services which cannot reach the services of the other groups,
+//!   services in no group keep reaching everyone
+//! - links: latency, loss and bandwidth shaping of the traffic between two services
+//!
+//! The active faults are recorded in the network directory (see
+//! [`DirectoryManager::faults`](crate::directory_manager::DirectoryManager::faults))
+//! and applied by the backend with [`Backend::apply_faults`](crate::backend::Backend::apply_faults).
+//! In docker mode each service gets the `iptables` and `tc` commands of [`script`].
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::{self, Result},
+    str::FromStr,
+};
+
+/// Interface of the services' network
+const INTERFACE: &str = "eth0";
+
+/// iptables chain holding the partition rules
+const CHAIN: &str = "MINIMINA";
+
+/// Rate of the traffic which is not shaped
+const UNSHAPED_RATE: &str = "10gbit";
+
+/// Groups of services `a,b|c,d`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Groups(pub Vec<Vec<String>>);
+
+impl FromStr for Groups {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let groups: Vec<Vec<String>> = s
+            .split('|')
+            .map(|group| {
+                group
+                    .split(',')
+                    .map(|service| service.trim().to_string())
+                    .filter(|service| !service.is_empty())
+                    .collect()
+            })
+            .collect();
+        if groups.len() < 2 || groups.iter().any(Vec::is_empty) {
+            return Err(format!(
+                "Invalid groups '{s}', expected at least two groups of services like a,b|c,d"
+            ));
+        }
+        Ok(Groups(groups))
+    }
+}
+
+/// Shaping of the traffic between services `a` and `b`, in both directions
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Link {
+    pub a: String,
+    pub b: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loss_percent: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bandwidth_kbit: Option<u64>,
+}
+
+/// Parses `a:b,latency=200ms,loss=5%,bandwidth=1mbit`
+impl FromStr for Link {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("Invalid link '{s}': {reason}");
+        let mut parts = s.split(',');
+        let endpoints = parts.next().unwrap_or_default();
+        let (a, b) = endpoints
+            .split_once(':')
+            .ok_or_else(|| invalid("expected a:b,latency=200ms,loss=5%,bandwidth=1mbit"))?;
+        let mut link = Link {
+            a: a.trim().to_string(),
+            b: b.trim().to_string(),
+            ..Default::default()
+        };
+        for part in parts {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid(&format!("expected key=value, got '{part}'")))?;
+            let value = value.trim();
+            match key.trim() {
+                "latency" => {
+                    let ms = value.strip_suffix("ms").unwrap_or(value);
+                    link.latency_ms = Some(ms.parse().map_err(|_| invalid("invalid latency"))?);
+                }
+                "loss" => {
+                    let percent = value.strip_suffix('%').unwrap_or(value);
+                    link.loss_percent = Some(percent.parse().map_err(|_| invalid("invalid loss"))?);
+                }
+                "bandwidth" => {
+                    link.bandwidth_kbit =
+                        Some(parse_kbit(value).ok_or_else(|| invalid("invalid bandwidth"))?);
+                }
+                key => return Err(invalid(&format!("unknown parameter '{key}'"))),
+            }
+        }
+        Ok(link)
+    }
+}
+
+/// Bandwidth `value` in kbit/s, from `<N>kbit`, `<N>mbit` or `<N>gbit`
+fn parse_kbit(value: &str) -> Option<u64> {
+    let units = [("kbit", 1), ("mbit", 1_000), ("gbit", 1_000_000)];
+    units.iter().find_map(|(unit, factor)| {
+        let n: u64 = value.strip_suffix(unit)?.parse().ok()?;
+        Some(n * factor)
+    })
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.a, self.b)?;
+        if let Some(latency) = self.latency_ms {
+            write!(f, ",latency={latency}ms")?;
+        }
+        if let Some(loss) = self.loss_percent {
+            write!(f, ",loss={loss}%")?;
+        }
+        if let Some(bandwidth) = self.bandwidth_kbit {
+            write!(f, ",bandwidth={bandwidth}kbit")?;
+        }
+        Ok(())
+    }
+}
+
+/// Faults active on a network
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Faults {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<Link>,
+}
+
+impl Faults {
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty() && self.links.is_empty()
+    }
+
+    /// Services the faults apply to
+    pub fn services(&self) -> BTreeSet<&str> {
+        let grouped = self.groups.iter().flatten().map(String::as_str);
+        let linked = self
+            .links
+            .iter()
+            .flat_map(|link| [link.a.as_str(), link.b.as_str()]);
+        grouped.chain(linked).collect()
+    }
+
+    /// Checks the faults only name `services` of the network, that groups are disjoint
+    /// and that links shape the traffic between two different services
+    pub fn validate(&self, services: &[String]) -> Result<()> {
+        let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        if let Some(unknown) = self
+            .services()
+            .into_iter()
+            .find(|service| !services.iter().any(|s| s == service))
+        {
+            return invalid(format!("Unknown service '{unknown}'"));
+        }
+        if !self.groups.is_empty() && self.groups.len() < 2 {
+            return invalid("A partition needs at least two groups".to_string());
+        }
+        let mut grouped = BTreeSet::new();
+        for service in self.groups.iter().flatten() {
+            if !grouped.insert(service) {
+                return invalid(format!("Service '{service}' is in several groups"));
+            }
+        }
+        for link in &self.links {
+            if link.a == link.b {
+                return invalid(format!("Link '{link}' has a single service"));
+            }
+            if link.latency_ms.is_none()
+                && link.loss_percent.is_none()
+                && link.bandwidth_kbit.is_none()
+            {
+                return invalid(format!("Link '{link}' has no latency, loss or bandwidth"));
+            }
+            if link
+                .loss_percent
+                .is_some_and(|loss| !(0.0..=100.0).contains(&loss))
+            {
+                return invalid(format!("Link '{link}' has a loss out of 0-100%"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Services `service` is partitioned from
+    fn partitioned_from(&self, service: &str) -> Vec<&str> {
+        match self
+            .groups
+            .iter()
+            .position(|g| g.iter().any(|s| s == service))
+        {
+            Some(own) => self
+                .groups
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != own)
+                .flat_map(|(_, group)| group.iter().map(String::as_str))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Links of `service`, with the service at their other end
+    fn links_of(&self, service: &str) -> Vec<(&str, &Link)> {
+        self.links
+            .iter()
+            .filter_map(|link| match service {
+                s if s == link.a => Some((link.b.as_str(), link)),
+                s if s == link.b => Some((link.a.as_str(), link)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Shell script run in the network namespace of `service` which removes the faults
+/// applied before and applies `faults`, given the `ips` of the services they name
+pub fn script(faults: &Faults, service: &str, ips: &BTreeMap<String, String>) -> Result<String> {
+    let ip = |service: &str| {
+        ips.get(service).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Service '{service}' has no IP address, is it running?"),
+            )
+        })
+    };
+
+    // removing faults which are not there fails, so it is not checked
+    let mut lines = vec![
+        format!("iptables -D INPUT -j {CHAIN} 2>/dev/null"),
+        format!("iptables -D OUTPUT -j {CHAIN} 2>/dev/null"),
+        format!("iptables -F {CHAIN} 2>/dev/null"),
+        format!("iptables -X {CHAIN} 2>/dev/null"),
+        format!("tc qdisc del dev {INTERFACE} root 2>/dev/null"),
+        "set -e".to_string(),
+    ];
+
+    let partitioned = faults.partitioned_from(service);
+    if !partitioned.is_empty() {
+        lines.push(format!("iptables -N {CHAIN}"));
+        lines.push(format!("iptables -I INPUT -j {CHAIN}"));
+        lines.push(format!("iptables -I OUTPUT -j {CHAIN}"));
+        for peer in partitioned {
+            let peer_ip = ip(peer)?;
+            lines.push(format!("iptables -A {CHAIN} -s {peer_ip} -j DROP"));
+            lines.push(format!("iptables -A {CHAIN} -d {peer_ip} -j DROP"));
+        }
+    }
+
+    // each link shapes the egress traffic to its other end in a class of its own,
+    // the rest of the traffic goes through the unshaped default class 1:1
+    let links = faults.links_of(service);
+    if !links.is_empty() {
+        lines.push(format!(
+            "tc qdisc add dev {INTERFACE} root handle 1: htb default 1"
+        ));
+        lines.push(format!(
+            "tc class add dev {INTERFACE} parent 1: classid 1:1 htb rate {UNSHAPED_RATE}"
+        ));
+        for (i, (peer, link)) in links.into_iter().enumerate() {
+            let class = format!("{:x}", i + 16);
+            let rate = link
+                .bandwidth_kbit
+                .map(|kbit| format!("{kbit}kbit"))
+                .unwrap_or_else(|| UNSHAPED_RATE.to_string());
+            lines.push(format!(
+                "tc class add dev {INTERFACE} parent 1: classid 1:{class} htb rate {rate}"
+            ));
+            let mut netem = String::new();
+            if let Some(latency) = link.latency_ms {
+                netem.push_str(&format!(" delay {latency}ms"));
+            }
+            if let Some(loss) = link.loss_percent {
+                netem.push_str(&format!(" loss {loss}%"));
+            }
+            if !netem.is_empty() {
+                lines.push(format!(
+                    "tc qdisc add dev {INTERFACE} parent 1:{class} handle {class}: netem{netem}"
+                ));
+            }
+            lines.push(format!(
+                "tc filter add dev {INTERFACE} parent 1: protocol ip prio 1 u32 match ip dst {}/32 flowid 1:{class}",
+                ip(peer)?
+            ));
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn services(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_groups_and_links() {
+        let groups: Groups = "mina-bp-1, mina-seed-1|mina-bp-2".parse().unwrap();
+        assert_eq!(
+            groups.0,
+            [
+                services(&["mina-bp-1", "mina-seed-1"]),
+                services(&["mina-bp-2"])
+            ]
+        );
+        assert!("mina-bp-1,mina-bp-2".parse::<Groups>().is_err());
+        assert!("mina-bp-1||mina-bp-2".parse::<Groups>().is_err());
+
+        let link: Link = "mina-bp-1:mina-bp-2,latency=200ms,loss=5%,bandwidth=1mbit"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            link,
+            Link {
+                a: "mina-bp-1".to_string(),
+                b: "mina-bp-2".to_string(),
+                latency_ms: Some(200),
+                loss_percent: Some(5.0),
+                bandwidth_kbit: Some(1_000),
+            }
+        );
+        assert_eq!(
+            link.to_string(),
+            "mina-bp-1:mina-bp-2,latency=200ms,loss=5%,bandwidth=1000kbit"
+        );
+        assert_eq!(link.to_string().parse::<Link>().unwrap(), link);
+        assert!("mina-bp-1".parse::<Link>().is_err());
+        assert!("a:b,jitter=10ms".parse::<Link>().is_err());
+        assert!("a:b,bandwidth=1mb".parse::<Link>().is_err());
+    }
+
+    #[test]
+    fn test_validate_faults() {
+        let network = services(&["mina-bp-1", "mina-bp-2", "mina-seed-1"]);
+        let link: Link = "mina-bp-1:mina-seed-1,latency=100".parse().unwrap();
+        let faults = Faults {
+            groups: vec![services(&["mina-bp-1"]), services(&["mina-bp-2"])],
+            links: vec![link.clone()],
+        };
+        assert!(faults.validate(&network).is_ok());
+        assert_eq!(
+            faults.services().into_iter().collect::<Vec<_>>(),
+            ["mina-bp-1", "mina-bp-2", "mina-seed-1"]
+        );
+
+        let unknown = Faults {
+            groups: vec![services(&["mina-bp-1"]), services(&["mina-bp-3"])],
+            ..Default::default()
+        };
+        assert!(unknown.validate(&network).is_err());
+        let overlapping = Faults {
+            groups: vec![services(&["mina-bp-1"]), services(&["mina-bp-1"])],
+            ..Default::default()
+        };
+        assert!(overlapping.validate(&network).is_err());
+        let unshaped = Faults {
+            links: vec!["mina-bp-1:mina-bp-2".parse().unwrap()],
+            ..Default::default()
+        };
+        assert!(unshaped.validate(&network).is_err());
+        let lossy = Faults {
+            links: vec!["mina-bp-1:mina-bp-2,loss=150".parse().unwrap()],
+            ..Default::default()
+        };
+        assert!(lossy.validate(&network).is_err());
+    }
+
+    #[test]
+    fn test_script() {
+        let faults = Faults {
+            groups: vec![
+                services(&["mina-bp-1", "mina-seed-1"]),
+                services(&["mina-bp-2"]),
+            ],
+            links: vec!["mina-bp-1:mina-seed-1,latency=100ms,bandwidth=500kbit"
+                .parse()
+                .unwrap()],
+        };
+        let ips = BTreeMap::from([
+            ("mina-bp-1".to_string(), "172.18.0.2".to_string()),
+            ("mina-bp-2".to_string(), "172.18.0.3".to_string()),
+            ("mina-seed-1".to_string(), "172.18.0.4".to_string()),
+        ]);
+
+        let script = script(&faults, "mina-bp-1", &ips).unwrap();
+        assert!(script.starts_with("iptables -D INPUT -j MINIMINA"));
+        assert!(script.contains("iptables -A MINIMINA -s 172.18.0.3 -j DROP"));
+        assert!(script.contains("iptables -A MINIMINA -d 172.18.0.3 -j DROP"));
+        assert!(!script.contains("172.18.0.4 -j DROP"));
+        assert!(script.contains("classid 1:10 htb rate 500kbit"));
+        assert!(script.contains("handle 10: netem delay 100ms"));
+        assert!(script.contains("match ip dst 172.18.0.4/32 flowid 1:10"));
+
+        let healed = super::script(&Faults::default(), "mina-bp-1", &ips).unwrap();
+        assert!(healed.ends_with("set -e"));
+
+        let unreachable = BTreeMap::from([("mina-bp-1".to_string(), "172.18.0.2".to_string())]);
+        let error = super::script(&faults, "mina-bp-2", &unreachable).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
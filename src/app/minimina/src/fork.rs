@@ -0,0 +1,334 @@
+//! # Fork Module
+//!
+//! Hard fork rehearsal: stops a network at a fork point and creates a successor network
+//! continuing its chain.
+//!
+//! The fork point is the best tip of one node, read once every other service is stopped so
+//! that the chain no longer moves, optionally after the tip reached a given slot. The node's
+//! staged and epoch ledgers are exported with `mina ledger export` and become the genesis and
+//! epoch ledgers of the successor's runtime config, next to a `proof.fork` section and a new
+//! genesis timestamp. The successor reuses the keys and topology of the network, with the
+//! images or binaries of the new release.
+
+use crate::{
+    backend::Backend,
+    commands,
+    directory_manager::DirectoryManager,
+    genesis_ledger::{ForkConfig, ProtocolConstants},
+    graphql::GraphQl,
+    output::network,
+    upgrade::Release,
+    wait::{self, Condition},
+};
+use log::info;
+use minimina::graphql_client::types::Block;
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::{self, Result},
+    time::Duration,
+};
+
+/// Ledgers exported from the fork node
+const LEDGERS: [&str; 3] = ["staged-ledger", "staking-epoch-ledger", "next-epoch-ledger"];
+
+pub struct Fork {
+    /// Node the fork point and ledgers are read from, the first with a graphql endpoint by default
+    pub node: Option<String>,
+    /// Global slot the node's best tip must reach before the fork, and how long to wait for it
+    pub slot: Option<(u64, Duration)>,
+    /// Network identifier of the successor
+    pub successor: String,
+    /// Images or binaries of the successor's services
+    pub release: Release,
+}
+
+/// Forks network `network_id` into a successor network
+pub struct Forker<'a> {
+    pub backend: &'a dyn Backend,
+    /// Backend of the successor network
+    pub successor_backend: &'a dyn Backend,
+    pub directory_manager: &'a DirectoryManager,
+    pub graphql: &'a GraphQl,
+    pub network_id: &'a str,
+}
+
+impl Forker<'_> {
+    pub fn run(&self, fork: &Fork) -> Result<network::Fork> {
+        if self.directory_manager.network_path_exists(&fork.successor) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Network '{}' already exists", fork.successor),
+            ));
+        }
+        let services = self.directory_manager.get_services_info(self.network_id)?;
+        let endpoints = self.graphql.endpoints(self.network_id)?;
+        let node = match &fork.node {
+            Some(node) if endpoints.contains_key(node) => node.clone(),
+            Some(node) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Node '{node}' does not exist or has no graphql endpoint"),
+                ))
+            }
+            None => services
+                .iter()
+                .map(|service| &service.service_name)
+                .find(|name| endpoints.contains_key(*name))
+                .cloned()
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "No node has a graphql endpoint")
+                })?,
+        };
+        let service = services
+            .iter()
+            .find(|service| service.service_name == node)
+            .expect("Nodes with an endpoint are services of the network");
+
+        if let Some((slot, timeout)) = fork.slot {
+            let report = wait::wait(
+                self.graphql,
+                self.network_id,
+                std::slice::from_ref(&node),
+                &Condition::Slot(slot),
+                timeout,
+            )?;
+            if !report.met {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "Node '{node}' did not reach slot {slot} within {}s",
+                        timeout.as_secs()
+                    ),
+                ));
+            }
+        }
+
+        info!(
+            "Stopping the services of network '{}' but '{node}'.",
+            self.network_id
+        );
+        for other in services.iter().filter(|s| s.service_name != node) {
+            self.backend.stop_service(&other.service_name)?;
+        }
+        let client = self.graphql.client(&node, self.network_id)?;
+        let best_tip = || {
+            client
+                .best_chain(1)?
+                .pop()
+                .ok_or_else(|| io::Error::other(format!("Node '{node}' has no best tip")))
+        };
+        let tip = best_tip()?;
+        info!("Exporting the ledgers of '{node}' at {}.", tip.state_hash);
+        let ledgers = LEDGERS
+            .iter()
+            .map(|ledger| {
+                let accounts = self.backend.export_ledger(service, ledger)?;
+                serde_json::from_str(&accounts).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid {ledger} exported from '{node}': {e}"),
+                    )
+                })
+            })
+            .collect::<Result<Vec<Value>>>()?;
+        // a block producer fork node may still extend the chain
+        if best_tip()?.state_hash != tip.state_hash {
+            return Err(io::Error::other(format!(
+                "Best tip of '{node}' changed while exporting its ledgers, fork from a node which is not a block producer"
+            )));
+        }
+        commands::stop_network(self.backend, self.directory_manager, self.network_id)?;
+
+        let fork_config = fork_config(&tip)?;
+        let mut successor_services =
+            commands::copy_network(self.directory_manager, self.network_id, &fork.successor)?;
+        for service in successor_services.iter_mut() {
+            fork.release.apply(service);
+        }
+        let genesis_ledger_path = self.directory_manager.genesis_ledger_path(&fork.successor);
+        let mut runtime_config: Value =
+            serde_json::from_str(&fs::read_to_string(&genesis_ledger_path)?)?;
+        set_fork_ledgers(&mut runtime_config, &tip, &ledgers)?;
+        fs::write(
+            &genesis_ledger_path,
+            serde_json::to_string_pretty(&runtime_config)?,
+        )?;
+        ProtocolConstants {
+            fork: Some(fork_config.clone()),
+            ..Default::default()
+        }
+        .write(&self.directory_manager.network_path(&fork.successor))?;
+        self.directory_manager
+            .overwrite_genesis_timestamp(&fork.successor, &genesis_ledger_path)?;
+
+        let successor = commands::create_network(
+            self.successor_backend,
+            self.directory_manager,
+            &fork.successor,
+            &successor_services,
+        )?;
+        Ok(network::Fork {
+            network_id: self.network_id.to_string(),
+            node,
+            fork: fork_config,
+            accounts: ledgers[0].as_array().map_or(0, Vec::len),
+            successor,
+        })
+    }
+}
+
+/// Fork point of the successor, at block `tip`
+fn fork_config(tip: &Block) -> Result<ForkConfig> {
+    let consensus_state = &tip.protocol_state.consensus_state;
+    let to_u32 = |value: u64| {
+        u32::try_from(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    };
+    Ok(ForkConfig {
+        state_hash: tip.state_hash.clone(),
+        blockchain_length: to_u32(consensus_state.block_height)?,
+        global_slot_since_genesis: to_u32(consensus_state.slot_since_genesis)?,
+    })
+}
+
+/// Replaces the genesis ledger of `runtime_config` by the staged ledger of `ledgers`, and its
+/// epoch ledgers by the staking and next epoch ledgers with the seeds of `tip`
+fn set_fork_ledgers(runtime_config: &mut Value, tip: &Block, ledgers: &[Value]) -> Result<()> {
+    let [staged, staking, next] = ledgers else {
+        unreachable!("One ledger is exported for each of LEDGERS")
+    };
+    let config = runtime_config.as_object_mut().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Runtime config is not a JSON object",
+        )
+    })?;
+    config.insert("ledger".to_string(), json!({ "accounts": staged }));
+    let consensus_state = &tip.protocol_state.consensus_state;
+    config.insert(
+        "epoch_data".to_string(),
+        json!({
+            "staking": { "seed": consensus_state.staking_epoch_data.seed, "accounts": staking },
+            "next": { "seed": consensus_state.next_epoch_data.seed, "accounts": next },
+        }),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::mock::MockBackend,
+        service::{ServiceConfig, ServiceType},
+    };
+    use tempdir::TempDir;
+
+    fn tip() -> Block {
+        serde_json::from_value(json!({
+            "stateHash": "3NKtip",
+            "creator": "B62qbp1",
+            "commandTransactionCount": 0,
+            "protocolState": {
+                "previousStateHash": "3NKparent",
+                "consensusState": {
+                    "blockHeight": "42", "epoch": "1", "slot": "5", "slotSinceGenesis": "53",
+                    "stakingEpochData": { "seed": "2vaStaking" },
+                    "nextEpochData": { "seed": "2vaNext" }
+                }
+            },
+            "transactions": { "userCommands": [], "zkappCommands": [] }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_fork_runtime_config() {
+        let tip = tip();
+        assert_eq!(
+            fork_config(&tip).unwrap(),
+            ForkConfig {
+                state_hash: "3NKtip".to_string(),
+                blockchain_length: 42,
+                global_slot_since_genesis: 53,
+            }
+        );
+
+        let mut runtime_config = json!({
+            "genesis": { "k": 10, "genesis_state_timestamp": "2024-01-01T00:00:00Z" },
+            "proof": { "level": "none" },
+            "ledger": { "name": "release", "accounts": [{ "pk": "B62qold", "balance": "1" }] }
+        });
+        let ledgers = [
+            json!([{ "pk": "B62qstaged", "balance": "2" }]),
+            json!([{ "pk": "B62qstaking", "balance": "3" }]),
+            json!([]),
+        ];
+        set_fork_ledgers(&mut runtime_config, &tip, &ledgers).unwrap();
+        assert_eq!(
+            runtime_config,
+            json!({
+                "genesis": { "k": 10, "genesis_state_timestamp": "2024-01-01T00:00:00Z" },
+                "proof": { "level": "none" },
+                "ledger": { "accounts": [{ "pk": "B62qstaged", "balance": "2" }] },
+                "epoch_data": {
+                    "staking": {
+                        "seed": "2vaStaking",
+                        "accounts": [{ "pk": "B62qstaking", "balance": "3" }]
+                    },
+                    "next": { "seed": "2vaNext", "accounts": [] }
+                }
+            })
+        );
+        assert!(set_fork_ledgers(&mut json!([]), &tip, &ledgers).is_err());
+    }
+
+    #[test]
+    fn test_fork_checks_before_stopping() {
+        let tempdir = TempDir::new("test_fork").expect("Cannot create temporary directory");
+        let directory_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        let services = [ServiceConfig {
+            service_name: "mina-seed-1".to_string(),
+            service_type: ServiceType::Seed,
+            client_port: Some(3100),
+            ..Default::default()
+        }];
+        for network_id in ["test", "taken"] {
+            directory_manager
+                .generate_dir_structure(network_id)
+                .unwrap();
+            directory_manager
+                .save_network_info(network_id, &services)
+                .unwrap();
+            directory_manager
+                .save_services_info(network_id, &services)
+                .unwrap();
+        }
+        let backend = MockBackend::default();
+        let graphql = GraphQl::new(directory_manager.clone());
+        let forker = Forker {
+            backend: &backend,
+            successor_backend: &backend,
+            directory_manager: &directory_manager,
+            graphql: &graphql,
+            network_id: "test",
+        };
+        let fork = |node: Option<&str>, successor: &str| Fork {
+            node: node.map(str::to_string),
+            slot: None,
+            successor: successor.to_string(),
+            release: Release {
+                image: Some("mina-daemon:fork".to_string()),
+                archive_image: None,
+                bin_path: None,
+            },
+        };
+
+        let err = forker.run(&fork(None, "taken")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        let err = forker.run(&fork(Some("mina-bp-9"), "next")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(backend.calls().is_empty());
+        assert!(!directory_manager.network_path_exists("next"));
+    }
+}
@@ -10,15 +10,23 @@ use chrono::prelude::*;
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
+use std::str::FromStr;
 
 use crate::keys::NodeKey;
+use minimina::amounts::{convert_balance_to_decimal_mina, parse_iso_duration, AmountParseError};
 
 pub(crate) const GENESIS_LEDGER_JSON: &str = "genesis_ledger.json";
 pub(crate) const REPLAYER_INPUT_JSON: &str = "replayer_input.json";
 
+/// Replayer input file of an archive node, relative to the network directory
+pub fn replayer_input_file(archive_name: &str) -> String {
+    format!("{archive_name}_{REPLAYER_INPUT_JSON}")
+}
+
 /// Genesis ledger format
 #[derive(Serialize, Deserialize)]
 struct GenesisLedger {
@@ -47,7 +55,7 @@ struct Account {
     timing: Option<Timing>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 struct Timing {
     initial_minimum_balance: String,
     cliff_time: String,
@@ -56,6 +64,189 @@ struct Timing {
     vesting_increment: String,
 }
 
+/// Slot duration (`proof.block_window_duration_ms`) the daemons are compiled with,
+/// unless the runtime config sets another
+pub const BLOCK_WINDOW_DURATION_MS: u64 = 180_000;
+
+/// Fork point of a network continuing the chain of another (`proof.fork`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForkConfig {
+    pub state_hash: String,
+    pub blockchain_length: u32,
+    pub global_slot_since_genesis: u32,
+}
+
+/// Protocol constants written to the runtime config. Those not set keep the value
+/// of the given genesis ledger, or the daemons' compiled defaults.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProtocolConstants {
+    pub k: Option<u32>,
+    pub delta: Option<u32>,
+    pub slots_per_epoch: Option<u32>,
+    pub slots_per_sub_window: Option<u32>,
+    pub block_window_duration_ms: Option<u64>,
+    pub txpool_max_size: Option<u32>,
+    /// Maximum number of transactions per block, as a power of 2
+    pub transaction_capacity: Option<u32>,
+    pub fork: Option<ForkConfig>,
+}
+
+impl ProtocolConstants {
+    /// Slot duration of the network, timing durations are converted to slots with it
+    pub fn block_window_duration_ms(&self) -> u64 {
+        self.block_window_duration_ms
+            .unwrap_or(BLOCK_WINDOW_DURATION_MS)
+    }
+
+    /// Write the constants that are set into the runtime config of the network at `network_path`
+    pub fn write(&self, network_path: &Path) -> std::io::Result<()> {
+        let path = network_path.join(GENESIS_LEDGER_JSON);
+        let mut runtime_config: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        self.apply(&mut runtime_config)?;
+        std::fs::write(path, serde_json::to_string_pretty(&runtime_config)?)
+    }
+
+    fn apply(&self, runtime_config: &mut serde_json::Value) -> std::io::Result<()> {
+        use serde_json::{json, Value};
+
+        let invalid =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let fork = self.fork.as_ref().map(serde_json::to_value).transpose()?;
+        let constants = [
+            ("genesis", "k", self.k.map(Value::from)),
+            ("genesis", "delta", self.delta.map(Value::from)),
+            (
+                "genesis",
+                "slots_per_epoch",
+                self.slots_per_epoch.map(Value::from),
+            ),
+            (
+                "genesis",
+                "slots_per_sub_window",
+                self.slots_per_sub_window.map(Value::from),
+            ),
+            (
+                "proof",
+                "block_window_duration_ms",
+                self.block_window_duration_ms.map(Value::from),
+            ),
+            (
+                "proof",
+                "transaction_capacity",
+                self.transaction_capacity
+                    .map(|log2| json!({ "2_to_the": log2 })),
+            ),
+            ("proof", "fork", fork),
+            (
+                "daemon",
+                "txpool_max_size",
+                self.txpool_max_size.map(Value::from),
+            ),
+        ];
+
+        let config = runtime_config
+            .as_object_mut()
+            .ok_or_else(|| invalid("Runtime config is not a JSON object".to_string()))?;
+        for (section, key, value) in constants {
+            let Some(value) = value else { continue };
+            config
+                .entry(section)
+                .or_insert_with(|| json!({}))
+                .as_object_mut()
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "`{section}` of the runtime config is not an object"
+                    ))
+                })?
+                .insert(key.to_string(), value);
+        }
+
+        Ok(())
+    }
+}
+
+/// A number of slots, or an ISO-8601 duration (`PT86400S`) converted to slots
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Slots {
+    Slots(u64),
+    Duration(String),
+}
+
+impl Slots {
+    /// Number of slots, a duration is rounded up to whole slots of `block_window_duration_ms`
+    pub fn to_slots(&self, block_window_duration_ms: u64) -> Result<u64, AmountParseError> {
+        match self {
+            Slots::Slots(slots) => Ok(*slots),
+            Slots::Duration(duration) => {
+                let millis = parse_iso_duration(duration)? * 1000.0;
+                Ok((millis / block_window_duration_ms as f64).ceil() as u64)
+            }
+        }
+    }
+}
+
+impl FromStr for Slots {
+    type Err = AmountParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<u64>() {
+            Ok(slots) => Ok(Slots::Slots(slots)),
+            Err(_) => {
+                parse_iso_duration(s)?;
+                Ok(Slots::Duration(s.to_string()))
+            }
+        }
+    }
+}
+
+/// Vesting schedule of a timed account, amounts in the amount DSL,
+/// times in slots or ISO-8601 durations
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimingSpec {
+    pub initial_minimum_balance: String,
+    pub cliff_time: Slots,
+    pub cliff_amount: String,
+    pub vesting_period: Slots,
+    pub vesting_increment: String,
+}
+
+impl TimingSpec {
+    /// Ledger timing of the spec, `invalid` builds the error of a field that can't be converted
+    fn timing(
+        &self,
+        block_window_duration_ms: u64,
+        invalid: impl Fn(&str, String) -> std::io::Error,
+    ) -> std::io::Result<Timing> {
+        let amount = |field: &str, amount: &str| {
+            convert_balance_to_decimal_mina(amount).map_err(|e| invalid(field, e.to_string()))
+        };
+        let slots = |field: &str, slots: &Slots| {
+            slots
+                .to_slots(block_window_duration_ms)
+                .map_err(|e| invalid(field, e.to_string()))
+        };
+
+        let vesting_period = slots("vesting_period", &self.vesting_period)?;
+        if vesting_period == 0 {
+            return Err(invalid("vesting_period", "must be at least 1 slot".into()));
+        }
+
+        Ok(Timing {
+            initial_minimum_balance: amount(
+                "initial_minimum_balance",
+                &self.initial_minimum_balance,
+            )?,
+            cliff_time: slots("cliff_time", &self.cliff_time)?.to_string(),
+            cliff_amount: amount("cliff_amount", &self.cliff_amount)?,
+            vesting_period: vesting_period.to_string(),
+            vesting_increment: amount("vesting_increment", &self.vesting_increment)?,
+        })
+    }
+}
+
 /// Replayer input format
 #[derive(Serialize, Deserialize)]
 struct ReplayerInput {
@@ -78,12 +269,21 @@ pub mod default {
     pub struct LedgerGenerator;
 
     impl LedgerGenerator {
-        /// Generate default genesis ledger
+        /// Generate default genesis ledger, every account vests according to `timing` if given.
+        /// Timing durations are converted to slots of `block_window_duration_ms`.
         pub fn generate(
             network_path: &Path,
             bp_keys: &HashMap<String, NodeKey>,
+            timing: Option<&TimingSpec>,
+            block_window_duration_ms: u64,
         ) -> std::io::Result<()> {
             info!("Generating default genesis ledger.");
+            let timing = match timing {
+                Some(timing) => Some(timing.timing(block_window_duration_ms, |field, e| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("`{field}`: {e}"))
+                })?),
+                None => None,
+            };
             let accounts: Vec<Account> = bp_keys
                 .values()
                 .map(|key_info| Account {
@@ -91,7 +291,7 @@ pub mod default {
                     sk: None,
                     balance: "11550000.000000000".into(),
                     delegate: None,
-                    timing: None,
+                    timing: timing.clone(),
                 })
                 .collect();
 
@@ -120,8 +320,11 @@ pub mod default {
             Ok(())
         }
 
-        /// Generate replayer input file out of genesis ledger
-        pub fn generate_replayer_input(network_path: &Path) -> std::io::Result<()> {
+        /// Generate the replayer input file of an archive node out of genesis ledger
+        pub fn generate_replayer_input(
+            network_path: &Path,
+            archive_name: &str,
+        ) -> std::io::Result<()> {
             let genesis_ledger_file = network_path.join(GENESIS_LEDGER_JSON);
             let genesis_ledger = serde_json::from_str::<GenesisLedger>(&std::fs::read_to_string(
                 genesis_ledger_file,
@@ -139,7 +342,7 @@ pub mod default {
 
             let content = serde_json::to_string_pretty(&replayer_input)?;
 
-            let output_file = network_path.join(REPLAYER_INPUT_JSON);
+            let output_file = network_path.join(replayer_input_file(archive_name));
             let mut file = File::create(output_file)?;
             file.write_all(content.as_bytes())?;
 
@@ -148,21 +351,429 @@ pub mod default {
     }
 }
 
+/// Genesis ledger generated from a declarative spec (`network create --ledger-spec`)
+pub mod spec {
+
+    use super::*;
+    use minimina::amounts::parse_account_spec;
+    use std::collections::BTreeMap;
+    use std::io;
+
+    /// Balance of the nodes' accounts, unless the spec sets `node_balance`
+    const DEFAULT_NODE_BALANCE: &str = "11550000mina";
+
+    /// Ledger spec, e.g.
+    /// `{"tiers": {"whale": {"count": 2, "balance": "1000000mina"},
+    ///             "fish": {"count": 10, "balance": "0.25mina", "delegate": "whale-0"}}}`
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct LedgerSpec {
+        /// Balance (amount DSL) of the account of every node in the network
+        pub node_balance: Option<String>,
+        /// Account tiers by name, each account is named `<tier>-<index>`
+        pub tiers: BTreeMap<String, TierSpec>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct TierSpec {
+        pub count: u32,
+        /// Balance (amount DSL) of each account
+        pub balance: String,
+        /// A tier account (`whale-0`) or a node name the accounts delegate to
+        pub delegate: Option<String>,
+        pub timing: Option<TimingSpec>,
+    }
+
+    fn invalid(tier: &str, field: &str, e: impl fmt::Display) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("tier '{tier}': `{field}`: {e}"),
+        )
+    }
+
+    fn amount(tier: &str, field: &str, amount: &str) -> io::Result<String> {
+        convert_balance_to_decimal_mina(amount)
+            .map_err(|e: AmountParseError| invalid(tier, field, e))
+    }
+
+    impl LedgerSpec {
+        /// Read the spec at `path` and check its tier names
+        pub fn from_file(path: &Path) -> io::Result<Self> {
+            let spec: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            for tier in spec.tiers.keys() {
+                parse_account_spec(&format!("{tier}-0")).map_err(|_| {
+                    invalid(
+                        tier,
+                        "tiers",
+                        "names must be alphanumeric, starting with a letter",
+                    )
+                })?;
+            }
+            Ok(spec)
+        }
+
+        /// Names of the tier accounts, `<tier>-<index>`, each needs a keypair
+        pub fn account_names(&self) -> Vec<String> {
+            self.tiers
+                .iter()
+                .flat_map(|(tier, spec)| (0..spec.count).map(move |i| format!("{tier}-{i}")))
+                .collect()
+        }
+    }
+
+    pub struct LedgerGenerator;
+
+    impl LedgerGenerator {
+        /// Generate the genesis ledger of `spec`.
+        /// `node_keys` are the public keys of the network's nodes by name,
+        /// `account_keys` the keypairs of the spec's [`LedgerSpec::account_names`].
+        /// Timing durations are converted to slots of `block_window_duration_ms`.
+        pub fn generate(
+            network_path: &Path,
+            spec: &LedgerSpec,
+            node_keys: &HashMap<String, String>,
+            account_keys: &HashMap<String, NodeKey>,
+            block_window_duration_ms: u64,
+        ) -> io::Result<()> {
+            info!("Generating genesis ledger from spec.");
+            let accounts = Self::accounts(spec, node_keys, account_keys, block_window_duration_ms)?;
+            let genesis_ledger = GenesisLedger {
+                genesis: Genesis {
+                    genesis_state_timestamp: current_timestamp(),
+                },
+                ledger: Ledger {
+                    name: None,
+                    accounts,
+                },
+            };
+
+            let content = serde_json::to_string_pretty(&genesis_ledger)?;
+            debug!("Generated genesis ledger: {}", content);
+            std::fs::write(network_path.join(GENESIS_LEDGER_JSON), content)
+        }
+
+        /// Node accounts sorted by name, followed by the tier accounts
+        fn accounts(
+            spec: &LedgerSpec,
+            node_keys: &HashMap<String, String>,
+            account_keys: &HashMap<String, NodeKey>,
+            block_window_duration_ms: u64,
+        ) -> io::Result<Vec<Account>> {
+            let node_balance = spec.node_balance.as_deref().unwrap_or(DEFAULT_NODE_BALANCE);
+            let node_balance = amount("nodes", "node_balance", node_balance)?;
+            let mut nodes: Vec<(&String, &String)> = node_keys.iter().collect();
+            nodes.sort();
+
+            let mut accounts: Vec<Account> = nodes
+                .into_iter()
+                .map(|(_, pk)| Account {
+                    pk: pk.clone(),
+                    sk: None,
+                    balance: node_balance.clone(),
+                    delegate: None,
+                    timing: None,
+                })
+                .collect();
+
+            let account_pk = |name: &str| -> io::Result<String> {
+                account_keys
+                    .get(name)
+                    .map(|key| key.key_string.clone())
+                    .ok_or_else(|| io::Error::other(format!("No keypair for account '{name}'")))
+            };
+
+            for (tier, tier_spec) in &spec.tiers {
+                let balance = amount(tier, "balance", &tier_spec.balance)?;
+                let delegate = match &tier_spec.delegate {
+                    Some(delegate) => Some(
+                        Self::delegate(spec, delegate, node_keys, &account_pk)
+                            .map_err(|e| invalid(tier, "delegate", e))?,
+                    ),
+                    None => None,
+                };
+                let timing = match &tier_spec.timing {
+                    Some(timing) => Some(
+                        timing
+                            .timing(block_window_duration_ms, |field, e| invalid(tier, field, e))?,
+                    ),
+                    None => None,
+                };
+
+                for i in 0..tier_spec.count {
+                    accounts.push(Account {
+                        pk: account_pk(&format!("{tier}-{i}"))?,
+                        sk: None,
+                        balance: balance.clone(),
+                        delegate: delegate.clone(),
+                        timing: timing.clone(),
+                    });
+                }
+            }
+
+            Ok(accounts)
+        }
+
+        /// Public key of `delegate`, a tier account of the spec or a node
+        fn delegate(
+            spec: &LedgerSpec,
+            delegate: &str,
+            node_keys: &HashMap<String, String>,
+            account_pk: &dyn Fn(&str) -> io::Result<String>,
+        ) -> io::Result<String> {
+            if let Ok((tier, index)) = parse_account_spec(delegate) {
+                if let Some(tier_spec) = spec.tiers.get(&tier) {
+                    if index >= tier_spec.count {
+                        return Err(io::Error::other(format!(
+                            "'{delegate}' is out of range, tier '{tier}' has {} accounts",
+                            tier_spec.count
+                        )));
+                    }
+                    return account_pk(delegate);
+                }
+            }
+
+            node_keys.get(delegate).cloned().ok_or_else(|| {
+                io::Error::other(format!("'{delegate}' is neither a tier account nor a node"))
+            })
+        }
+    }
+}
+
+/// Genesis ledger imported from a `mina ledger export` dump (`network create --ledger-from`)
+pub mod import {
+
+    use super::*;
+    use minimina::amounts::amount_dsl_to_nanomina;
+    use serde_json::{json, Map, Value};
+    use std::collections::HashSet;
+    use std::io;
+
+    /// Which accounts of the exported ledger are kept
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Subset {
+        All,
+        /// The accounts with the largest balances
+        Top(usize),
+        /// Accounts evenly spread over the balance ranking, keeping the shape of the distribution
+        Sample(usize),
+    }
+
+    /// Exported account with its balance in nanomina
+    struct ExportedAccount {
+        fields: Map<String, Value>,
+        pk: String,
+        nanomina: u64,
+    }
+
+    impl ExportedAccount {
+        fn delegate(&self) -> &str {
+            self.fields
+                .get("delegate")
+                .and_then(Value::as_str)
+                .unwrap_or(&self.pk)
+        }
+    }
+
+    fn invalid(message: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    }
+
+    pub struct LedgerImporter;
+
+    impl LedgerImporter {
+        /// Generate the genesis ledger from the exported ledger at `ledger_path`, an array of
+        /// accounts or a runtime config. The block producers' `(name, public key)`s, sorted by
+        /// name, take over the accounts `replace_keys`, or those with the largest delegated stake.
+        pub fn generate(
+            network_path: &Path,
+            ledger_path: &Path,
+            subset: Subset,
+            replace_keys: &[String],
+            block_producer_keys: &[(String, String)],
+        ) -> io::Result<()> {
+            info!("Importing genesis ledger from '{}'.", ledger_path.display());
+            let exported: Value = serde_json::from_str(&std::fs::read_to_string(ledger_path)?)?;
+            let accounts = Self::accounts(exported, subset, replace_keys, block_producer_keys)?;
+            let genesis_ledger = json!({
+                "genesis": { "genesis_state_timestamp": current_timestamp() },
+                "ledger": { "accounts": accounts },
+            });
+
+            let content = serde_json::to_string_pretty(&genesis_ledger)?;
+            std::fs::write(network_path.join(GENESIS_LEDGER_JSON), content)
+        }
+
+        fn accounts(
+            exported: Value,
+            subset: Subset,
+            replace_keys: &[String],
+            block_producer_keys: &[(String, String)],
+        ) -> io::Result<Vec<Value>> {
+            let accounts = Self::parse(exported)?;
+            let mut accounts = Self::subset(accounts, subset, replace_keys)?;
+
+            // delegates left out of the subset can't produce blocks, stake with themselves
+            let pks: HashSet<String> = accounts.iter().map(|a| a.pk.clone()).collect();
+            let mut undelegated = 0;
+            for account in &mut accounts {
+                if !pks.contains(account.delegate()) {
+                    account.fields.remove("delegate");
+                    undelegated += 1;
+                }
+            }
+            if undelegated > 0 {
+                info!("{undelegated} accounts delegated outside the imported subset, they now stake with themselves.");
+            }
+
+            if replace_keys.len() > block_producer_keys.len() {
+                return Err(invalid(format!(
+                    "Cannot replace the keys of {} accounts with {} block producers",
+                    replace_keys.len(),
+                    block_producer_keys.len()
+                )));
+            }
+            let replaced = if replace_keys.is_empty() {
+                Self::largest_delegates(&accounts)
+            } else {
+                replace_keys.to_vec()
+            };
+            let replacements: HashMap<String, String> = replaced
+                .into_iter()
+                .zip(block_producer_keys)
+                .map(|(old_pk, (name, new_pk))| {
+                    info!("Block producer '{name}' takes over account '{old_pk}'.");
+                    (old_pk, new_pk.clone())
+                })
+                .collect();
+
+            // taken over accounts stake with their block producer, delegations to them follow
+            Ok(accounts
+                .into_iter()
+                .map(|mut account| {
+                    if let Some(new_pk) = replacements.get(&account.pk) {
+                        account.fields.insert("pk".to_string(), json!(new_pk));
+                        account.fields.remove("delegate");
+                    } else if let Some(new_pk) = replacements.get(account.delegate()) {
+                        account.fields.insert("delegate".to_string(), json!(new_pk));
+                    }
+                    Value::Object(account.fields)
+                })
+                .collect())
+        }
+
+        /// Accounts of an exported ledger, or of the `ledger` of a runtime config
+        fn parse(exported: Value) -> io::Result<Vec<ExportedAccount>> {
+            let accounts = match exported {
+                Value::Array(accounts) => accounts,
+                Value::Object(mut config) => match config
+                    .get_mut("ledger")
+                    .and_then(|ledger| ledger.get_mut("accounts"))
+                    .map(Value::take)
+                {
+                    Some(Value::Array(accounts)) => accounts,
+                    _ => return Err(invalid("No `ledger.accounts` in runtime config".into())),
+                },
+                _ => {
+                    return Err(invalid(
+                        "Exported ledger is not an array of accounts".into(),
+                    ))
+                }
+            };
+
+            accounts
+                .into_iter()
+                .enumerate()
+                .map(|(i, account)| {
+                    let Value::Object(fields) = account else {
+                        return Err(invalid(format!("Account {i} is not an object")));
+                    };
+                    let pk = fields.get("pk").and_then(Value::as_str);
+                    let balance = fields.get("balance").and_then(Value::as_str);
+                    let (Some(pk), Some(balance)) = (pk, balance) else {
+                        return Err(invalid(format!("Account {i} has no `pk` or `balance`")));
+                    };
+                    let nanomina = amount_dsl_to_nanomina(&format!("{balance}mina"))
+                        .map_err(|e| invalid(format!("Account '{pk}': {e}")))?;
+                    Ok(ExportedAccount {
+                        pk: pk.to_string(),
+                        nanomina,
+                        fields,
+                    })
+                })
+                .collect()
+        }
+
+        /// The `replace_keys` accounts and the `subset`, in the order of the exported ledger
+        fn subset(
+            accounts: Vec<ExportedAccount>,
+            subset: Subset,
+            replace_keys: &[String],
+        ) -> io::Result<Vec<ExportedAccount>> {
+            for pk in replace_keys {
+                if !accounts.iter().any(|account| &account.pk == pk) {
+                    return Err(invalid(format!(
+                        "'{pk}' is not an account of the exported ledger"
+                    )));
+                }
+            }
+
+            let mut ranking: Vec<usize> = (0..accounts.len()).collect();
+            ranking.sort_by_key(|&i| std::cmp::Reverse(accounts[i].nanomina));
+            let kept: HashSet<usize> = match subset {
+                Subset::All => return Ok(accounts),
+                Subset::Top(n) => ranking.into_iter().take(n).collect(),
+                Subset::Sample(n) => {
+                    let n = n.min(ranking.len());
+                    (0..n).map(|i| ranking[i * ranking.len() / n]).collect()
+                }
+            };
+
+            Ok(accounts
+                .into_iter()
+                .enumerate()
+                .filter(|(i, account)| kept.contains(i) || replace_keys.contains(&account.pk))
+                .map(|(_, account)| account)
+                .collect())
+        }
+
+        /// Delegates by stake delegated to them, the largest first
+        fn largest_delegates(accounts: &[ExportedAccount]) -> Vec<String> {
+            let mut stakes: HashMap<&str, u64> = HashMap::new();
+            for account in accounts {
+                *stakes.entry(account.delegate()).or_default() += account.nanomina;
+            }
+            let mut delegates: Vec<(&str, u64)> = stakes.into_iter().collect();
+            delegates.sort_by(|(pk_a, stake_a), (pk_b, stake_b)| {
+                stake_b.cmp(stake_a).then(pk_a.cmp(pk_b))
+            });
+            delegates
+                .into_iter()
+                .map(|(pk, _)| pk.to_string())
+                .collect()
+        }
+    }
+}
+
 pub fn current_timestamp() -> String {
     let datetime = Local::now();
     datetime.format("%Y-%m-%dT%H:%M:%S%.6f%Z").to_string()
 }
 
-pub fn set_slot_since_genesis(network_path: &Path, slot_since_genesis: u64) -> std::io::Result<()> {
-    let replayer_input_file = network_path.join(REPLAYER_INPUT_JSON);
+pub fn set_slot_since_genesis(
+    network_path: &Path,
+    archive_name: &str,
+    slot_since_genesis: u64,
+) -> std::io::Result<()> {
+    let input_file = network_path.join(replayer_input_file(archive_name));
     let mut replayer_input =
-        serde_json::from_str::<ReplayerInput>(&std::fs::read_to_string(replayer_input_file)?)?;
+        serde_json::from_str::<ReplayerInput>(&std::fs::read_to_string(&input_file)?)?;
 
     replayer_input.start_slot_since_genesis = slot_since_genesis;
 
     let content = serde_json::to_string_pretty(&replayer_input)?;
 
-    let output_file = network_path.join(REPLAYER_INPUT_JSON);
+    let output_file = input_file;
     let mut file = File::create(output_file)?;
     file.write_all(content.as_bytes())?;
 
@@ -187,7 +798,12 @@ mod tests {
             key_path: "test_key_path".to_string(),
         };
         bp_keys_map.insert("node0".to_string(), service_key);
-        let result = default::LedgerGenerator::generate(network_path, &bp_keys_map);
+        let result = default::LedgerGenerator::generate(
+            network_path,
+            &bp_keys_map,
+            None,
+            BLOCK_WINDOW_DURATION_MS,
+        );
         println!("{:?}", result);
         assert!(result.is_ok());
 
@@ -200,6 +816,156 @@ mod tests {
         assert!(content.contains("test_key"));
     }
 
+    #[test]
+    fn test_slots() {
+        assert_eq!("480".parse::<Slots>().unwrap(), Slots::Slots(480));
+        assert_eq!(
+            "PT86400S".parse::<Slots>().unwrap(),
+            Slots::Duration("PT86400S".to_string())
+        );
+        assert!("1d".parse::<Slots>().is_err());
+
+        assert_eq!(Slots::Slots(7).to_slots(BLOCK_WINDOW_DURATION_MS), Ok(7));
+        let day = Slots::Duration("PT86400S".to_string());
+        assert_eq!(day.to_slots(BLOCK_WINDOW_DURATION_MS), Ok(480));
+        // partial slots are rounded up
+        let duration = Slots::Duration("PT90.5S".to_string());
+        assert_eq!(duration.to_slots(BLOCK_WINDOW_DURATION_MS), Ok(1));
+        assert_eq!(duration.to_slots(20_000), Ok(5));
+    }
+
+    #[test]
+    fn test_generate_default_ledger_with_timing() {
+        let tempdir = TempDir::new("test_generate_default_ledger_with_timing")
+            .expect("Cannot create temporary directory");
+        let network_path = tempdir.path();
+        let bp_keys_map = HashMap::from([(
+            "node0".to_string(),
+            NodeKey {
+                key_string: "test_key".to_string(),
+                key_path: "test_key_path".to_string(),
+            },
+        )]);
+        let mut timing = TimingSpec {
+            initial_minimum_balance: "10000000mina".to_string(),
+            cliff_time: Slots::Duration("PT86400S".to_string()),
+            cliff_amount: "500000mina".to_string(),
+            vesting_period: Slots::Slots(1),
+            vesting_increment: "0.5mina".to_string(),
+        };
+
+        default::LedgerGenerator::generate(
+            network_path,
+            &bp_keys_map,
+            Some(&timing),
+            BLOCK_WINDOW_DURATION_MS,
+        )
+        .unwrap();
+
+        let content = std::fs::read_to_string(network_path.join(GENESIS_LEDGER_JSON)).unwrap();
+        let ledger: GenesisLedger = serde_json::from_str(&content).unwrap();
+        let timing_json = serde_json::to_value(ledger.ledger.accounts[0].timing.as_ref()).unwrap();
+        assert_eq!(
+            timing_json,
+            serde_json::json!({
+                "initial_minimum_balance": "10000000.000000000",
+                "cliff_time": "480",
+                "cliff_amount": "500000.000000000",
+                "vesting_period": "1",
+                "vesting_increment": "0.500000000"
+            })
+        );
+
+        timing.vesting_period = Slots::Duration("PT0S".to_string());
+        let error = default::LedgerGenerator::generate(
+            network_path,
+            &bp_keys_map,
+            Some(&timing),
+            BLOCK_WINDOW_DURATION_MS,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`vesting_period`: must be at least 1 slot"
+        );
+
+        timing.vesting_period = Slots::Slots(1);
+        timing.cliff_amount = "500000".to_string();
+        let error = default::LedgerGenerator::generate(
+            network_path,
+            &bp_keys_map,
+            Some(&timing),
+            BLOCK_WINDOW_DURATION_MS,
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("`cliff_amount`: "));
+    }
+
+    #[test]
+    fn test_write_protocol_constants() {
+        let tempdir = TempDir::new("test_write_protocol_constants")
+            .expect("Cannot create temporary directory");
+        let network_path = tempdir.path();
+        std::fs::write(
+            network_path.join(GENESIS_LEDGER_JSON),
+            r#"{"genesis": {"k": 20, "genesis_state_timestamp": "2024-01-01T00:00:00Z"},
+                "proof": {"level": "none"}, "ledger": {"accounts": []}}"#,
+        )
+        .unwrap();
+        let constants = ProtocolConstants {
+            k: Some(10),
+            slots_per_epoch: Some(48),
+            block_window_duration_ms: Some(20_000),
+            txpool_max_size: Some(3000),
+            transaction_capacity: Some(3),
+            fork: Some(ForkConfig {
+                state_hash: "3NKSiqFZQmAS12U8qeX4KNo8b4199spwNh7mrSs4Ci1Vacpfix2Q".to_string(),
+                blockchain_length: 100,
+                global_slot_since_genesis: 120,
+            }),
+            ..Default::default()
+        };
+
+        constants.write(network_path).unwrap();
+
+        let content = std::fs::read_to_string(network_path.join(GENESIS_LEDGER_JSON)).unwrap();
+        let runtime_config: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(
+            runtime_config,
+            serde_json::json!({
+                "genesis": {
+                    "k": 10,
+                    "slots_per_epoch": 48,
+                    "genesis_state_timestamp": "2024-01-01T00:00:00Z"
+                },
+                "proof": {
+                    "level": "none",
+                    "block_window_duration_ms": 20000,
+                    "transaction_capacity": { "2_to_the": 3 },
+                    "fork": {
+                        "state_hash": "3NKSiqFZQmAS12U8qeX4KNo8b4199spwNh7mrSs4Ci1Vacpfix2Q",
+                        "blockchain_length": 100,
+                        "global_slot_since_genesis": 120
+                    }
+                },
+                "daemon": { "txpool_max_size": 3000 },
+                "ledger": { "accounts": [] }
+            })
+        );
+
+        std::fs::write(
+            network_path.join(GENESIS_LEDGER_JSON),
+            r#"{"genesis": [], "ledger": {"accounts": []}}"#,
+        )
+        .unwrap();
+        let error = constants.write(network_path).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`genesis` of the runtime config is not an object"
+        );
+    }
+
     #[test]
     fn test_generate_replayer_input() {
         let tempdir = TempDir::new("test_generate_replayer_input")
@@ -211,16 +977,21 @@ mod tests {
             key_path: "test_key_path".to_string(),
         };
         bp_keys_map.insert("node0".to_string(), service_key);
-        let result = default::LedgerGenerator::generate(network_path, &bp_keys_map);
+        let result = default::LedgerGenerator::generate(
+            network_path,
+            &bp_keys_map,
+            None,
+            BLOCK_WINDOW_DURATION_MS,
+        );
         println!("{:?}", result);
         assert!(result.is_ok());
 
-        let result = default::LedgerGenerator::generate_replayer_input(network_path);
+        let result = default::LedgerGenerator::generate_replayer_input(network_path, "archive");
         println!("{:?}", result);
         assert!(result.is_ok());
 
         let path = network_path.to_path_buf();
-        let path = path.join(REPLAYER_INPUT_JSON);
+        let path = path.join("archive_replayer_input.json");
         assert!(path.exists());
         let content = std::fs::read_to_string(path).unwrap();
         assert!(content.contains("genesis_ledger"));
@@ -353,24 +1124,268 @@ mod tests {
             key_path: "test_key_path".to_string(),
         };
         bp_keys_map.insert("node0".to_string(), service_key);
-        let result = default::LedgerGenerator::generate(network_path, &bp_keys_map);
+        let result = default::LedgerGenerator::generate(
+            network_path,
+            &bp_keys_map,
+            None,
+            BLOCK_WINDOW_DURATION_MS,
+        );
         println!("{:?}", result);
         assert!(result.is_ok());
 
-        let result = default::LedgerGenerator::generate_replayer_input(network_path);
+        let result = default::LedgerGenerator::generate_replayer_input(network_path, "archive");
         println!("{:?}", result);
         assert!(result.is_ok());
 
-        let result = set_slot_since_genesis(network_path, 100);
+        let result = set_slot_since_genesis(network_path, "archive", 100);
         println!("{:?}", result);
         assert!(result.is_ok());
 
         let path = network_path.to_path_buf();
-        let path = path.join(REPLAYER_INPUT_JSON);
+        let path = path.join("archive_replayer_input.json");
         assert!(path.exists());
         let content = std::fs::read_to_string(path).unwrap();
         let replayer_input: ReplayerInput = serde_json::from_str(&content).unwrap();
 
         assert_eq!(replayer_input.start_slot_since_genesis, 100);
     }
+
+    fn spec_keys(spec: &spec::LedgerSpec) -> HashMap<String, NodeKey> {
+        spec.account_names()
+            .into_iter()
+            .map(|name| {
+                let key = NodeKey {
+                    key_string: format!("pk-{name}"),
+                    key_path: format!("network-keypairs/{name}"),
+                };
+                (name, key)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_generate_spec_ledger() {
+        let tempdir =
+            TempDir::new("test_generate_spec_ledger").expect("Cannot create temporary directory");
+        let network_path = tempdir.path();
+        let spec =
+            spec::LedgerSpec::from_file(Path::new("./tests/data/ledger_spec/ledger_spec.json"))
+                .unwrap();
+        assert_eq!(
+            spec.account_names(),
+            ["fish-0", "fish-1", "fish-2", "whale-0", "whale-1"]
+        );
+        let node_keys = HashMap::from([("mina-bp-1".to_string(), "pk-bp".to_string())]);
+
+        spec::LedgerGenerator::generate(
+            network_path,
+            &spec,
+            &node_keys,
+            &spec_keys(&spec),
+            BLOCK_WINDOW_DURATION_MS,
+        )
+        .unwrap();
+
+        let content = std::fs::read_to_string(network_path.join(GENESIS_LEDGER_JSON)).unwrap();
+        let ledger: GenesisLedger = serde_json::from_str(&content).unwrap();
+        let accounts = ledger.ledger.accounts;
+        let summary: Vec<(&str, &str, Option<&str>)> = accounts
+            .iter()
+            .map(|account| {
+                (
+                    account.pk.as_str(),
+                    account.balance.as_str(),
+                    account.delegate.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("pk-bp", "1000.000000000", None),
+                ("pk-fish-0", "0.250000000", Some("pk-whale-0")),
+                ("pk-fish-1", "0.250000000", Some("pk-whale-0")),
+                ("pk-fish-2", "0.250000000", Some("pk-whale-0")),
+                ("pk-whale-0", "1000000.000000000", None),
+                ("pk-whale-1", "1000000.000000000", None),
+            ]
+        );
+
+        let timing = accounts[1].timing.as_ref().unwrap();
+        assert_eq!(timing.initial_minimum_balance, "0.200000000");
+        assert_eq!(timing.cliff_time, "100");
+        assert_eq!(timing.vesting_period, "10");
+        assert_eq!(timing.vesting_increment, "0.010000000");
+        assert!(accounts[4].timing.is_none());
+    }
+
+    #[test]
+    fn test_spec_ledger_errors() {
+        let tempdir =
+            TempDir::new("test_spec_ledger_errors").expect("Cannot create temporary directory");
+        let path = tempdir.path().join("spec.json");
+        let generate = |tiers: &str| {
+            std::fs::write(&path, format!(r#"{{"tiers": {tiers}}}"#)).unwrap();
+            let spec = spec::LedgerSpec::from_file(&path)?;
+            let node_keys = HashMap::from([("mina-bp-1".to_string(), "pk-bp".to_string())]);
+            spec::LedgerGenerator::generate(
+                tempdir.path(),
+                &spec,
+                &node_keys,
+                &spec_keys(&spec),
+                BLOCK_WINDOW_DURATION_MS,
+            )
+        };
+
+        assert!(
+            generate(r#"{"fish": {"count": 1, "balance": "1mina", "delegate": "mina-bp-1"}}"#)
+                .is_ok()
+        );
+        let error = |tiers: &str| generate(tiers).unwrap_err().to_string();
+        assert_eq!(
+            error(r#"{"fish": {"count": 1, "balance": "1mina", "delegate": "fish-1"}}"#),
+            "tier 'fish': `delegate`: 'fish-1' is out of range, tier 'fish' has 1 accounts"
+        );
+        assert_eq!(
+            error(r#"{"fish": {"count": 1, "balance": "1mina", "delegate": "shark-0"}}"#),
+            "tier 'fish': `delegate`: 'shark-0' is neither a tier account nor a node"
+        );
+        assert!(error(r#"{"fish": {"count": 1, "balance": "1"}}"#)
+            .starts_with("tier 'fish': `balance`: Invalid amount"));
+        assert!(error(r#"{"big-fish": {"count": 1, "balance": "1mina"}}"#)
+            .starts_with("tier 'big-fish'"));
+        assert!(
+            error(r#"{"fish": {"count": 1, "balance": "1mina", "colour": "red"}}"#)
+                .contains("unknown field `colour`")
+        );
+    }
+
+    const EXPORTED_LEDGER: &str = "./tests/data/ledger_export/staking_ledger.json";
+    const WHALE: &str = "B62qmqMrgPshhHKLJ7DqWn1KeizEgga5MuGmWb2bXajUnyivfeMW6JE";
+    const POOL: &str = "B62qoXQhp63oNsLSN9Dy7wcF3PzLmdBnnin2rTnNWLbpgF7diABciU6";
+    const DOLPHIN: &str = "B62qkdFWJSW8zaTBZjTVtmeU3rVxyUkNxPhKKW8T2JBtpj5XfdywLSM";
+    const FISH: &str = "B62qrPN5Y5yq8kGE3FbVKbGTdTAJNdtNtB5sNVpxyRwWGcDEhpMzc8g";
+
+    fn import_ledger(
+        subset: import::Subset,
+        replace_keys: &[&str],
+        block_producers: usize,
+    ) -> std::io::Result<Vec<serde_json::Value>> {
+        let tempdir = TempDir::new("import_ledger").expect("Cannot create temporary directory");
+        let block_producer_keys: Vec<(String, String)> = (1..=block_producers)
+            .map(|i| (format!("mina-bp-{i}"), format!("pk-bp-{i}")))
+            .collect();
+        let replace_keys: Vec<String> = replace_keys.iter().map(|pk| pk.to_string()).collect();
+        import::LedgerImporter::generate(
+            tempdir.path(),
+            Path::new(EXPORTED_LEDGER),
+            subset,
+            &replace_keys,
+            &block_producer_keys,
+        )?;
+
+        let content = std::fs::read_to_string(tempdir.path().join(GENESIS_LEDGER_JSON))?;
+        let ledger: serde_json::Value = serde_json::from_str(&content)?;
+        assert!(ledger["genesis"]["genesis_state_timestamp"].is_string());
+        Ok(ledger["ledger"]["accounts"].as_array().unwrap().clone())
+    }
+
+    fn pks(accounts: &[serde_json::Value]) -> Vec<(&str, Option<&str>)> {
+        accounts
+            .iter()
+            .map(|account| {
+                (
+                    account["pk"].as_str().unwrap(),
+                    account["delegate"].as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_import_ledger() {
+        // the largest delegates are taken over, the other fields are kept
+        let accounts = import_ledger(import::Subset::All, &[], 2).unwrap();
+        assert_eq!(
+            pks(&accounts),
+            [
+                (FISH, Some("pk-bp-1")),
+                ("pk-bp-1", None),
+                (DOLPHIN, Some("pk-bp-2")),
+                ("pk-bp-2", None),
+                (
+                    "B62qpge4uMq4Vv5Rvc8Gw9qSquUYd6xoW1pz7HQkMSHm6h1o7pvLPAN",
+                    Some("pk-bp-2")
+                ),
+            ]
+        );
+        assert_eq!(accounts[0]["nonce"], "3");
+        assert_eq!(accounts[1]["balance"], "66000.5");
+        assert_eq!(accounts[1]["timing"]["cliff_time"], "86400");
+
+        // delegations out of the subset are dropped
+        let accounts = import_ledger(import::Subset::Top(2), &[], 2).unwrap();
+        assert_eq!(pks(&accounts), [("pk-bp-1", None), ("pk-bp-2", None)]);
+        assert_eq!(accounts[1]["balance"], "30000");
+
+        let accounts = import_ledger(import::Subset::Sample(3), &[], 1).unwrap();
+        assert_eq!(
+            pks(&accounts),
+            [
+                ("pk-bp-1", None),
+                (DOLPHIN, None),
+                (
+                    "B62qpge4uMq4Vv5Rvc8Gw9qSquUYd6xoW1pz7HQkMSHm6h1o7pvLPAN",
+                    None
+                ),
+            ]
+        );
+
+        // chosen accounts are kept and taken over
+        let accounts = import_ledger(import::Subset::Top(1), &[FISH], 2).unwrap();
+        assert_eq!(pks(&accounts), [("pk-bp-1", None), (WHALE, None)]);
+    }
+
+    #[test]
+    fn test_import_ledger_errors() {
+        let error = |replace_keys: &[&str]| {
+            import_ledger(import::Subset::All, replace_keys, 1)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(&["B62qnotanaccount"]),
+            "'B62qnotanaccount' is not an account of the exported ledger"
+        );
+        assert_eq!(
+            error(&[WHALE, POOL]),
+            "Cannot replace the keys of 2 accounts with 1 block producers"
+        );
+
+        let tempdir =
+            TempDir::new("test_import_ledger_errors").expect("Cannot create temporary directory");
+        let ledger_path = tempdir.path().join("exported.json");
+        let import = |content: &str| {
+            std::fs::write(&ledger_path, content).unwrap();
+            import::LedgerImporter::generate(
+                tempdir.path(),
+                &ledger_path,
+                import::Subset::All,
+                &[],
+                &[],
+            )
+            .unwrap_err()
+            .to_string()
+        };
+        assert!(import(r#"[{"pk": "B62q", "balance": "-1"}]"#)
+            .starts_with("Account 'B62q': Invalid amount"));
+        assert_eq!(
+            import(r#"[{"pk": "B62q"}]"#),
+            "Account 0 has no `pk` or `balance`"
+        );
+        assert_eq!(
+            import(r#"{"genesis": {}}"#),
+            "No `ledger.accounts` in runtime config"
+        );
+    }
 }
@@ -0,0 +1,450 @@
+//! # Git Build Module
+//!
+//! Builds the daemon and archive docker images (docker mode) or binaries (native mode) of
+//! services whose topology entry has a `git_build` commit or tag, from a local mina checkout.
+//!
+//! Refs are resolved to commits, which name the builds: images are tagged
+//! `minimina/mina-daemon:<short commit>` and `minimina/mina-archive:<short commit>`, binaries are in
+//! `~/.minimina/.builds/<commit>/bin`. A build already there is reused, so services of the
+//! same commit, and later networks, share it.
+
+use crate::{
+    backend::ensure_success,
+    cli::ExecutionMode,
+    service::{ServiceConfig, ServiceType},
+    topology::GitBuild,
+    utils::run_command,
+};
+use log::{info, warn};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Result},
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Environment variable with the path of the mina checkout, when `--mina-repo` is not given
+pub const MINA_REPO_ENV: &str = "MINIMINA_MINA_REPO";
+
+const DAEMON_IMAGE: &str = "minimina/mina-daemon";
+const ARCHIVE_IMAGE: &str = "minimina/mina-archive";
+/// Length of the commit prefix in image tags
+const TAG_COMMIT_LEN: usize = 12;
+
+/// Makefile targets of the checkout's dune build, which the debian packages are made from
+const MAKE_TARGETS: [&str; 3] = ["build", "build-daemon-utils", "build-archive-utils"];
+/// Packages installed by the daemon and archive Dockerfiles, as `scripts/debian/build.sh` names them
+const DEB_PACKAGES: [&str; 7] = [
+    "daemon_generic",
+    "logproc",
+    "daemon_storage_toolbox",
+    "prefork_devnet_genesis_ledger",
+    "archive_devnet",
+    "archive_generic",
+    "daemon_devnet_config",
+];
+/// Line of `scripts/docker/build.sh`'s output naming the image it built
+const BUILT_IMAGE_PREFIX: &str = "Full image name: ";
+
+/// Dune targets of the native build, with the names they are installed under
+const NATIVE_TARGETS: [(&str, &str); 3] = [
+    ("src/app/cli/src/mina.exe", "mina"),
+    ("src/app/archive/archive.exe", "mina-archive"),
+    ("src/app/replayer/replayer.exe", "mina-replayer"),
+];
+
+impl GitBuild {
+    /// Git revision of the ref, tags are looked up as tags only
+    fn revision(&self) -> String {
+        match self {
+            GitBuild::Commit(commit) => format!("{commit}^{{commit}}"),
+            GitBuild::Tag(tag) => format!("refs/tags/{tag}^{{commit}}"),
+        }
+    }
+}
+
+/// Docker images of a commit, `(daemon, archive)`
+pub fn images(commit: &str) -> (String, String) {
+    let tag = &commit[..commit.len().min(TAG_COMMIT_LEN)];
+    (
+        format!("{DAEMON_IMAGE}:{tag}"),
+        format!("{ARCHIVE_IMAGE}:{tag}"),
+    )
+}
+
+/// Builds the services' refs from the mina checkout `repo` into `builds_dir`
+pub struct Builder {
+    pub repo: PathBuf,
+    pub builds_dir: PathBuf,
+}
+
+impl Builder {
+    /// Resolves `build` to the full hash of its commit in the checkout
+    pub fn resolve(&self, build: &GitBuild) -> Result<String> {
+        let output = run_command(
+            "git",
+            &[
+                "-C",
+                path_str(&self.repo)?,
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &build.revision(),
+            ],
+        )?;
+        if !output.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "{build:?} not found in mina checkout {}",
+                    self.repo.display()
+                ),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Builds the refs of `services` for `mode` and points the services at the builds.
+    /// Services without `git_build` are left as they are.
+    pub fn apply(&self, mode: &ExecutionMode, services: &mut [ServiceConfig]) -> Result<()> {
+        let mut commits = BTreeMap::new();
+        for build in services.iter().filter_map(|s| s.git_build.as_ref()) {
+            if !commits.contains_key(build) {
+                commits.insert(build.clone(), self.resolve(build)?);
+            }
+        }
+
+        let mut built = BTreeMap::new();
+        for service in services.iter_mut() {
+            let Some(commit) = service.git_build.as_ref().map(|build| &commits[build]) else {
+                continue;
+            };
+            if !built.contains_key(commit) {
+                built.insert(commit.clone(), self.build(mode, commit)?);
+            }
+            match (mode, &built[commit]) {
+                (ExecutionMode::Docker, _) => {
+                    if service.docker_image.is_some() {
+                        warn!(
+                            "Service '{}' has both a docker image and a git build, using the git build.",
+                            service.service_name
+                        );
+                    }
+                    let (daemon, archive) = images(commit);
+                    service.docker_image = Some(daemon);
+                    if service.service_type == ServiceType::ArchiveNode {
+                        service.archive_docker_image = Some(archive);
+                    }
+                }
+                (ExecutionMode::Native, bin) => service.bin_path = bin.clone(),
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds `commit` unless it was built before.
+    /// Returns the directory of the binaries in native mode.
+    fn build(&self, mode: &ExecutionMode, commit: &str) -> Result<Option<PathBuf>> {
+        match mode {
+            ExecutionMode::Docker => {
+                let (daemon, archive) = images(commit);
+                let mut missing = vec![];
+                for (image, service) in [(daemon, "mina-daemon"), (archive, "mina-archive")] {
+                    if image_exists(&image)? {
+                        info!("Using cached image '{image}'.");
+                    } else {
+                        missing.push((image, service));
+                    }
+                }
+                if !missing.is_empty() {
+                    self.build_images(commit, &missing)?;
+                }
+                Ok(None)
+            }
+            ExecutionMode::Native => {
+                let bin = self.builds_dir.join(commit).join("bin");
+                if bin.join("mina").exists() {
+                    info!("Using cached binaries in '{}'.", bin.display());
+                    return Ok(Some(bin));
+                }
+                let source = self.checkout(commit)?;
+                info!("Building the binaries of commit {commit}.");
+                let mut args = vec!["build", "--root", path_str(&source)?, "--profile=devnet"];
+                args.extend(NATIVE_TARGETS.iter().map(|(target, _)| *target));
+                ensure_success(run_command("dune", &args))?;
+
+                // installed last, so that an interrupted build isn't taken for a cached one
+                let staging = self.builds_dir.join(commit).join("bin.tmp");
+                fs::create_dir_all(&staging)?;
+                for (target, name) in NATIVE_TARGETS {
+                    fs::copy(
+                        source.join("_build/default").join(target),
+                        staging.join(name),
+                    )?;
+                }
+                fs::rename(&staging, &bin)?;
+                Ok(Some(bin))
+            }
+        }
+    }
+
+    /// Builds the debian packages of `commit` with the checkout's own scripts, and the
+    /// `(image, service)` images from them. Each image is built by
+    /// `scripts/docker/build.sh` from the checkout, then tagged for the commit.
+    fn build_images(&self, commit: &str, images: &[(String, &str)]) -> Result<()> {
+        let source = self.checkout(commit)?;
+        info!("Building the debian packages of commit {commit}.");
+        let mut make_args = MAKE_TARGETS.to_vec();
+        make_args.push("DUNE_PROFILE=devnet");
+        run_in(&source, Path::new("make"), &make_args)?;
+        run_in(
+            &source,
+            &source.join("scripts/debian/build.sh"),
+            &DEB_PACKAGES,
+        )?;
+        let deb_version = deb_version(&source)?;
+
+        // the Dockerfiles install the packages found in their build context
+        for entry in fs::read_dir(source.join("_build"))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "deb") {
+                let file_name = path.file_name().expect("Packages have a file name");
+                fs::copy(&path, source.join("dockerfiles").join(file_name))?;
+            }
+        }
+
+        for (image, service) in images {
+            info!("Building image '{image}' with packages {deb_version}.");
+            let mut args = vec![
+                "--service",
+                service,
+                "--version",
+                &deb_version,
+                "--deb-version",
+                &deb_version,
+                "--deb-legacy-version",
+                &deb_version,
+                "--network",
+                "devnet",
+                "--load-only",
+            ];
+            if *service == "mina-daemon" {
+                args.extend(["--deb-suffix", "generic"]);
+            }
+            let output = run_in(&source, &source.join("scripts/docker/build.sh"), &args)?;
+            let built = built_image(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+                io::Error::other(format!(
+                    "scripts/docker/build.sh of commit {commit} did not name the image of '{service}'"
+                ))
+            })?;
+            ensure_success(run_command("docker", &["tag", &built, image]))?;
+        }
+        Ok(())
+    }
+
+    /// Checks `commit` out, with its submodules, in a worktree of the builds directory
+    fn checkout(&self, commit: &str) -> Result<PathBuf> {
+        let source = self.builds_dir.join(commit).join("src");
+        if !source.exists() {
+            fs::create_dir_all(self.builds_dir.join(commit))?;
+            ensure_success(run_command(
+                "git",
+                &[
+                    "-C",
+                    path_str(&self.repo)?,
+                    "worktree",
+                    "add",
+                    "--detach",
+                    path_str(&source)?,
+                    commit,
+                ],
+            ))?;
+            ensure_success(run_command(
+                "git",
+                &[
+                    "-C",
+                    path_str(&source)?,
+                    "submodule",
+                    "update",
+                    "--init",
+                    "--recursive",
+                ],
+            ))?;
+        }
+        Ok(source)
+    }
+}
+
+/// Runs `program` from the root of the checkout `source`, failing with its stderr
+fn run_in(source: &Path, program: &Path, args: &[&str]) -> Result<Output> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(source)
+        .env("BUILD_DIR", source.join("_build"))
+        .env("DUNE_PROFILE", "devnet")
+        .env("KEEP_MY_TAGS_INTACT", "true")
+        .output();
+    ensure_success(output)
+}
+
+/// Version of the debian packages built from the checkout `source`
+fn deb_version(source: &Path) -> Result<String> {
+    let output = run_in(
+        source,
+        Path::new("bash"),
+        &[
+            "-c",
+            "source scripts/export-git-env-vars.sh >&2 && printf %s \"$MINA_DEB_VERSION\"",
+        ],
+    )?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if version.is_empty() {
+        return Err(io::Error::other(format!(
+            "No debian package version in '{}'",
+            source.display()
+        )));
+    }
+    Ok(version)
+}
+
+/// Image named in the output of `scripts/docker/build.sh`
+fn built_image(output: &str) -> Option<String> {
+    output
+        .lines()
+        .rev()
+        .find_map(|line| line.split_once(BUILT_IMAGE_PREFIX))
+        .map(|(_, image)| image.trim().to_string())
+        .filter(|image| !image.is_empty())
+}
+
+fn image_exists(image: &str) -> Result<bool> {
+    Ok(run_command("docker", &["image", "inspect", image])?
+        .status
+        .success())
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Path {} is not valid UTF-8", path.display()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn git(repo: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .expect("Failed to run git");
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    /// A checkout with two commits, the first tagged `1.0.0`
+    fn repo(dir: &Path) -> (String, String) {
+        git(dir, &["init", "-q"]);
+        git(dir, &["commit", "-q", "--allow-empty", "-m", "first"]);
+        git(dir, &["tag", "1.0.0"]);
+        let first = git(dir, &["rev-parse", "HEAD"]);
+        git(dir, &["commit", "-q", "--allow-empty", "-m", "second"]);
+        (first, git(dir, &["rev-parse", "HEAD"]))
+    }
+
+    #[test]
+    fn test_resolve() {
+        let tempdir = TempDir::new("test_git_build").expect("Cannot create temporary directory");
+        let (first, second) = repo(tempdir.path());
+        let builder = Builder {
+            repo: tempdir.path().to_path_buf(),
+            builds_dir: tempdir.path().join("builds"),
+        };
+
+        let resolve = |build| builder.resolve(&build);
+        assert_eq!(resolve(GitBuild::Tag("1.0.0".into())).unwrap(), first);
+        assert_eq!(
+            resolve(GitBuild::Commit(second[..8].into())).unwrap(),
+            second
+        );
+        assert_eq!(resolve(GitBuild::Commit("HEAD~1".into())).unwrap(), first);
+        for build in [
+            GitBuild::Tag("2.0.0".into()),
+            GitBuild::Tag(second.clone()),
+            GitBuild::Commit("deadbeef".into()),
+        ] {
+            let err = resolve(build).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        }
+    }
+
+    #[test]
+    fn test_images() {
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        assert_eq!(
+            images(commit),
+            (
+                "minimina/mina-daemon:0123456789ab".to_string(),
+                "minimina/mina-archive:0123456789ab".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_built_image() {
+        let output = "Staging 7 .deb file(s)\n\
+            ✅ Docker image for service mina-daemon built successfully.\n\
+            🐳 Full image name: mina-daemon:3.1.0-abcdef12-generic\n";
+        assert_eq!(
+            built_image(output).as_deref(),
+            Some("mina-daemon:3.1.0-abcdef12-generic")
+        );
+        assert_eq!(built_image("Skipping push to remote registry"), None);
+        assert_eq!(built_image("🐳 Full image name: "), None);
+    }
+
+    #[test]
+    fn test_apply_cached_native_build() {
+        let tempdir = TempDir::new("test_git_build").expect("Cannot create temporary directory");
+        let (first, _) = repo(tempdir.path());
+        let builder = Builder {
+            repo: tempdir.path().to_path_buf(),
+            builds_dir: tempdir.path().join("builds"),
+        };
+        let bin = builder.builds_dir.join(&first).join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join("mina"), "").unwrap();
+
+        let mut services = vec![
+            ServiceConfig {
+                service_name: "mina-bp-1".into(),
+                service_type: ServiceType::BlockProducer,
+                git_build: Some(GitBuild::Tag("1.0.0".into())),
+                ..Default::default()
+            },
+            ServiceConfig {
+                service_name: "mina-bp-2".into(),
+                service_type: ServiceType::BlockProducer,
+                ..Default::default()
+            },
+        ];
+        builder
+            .apply(&ExecutionMode::Native, &mut services)
+            .unwrap();
+        assert_eq!(services[0].bin_path, Some(bin));
+        assert_eq!(services[1].bin_path, None);
+
+        services[1].git_build = Some(GitBuild::Tag("2.0.0".into()));
+        assert!(builder
+            .apply(&ExecutionMode::Native, &mut services)
+            .is_err());
+    }
+}
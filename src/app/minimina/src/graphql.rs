@@ -1,8 +1,31 @@
 use log::info;
-
-use crate::{directory_manager::DirectoryManager, exit_with, output::network, TIMEOUT_IN_SECS};
-use std::{self, io::Result};
-
+use minimina::graphql_client::Client;
+
+use crate::{directory_manager::DirectoryManager, output::network, TIMEOUT_IN_SECS};
+use std::{
+    self,
+    collections::BTreeMap,
+    io::{self, Result},
+};
+
+/// Ids of the structured log events recorded with `--graphql-filtered-logs`
+const FILTERED_LOG_EVENTS: [&str; 13] = [
+    "21ccae8c619bc2666474085272d5fe1d",
+    "ef1182dc30f3e0aa9f6bf11c0ab90ba6",
+    "64e2d3e86c37c09b15efdaf7470ce879",
+    "db06cb5030f39e86e84b30d033f3bc5c",
+    "60076de624bf0c5fc0843b875001cf84",
+    "27953f46376ba8abc0c61400e2c38f8b",
+    "b4b5f5b1d1a0c457cbd13a35d1c8b57b",
+    "0fc65f5594c5e9ee0b6f0ddde747c758",
+    "b5a89d6d616a35fb6f73d1eaad6b2dbd",
+    "1c4150aa7058a3058c4d20ae90ff7ec3",
+    "f7254e63ad51092a0bd3078580ef9ce3",
+    "74a81f1e2f8d548e4550faa136c68160",
+    "30fe76cee159ea215fc05549e861501e",
+];
+
+/// Graphql endpoints of the nodes of networks, see [`Client`] to query them
 pub struct GraphQl {
     directory_manager: DirectoryManager,
 }
@@ -25,6 +48,53 @@ impl GraphQl {
         }
     }
 
+    /// Graphql endpoints of the nodes of a network, by node id
+    pub fn endpoints(&self, network_id: &str) -> Result<BTreeMap<String, String>> {
+        let info = self.directory_manager.get_network_info(network_id)?;
+        let info = serde_json::from_str::<network::Create>(&info)?;
+        Ok(info
+            .nodes
+            .into_iter()
+            .filter_map(|(node_id, node)| Some((node_id, node.graphql_uri?)))
+            .collect())
+    }
+
+    /// Graphql endpoints of `nodes`, or of all nodes which have one when empty
+    pub fn node_endpoints(
+        &self,
+        network_id: &str,
+        nodes: &[String],
+    ) -> Result<BTreeMap<String, String>> {
+        let mut endpoints = self.endpoints(network_id)?;
+        if let Some(node) = nodes.iter().find(|node| !endpoints.contains_key(*node)) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Node '{node}' has no graphql endpoint in network '{network_id}'"),
+            ));
+        }
+        if !nodes.is_empty() {
+            endpoints.retain(|node, _| nodes.contains(node));
+        }
+        if endpoints.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No node has a graphql endpoint in network '{network_id}'"),
+            ));
+        }
+        Ok(endpoints)
+    }
+
+    /// Client of the graphql endpoint of `node_id`
+    pub fn client(&self, node_id: &str, network_id: &str) -> Result<Client> {
+        match self.get_endpoint(node_id, network_id) {
+            Some(endpoint) => Ok(Client::new(&endpoint)),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Node '{node_id}' has no graphql endpoint in network '{network_id}'"),
+            )),
+        }
+    }
+
     /// Waits for graphql server to start
     pub fn wait_for_server(&self, gql_ep: &str) -> Result<()> {
         let mut retries = 0;
@@ -46,8 +116,9 @@ impl GraphQl {
             }
         }
         if !graphql_running {
-            return exit_with(format!(
-                "Failed to start graphql '{gql_ep}' within {TIMEOUT_IN_SECS}s",
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("Failed to start graphql '{gql_ep}' within {TIMEOUT_IN_SECS}s"),
             ));
         }
         Ok(())
@@ -55,46 +126,9 @@ impl GraphQl {
 
     /// Requests filtered logs via graphql
     pub fn request_filtered_logs(&self, gql_ep: &str) -> Result<()> {
-        // Filtered logs request payload
-        let query = r#"{
-        "query": "mutation MyMutation 
-                    { startFilteredLog(filter: [\"21ccae8c619bc2666474085272d5fe1d\", 
-                                                \"ef1182dc30f3e0aa9f6bf11c0ab90ba6\",
-                                                \"64e2d3e86c37c09b15efdaf7470ce879\",
-                                                \"db06cb5030f39e86e84b30d033f3bc5c\", 
-                                                \"60076de624bf0c5fc0843b875001cf84\", 
-                                                \"27953f46376ba8abc0c61400e2c38f8b\", 
-                                                \"b4b5f5b1d1a0c457cbd13a35d1c8b57b\", 
-                                                \"0fc65f5594c5e9ee0b6f0ddde747c758\", 
-                                                \"b5a89d6d616a35fb6f73d1eaad6b2dbd\", 
-                                                \"1c4150aa7058a3058c4d20ae90ff7ec3\", 
-                                                \"f7254e63ad51092a0bd3078580ef9ce3\", 
-                                                \"74a81f1e2f8d548e4550faa136c68160\", 
-                                                \"30fe76cee159ea215fc05549e861501e\"]) }"
-    }"#;
-
-        let client = reqwest::blocking::Client::new();
         info!("Sending request to: {gql_ep}");
-        let response = client
-            .post(gql_ep)
-            .header("Content-Type", "application/json")
-            .body(query)
-            .send();
-        if let Err(e) = response {
-            return exit_with(format!(
-                "Failed to send request to graphql endpoint '{gql_ep}': {e}",
-            ));
-        }
-
-        // Read the response body
-        let response_body = response.unwrap().text();
-        if let Err(e) = response_body {
-            return exit_with(format!(
-                "Failed to read response body from graphql endpoint '{gql_ep}': {e}",
-            ));
-        }
-        info!("Response body: {}", response_body.unwrap());
-
+        let started = Client::new(gql_ep).start_filtered_log(&FILTERED_LOG_EVENTS)?;
+        info!("Filtered logs started: {started}");
         Ok(())
     }
 }
@@ -166,5 +200,26 @@ mod test {
 
         let endpoint = graphql.get_endpoint("mina-snark-worker-1", network_id);
         assert_eq!(endpoint, None);
+
+        let endpoints = graphql.endpoints(network_id).unwrap();
+        assert_eq!(
+            endpoints.keys().collect::<Vec<_>>(),
+            [
+                "mina-bp-1",
+                "mina-bp-2",
+                "mina-seed-1",
+                "mina-snark-coordinator"
+            ]
+        );
+        assert!(graphql.endpoints("missing").is_err());
+
+        let nodes = ["mina-bp-2".to_string(), "mina-seed-1".to_string()];
+        let endpoints = graphql.node_endpoints(network_id, &nodes).unwrap();
+        assert_eq!(
+            endpoints.keys().collect::<Vec<_>>(),
+            ["mina-bp-2", "mina-seed-1"]
+        );
+        let nodes = ["mina-archive".to_string()];
+        assert!(graphql.node_endpoints(network_id, &nodes).is_err());
     }
 }
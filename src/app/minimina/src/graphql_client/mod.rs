@@ -0,0 +1,425 @@
+//! # Graphql Client Module
+//!
+//! Typed client of the daemon's graphql API (see `graphql_schema.json`):
+//! daemon status, best chain, accounts, payments, delegations, zkApp commands,
+//! snark work and peers. Responses are read into the structs of [`types`],
+//! failed requests and errors reported by the daemon are returned as `io::Error`s.
+//!
+//! ```no_run
+//! use minimina::graphql_client::{types::Payment, Client};
+//!
+//! let client = Client::new("http://localhost:4001/graphql");
+//! let status = client.daemon_status()?;
+//! println!("{:?} at height {:?}", status.sync_status, status.blockchain_length);
+//! let sent = client.send_payment(&Payment {
+//!     from: "B62q...".to_string(),
+//!     to: "B62q...".to_string(),
+//!     amount: 1_500_000_000,
+//!     fee: 10_000_000,
+//!     ..Default::default()
+//! })?;
+//! println!("payment {}", sent.hash);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+pub mod types;
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{
+    io::{self, Result},
+    path::Path,
+};
+use types::*;
+
+const USER_COMMAND_FIELDS: &str = "id hash kind nonce from to amount fee memo";
+const ZKAPP_COMMAND_FIELDS: &str = "id hash failureReason { index failures }";
+
+/// Typed client of the graphql API of one daemon
+pub struct Client {
+    endpoint: String,
+    http: reqwest::blocking::Client,
+}
+
+impl Client {
+    pub fn new(endpoint: &str) -> Self {
+        Client {
+            endpoint: endpoint.to_string(),
+            http: reqwest::blocking::Client::new(),
+        }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Sends `query`, returning the `data` of the response
+    pub fn query(&self, query: &str) -> Result<Value> {
+        self.request(query, Value::Null)
+    }
+
+    /// Sends `query` with `variables`, returning the `data` of the response.
+    /// Errors reported by the daemon are returned as errors.
+    pub fn request(&self, query: &str, variables: Value) -> Result<Value> {
+        let body = json!({ "query": query, "variables": variables }).to_string();
+        let response = self
+            .http
+            .post(&self.endpoint)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .and_then(|response| response.text())
+            .map_err(|e| {
+                io::Error::other(format!(
+                    "Failed to send request to graphql endpoint '{}': {e}",
+                    self.endpoint
+                ))
+            })?;
+        let mut response: Value = serde_json::from_str(&response)?;
+
+        if let Some(errors) = response.get("errors").and_then(Value::as_array) {
+            let messages: Vec<&str> = errors
+                .iter()
+                .map(|error| error["message"].as_str().unwrap_or("unknown error"))
+                .collect();
+            return Err(io::Error::other(format!(
+                "Graphql endpoint '{}' returned errors: {}",
+                self.endpoint,
+                messages.join("; ")
+            )));
+        }
+        match response.get_mut("data") {
+            Some(data) if !data.is_null() => Ok(data.take()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Graphql endpoint '{}' returned no data", self.endpoint),
+            )),
+        }
+    }
+
+    /// Sends `query` with `variables` and reads `field` of the response's data
+    fn request_field<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: Value,
+        field: &str,
+    ) -> Result<T> {
+        let mut data = self.request(query, variables)?;
+        serde_json::from_value(data[field].take()).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Unexpected '{field}' from graphql endpoint '{}': {e}",
+                    self.endpoint
+                ),
+            )
+        })
+    }
+
+    pub fn sync_status(&self) -> Result<SyncStatus> {
+        self.request_field("{ syncStatus }", Value::Null, "syncStatus")
+    }
+
+    pub fn daemon_status(&self) -> Result<DaemonStatus> {
+        let query = "{ daemonStatus { syncStatus blockchainLength highestBlockLengthReceived \
+            globalSlotSinceGenesisBestTip uptimeSecs stateHash chainId commitId \
+            peers { host libp2pPort peerId } blockProductionKeys snarkWorker } }";
+        self.request_field(query, Value::Null, "daemonStatus")
+    }
+
+    /// The last `max_length` blocks of the best chain, oldest first.
+    /// Empty until the daemon has bootstrapped.
+    pub fn best_chain(&self, max_length: u32) -> Result<Vec<Block>> {
+        let query = "query($maxLength: Int) { bestChain(maxLength: $maxLength) { \
+            stateHash creator commandTransactionCount protocolState { previousStateHash \
+            consensusState { blockHeight epoch slot slotSinceGenesis \
+            stakingEpochData { seed } nextEpochData { seed } } } \
+            transactions { userCommands { hash } zkappCommands { hash } } } }";
+        let blocks: Option<Vec<Block>> =
+            self.request_field(query, json!({ "maxLength": max_length }), "bestChain")?;
+        Ok(blocks.unwrap_or_default())
+    }
+
+    /// The account of `public_key` in the best tip's ledger, `None` if it does not exist
+    pub fn account(&self, public_key: &str) -> Result<Option<Account>> {
+        let query = "query($publicKey: PublicKey!) { account(publicKey: $publicKey) { \
+            publicKey balance { total liquid locked } nonce inferredNonce delegate } }";
+        self.request_field(query, json!({ "publicKey": public_key }), "account")
+    }
+
+    pub fn peers(&self) -> Result<Vec<Peer>> {
+        self.request_field(
+            "{ getPeers { host libp2pPort peerId } }",
+            Value::Null,
+            "getPeers",
+        )
+    }
+
+    /// Imports the key pair at `path`, a path on the daemon's file system
+    pub fn import_account(&self, path: &Path, password: &str) -> Result<ImportedAccount> {
+        let query = "mutation($path: String!, $password: String!) { \
+            importAccount(path: $path, password: $password) { publicKey alreadyImported } }";
+        let variables = json!({ "path": path, "password": password });
+        self.request_field(query, variables, "importAccount")
+    }
+
+    pub fn unlock_account(&self, public_key: &str, password: &str) -> Result<()> {
+        let query = "mutation($input: UnlockInput!) { unlockAccount(input: $input) { publicKey } }";
+        let variables = json!({ "input": { "publicKey": public_key, "password": password } });
+        self.request(query, variables).map(|_| ())
+    }
+
+    pub fn send_payment(&self, payment: &Payment) -> Result<UserCommand> {
+        let query = format!(
+            "mutation($input: SendPaymentInput!) {{ sendPayment(input: $input) \
+            {{ payment {{ {USER_COMMAND_FIELDS} }} }} }}"
+        );
+        let mut sent: Value =
+            self.request_field(&query, json!({ "input": payment }), "sendPayment")?;
+        serde_json::from_value(sent["payment"].take()).map_err(io::Error::other)
+    }
+
+    pub fn send_delegation(&self, delegation: &Delegation) -> Result<UserCommand> {
+        let query = format!(
+            "mutation($input: SendDelegationInput!) {{ sendDelegation(input: $input) \
+            {{ delegation {{ {USER_COMMAND_FIELDS} }} }} }}"
+        );
+        let mut sent: Value =
+            self.request_field(&query, json!({ "input": delegation }), "sendDelegation")?;
+        serde_json::from_value(sent["delegation"].take()).map_err(io::Error::other)
+    }
+
+    /// Sends a signed zkApp command, given as the JSON of a `ZkappCommandInput`
+    pub fn send_zkapp(&self, zkapp_command: &Value) -> Result<ZkappCommand> {
+        let query = format!(
+            "mutation($input: SendZkappInput!) {{ sendZkapp(input: $input) \
+            {{ zkapp {{ {ZKAPP_COMMAND_FIELDS} }} }} }}"
+        );
+        let variables = json!({ "input": { "zkappCommand": zkapp_command } });
+        let mut sent: Value = self.request_field(&query, variables, "sendZkapp")?;
+        serde_json::from_value(sent["zkapp"].take()).map_err(io::Error::other)
+    }
+
+    /// Payments and delegations in the transaction pool, of `public_key` only if set
+    pub fn pooled_user_commands(&self, public_key: Option<&str>) -> Result<Vec<UserCommand>> {
+        let query = format!(
+            "query($publicKey: PublicKey) {{ pooledUserCommands(publicKey: $publicKey) \
+            {{ {USER_COMMAND_FIELDS} }} }}"
+        );
+        let variables = json!({ "publicKey": public_key });
+        self.request_field(&query, variables, "pooledUserCommands")
+    }
+
+    /// zkApp commands in the transaction pool, of `public_key` only if set
+    pub fn pooled_zkapp_commands(&self, public_key: Option<&str>) -> Result<Vec<ZkappCommand>> {
+        let query = format!(
+            "query($publicKey: PublicKey) {{ pooledZkappCommands(publicKey: $publicKey) \
+            {{ {ZKAPP_COMMAND_FIELDS} }} }}"
+        );
+        let variables = json!({ "publicKey": public_key });
+        self.request_field(&query, variables, "pooledZkappCommands")
+    }
+
+    /// Status of the payment or delegation with id `id`
+    pub fn transaction_status(&self, id: &str) -> Result<TransactionStatus> {
+        let query = "query($id: ID) { transactionStatus(payment: $id) }";
+        self.request_field(query, json!({ "id": id }), "transactionStatus")
+    }
+
+    /// Status of the zkApp command with id `id`
+    pub fn zkapp_transaction_status(&self, id: &str) -> Result<TransactionStatus> {
+        let query = "query($id: ID) { transactionStatus(zkappTransaction: $id) }";
+        self.request_field(query, json!({ "id": id }), "transactionStatus")
+    }
+
+    pub fn snark_pool(&self) -> Result<Vec<CompletedWork>> {
+        self.request_field(
+            "{ snarkPool { prover fee workIds } }",
+            Value::Null,
+            "snarkPool",
+        )
+    }
+
+    pub fn pending_snark_work(&self) -> Result<Vec<PendingSnarkWork>> {
+        self.request_field(
+            "{ pendingSnarkWork { workBundle { workId } } }",
+            Value::Null,
+            "pendingSnarkWork",
+        )
+    }
+
+    /// Key and fee of the daemon's snark worker, `None` if it has none
+    pub fn current_snark_worker(&self) -> Result<Option<SnarkWorker>> {
+        self.request_field(
+            "{ currentSnarkWorker { key fee } }",
+            Value::Null,
+            "currentSnarkWorker",
+        )
+    }
+
+    /// Starts recording the structured log events with ids in `filter`
+    pub fn start_filtered_log(&self, filter: &[&str]) -> Result<bool> {
+        let query = "mutation($filter: [String!]!) { startFilteredLog(filter: $filter) }";
+        self.request_field(query, json!({ "filter": filter }), "startFilteredLog")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    /// Answers one request per response with `responses` in order, on a local endpoint.
+    /// The bodies of the requests are sent to the returned receiver.
+    fn serve(responses: Vec<Value>) -> (String, mpsc::Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/graphql", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                sender.send(serde_json::from_slice(&body).unwrap()).unwrap();
+
+                let response = response.to_string();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                    response.len()
+                )
+                .unwrap();
+            }
+        });
+        (endpoint, receiver)
+    }
+
+    #[test]
+    fn test_queries() {
+        let (endpoint, requests) = serve(vec![
+            json!({ "data": { "syncStatus": "CATCHUP" } }),
+            json!({ "data": { "bestChain": [{
+                "stateHash": "3NKtip",
+                "creator": "B62qbp1",
+                "commandTransactionCount": 2,
+                "protocolState": {
+                    "previousStateHash": "3NKparent",
+                    "consensusState": {
+                        "blockHeight": "12", "epoch": "1", "slot": "5", "slotSinceGenesis": "35",
+                        "stakingEpochData": { "seed": "2vaStaking" },
+                        "nextEpochData": { "seed": "2vaNext" }
+                    }
+                },
+                "transactions": { "userCommands": [{ "hash": "5Jhash" }], "zkappCommands": [] }
+            }] } }),
+            json!({ "data": { "bestChain": null } }),
+            json!({ "data": { "account": {
+                "publicKey": "B62qbp1",
+                "balance": { "total": "1500000000", "liquid": "1000000000", "locked": null },
+                "nonce": "3",
+                "inferredNonce": "4",
+                "delegate": "B62qbp1"
+            } } }),
+            json!({ "data": { "account": null } }),
+        ]);
+        let client = Client::new(&endpoint);
+
+        assert_eq!(client.sync_status().unwrap(), SyncStatus::Catchup);
+        requests.recv().unwrap();
+
+        let blocks = client.best_chain(1).unwrap();
+        assert_eq!(blocks.len(), 1);
+        let consensus_state = &blocks[0].protocol_state.consensus_state;
+        assert_eq!(consensus_state.block_height, 12);
+        assert_eq!(consensus_state.slot_since_genesis, 35);
+        assert_eq!(consensus_state.staking_epoch_data.seed, "2vaStaking");
+        assert_eq!(blocks[0].transactions.user_commands[0].hash, "5Jhash");
+        assert_eq!(
+            requests.recv().unwrap()["variables"],
+            json!({ "maxLength": 1 })
+        );
+
+        assert!(client.best_chain(1).unwrap().is_empty());
+        requests.recv().unwrap();
+
+        let account = client.account("B62qbp1").unwrap().unwrap();
+        assert_eq!(account.balance.total, 1_500_000_000);
+        assert_eq!(account.balance.locked, None);
+        assert_eq!(account.inferred_nonce, Some(4));
+        assert_eq!(
+            requests.recv().unwrap()["variables"],
+            json!({ "publicKey": "B62qbp1" })
+        );
+
+        assert_eq!(client.account("B62qnobody").unwrap(), None);
+    }
+
+    #[test]
+    fn test_send_payment() {
+        let (endpoint, requests) = serve(vec![
+            json!({ "data": { "sendPayment": { "payment": {
+                "id": "Ckpid", "hash": "5Jhash", "kind": "PAYMENT", "nonce": 4,
+                "from": "B62qbp1", "to": "B62qbp2",
+                "amount": "1500000000", "fee": "10000000", "memo": ""
+            } } } }),
+            json!({ "data": null, "errors": [{ "message": "Couldn't send user command" }] }),
+        ]);
+        let client = Client::new(&endpoint);
+        let payment = Payment {
+            from: "B62qbp1".to_string(),
+            to: "B62qbp2".to_string(),
+            amount: 1_500_000_000,
+            fee: 10_000_000,
+            ..Default::default()
+        };
+
+        let sent = client.send_payment(&payment).unwrap();
+        assert_eq!(sent.hash, "5Jhash");
+        assert_eq!(sent.amount, 1_500_000_000);
+        assert_eq!(
+            requests.recv().unwrap()["variables"],
+            json!({ "input": {
+                "from": "B62qbp1", "to": "B62qbp2", "amount": "1500000000", "fee": "10000000"
+            } })
+        );
+
+        let error = client
+            .send_payment(&Payment {
+                nonce: Some(7),
+                ..payment
+            })
+            .unwrap_err();
+        assert!(error.to_string().contains("Couldn't send user command"));
+        assert_eq!(requests.recv().unwrap()["variables"]["input"]["nonce"], "7");
+    }
+
+    #[test]
+    fn test_unreachable_endpoint() {
+        let error = Client::new("http://127.0.0.1:1/graphql")
+            .peers()
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Failed to send request to graphql endpoint"));
+    }
+}
@@ -0,0 +1,268 @@
+//! Responses of the daemon's graphql API read by [`super::Client`].
+//!
+//! Only the fields minimina needs are declared, see `graphql_schema.json` for the others.
+//! Scalars like `Balance`, `Fee` or `Length` are serialized as strings by the daemon,
+//! they are read into numbers.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt::Display;
+
+/// Input of the `sendPayment` mutation, amounts in nanomina.
+/// The sender's account must be unlocked on the daemon.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Payment {
+    pub from: String,
+    pub to: String,
+    #[serde(serialize_with = "display")]
+    pub amount: u64,
+    #[serde(serialize_with = "display")]
+    pub fee: u64,
+    /// The daemon's inferred nonce of the sender if not set
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "optional_display"
+    )]
+    pub nonce: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// Input of the `sendDelegation` mutation, delegating the stake of `from` to `to`
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Delegation {
+    pub from: String,
+    pub to: String,
+    #[serde(serialize_with = "display")]
+    pub fee: u64,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "optional_display"
+    )]
+    pub nonce: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SyncStatus {
+    Connecting,
+    Listening,
+    Offline,
+    Bootstrap,
+    Synced,
+    Catchup,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonStatus {
+    pub sync_status: SyncStatus,
+    pub blockchain_length: Option<u64>,
+    pub highest_block_length_received: u64,
+    pub global_slot_since_genesis_best_tip: Option<u64>,
+    pub uptime_secs: u64,
+    pub state_hash: Option<String>,
+    pub chain_id: String,
+    pub commit_id: String,
+    pub peers: Vec<Peer>,
+    pub block_production_keys: Vec<String>,
+    pub snark_worker: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Peer {
+    pub host: String,
+    pub libp2p_port: u16,
+    pub peer_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Block {
+    pub state_hash: String,
+    pub creator: String,
+    pub command_transaction_count: u64,
+    pub protocol_state: ProtocolState,
+    pub transactions: Transactions,
+}
+
+/// Commands included in a block
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Transactions {
+    pub user_commands: Vec<IncludedCommand>,
+    pub zkapp_commands: Vec<IncludedCommand>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct IncludedCommand {
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtocolState {
+    pub previous_state_hash: String,
+    pub consensus_state: ConsensusState,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsensusState {
+    #[serde(deserialize_with = "number")]
+    pub block_height: u64,
+    #[serde(deserialize_with = "number")]
+    pub epoch: u64,
+    /// Slot in the epoch
+    #[serde(deserialize_with = "number")]
+    pub slot: u64,
+    #[serde(deserialize_with = "number")]
+    pub slot_since_genesis: u64,
+    pub staking_epoch_data: EpochData,
+    pub next_epoch_data: EpochData,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct EpochData {
+    pub seed: String,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub public_key: String,
+    pub balance: Balance,
+    /// Nonce of the account in the best tip's ledger
+    #[serde(deserialize_with = "optional_number")]
+    pub nonce: Option<u64>,
+    /// Nonce including the transactions in the pool
+    #[serde(deserialize_with = "optional_number")]
+    pub inferred_nonce: Option<u64>,
+    pub delegate: Option<String>,
+}
+
+/// Balance in nanomina
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Balance {
+    #[serde(deserialize_with = "number")]
+    pub total: u64,
+    #[serde(deserialize_with = "optional_number")]
+    pub liquid: Option<u64>,
+    #[serde(deserialize_with = "optional_number")]
+    pub locked: Option<u64>,
+}
+
+/// Payment or delegation
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UserCommand {
+    pub id: String,
+    pub hash: String,
+    pub kind: String,
+    pub nonce: u64,
+    pub from: String,
+    pub to: String,
+    #[serde(deserialize_with = "number")]
+    pub amount: u64,
+    #[serde(deserialize_with = "number")]
+    pub fee: u64,
+    pub memo: String,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ZkappCommand {
+    pub id: String,
+    pub hash: String,
+    pub failure_reason: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TransactionStatus {
+    Included,
+    Pending,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedAccount {
+    pub public_key: String,
+    pub already_imported: bool,
+}
+
+/// Snark work in the snark pool
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletedWork {
+    pub prover: String,
+    #[serde(deserialize_with = "number")]
+    pub fee: u64,
+    pub work_ids: Vec<u64>,
+}
+
+/// Snark work waiting for a prover, in bundles of one or two jobs
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingSnarkWork {
+    pub work_bundle: Vec<WorkDescription>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkDescription {
+    pub work_id: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct SnarkWorker {
+    pub key: String,
+    #[serde(deserialize_with = "number")]
+    pub fee: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Number {
+    Number(u64),
+    String(String),
+}
+
+impl Number {
+    fn into_u64<E: serde::de::Error>(self) -> Result<u64, E> {
+        match self {
+            Number::Number(n) => Ok(n),
+            Number::String(s) => s.parse().map_err(E::custom),
+        }
+    }
+}
+
+fn number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    Number::deserialize(deserializer)?.into_u64()
+}
+
+fn optional_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    Option::<Number>::deserialize(deserializer)?
+        .map(Number::into_u64)
+        .transpose()
+}
+
+fn display<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+fn optional_display<T: Display, S: Serializer>(
+    value: &Option<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.collect_str(value),
+        None => serializer.serialize_none(),
+    }
+}
@@ -23,6 +23,9 @@ use log::{debug, info};
 
 use crate::utils::{get_current_user_uid_gid, run_command};
 
+/// Password of the generated private keys
+pub const MINA_PRIVKEY_PASS: &str = "naughty blue worm";
+
 #[derive(Debug)]
 pub struct NodeKey {
     pub key_string: String,
@@ -5,3 +5,4 @@
 //! library lets their doc examples run as doctests under `cargo test`.
 
 pub mod amounts;
+pub mod graphql_client;
@@ -0,0 +1,581 @@
+//! # Load Module
+//!
+//! Generates sustained transaction traffic on a network.
+//!
+//! Payments are sent at a target rate, round-robin over a pool of senders (accounts with a key
+//! pair in `network-keypairs`, see [`DirectoryManager::network_keypair`]), and each sender pays
+//! the next one of the pool. Senders are spread round-robin over the graphql endpoints of the
+//! network's nodes, each sender always sends through the same node so that its transactions
+//! reach the pool in nonce order. Nonces are tracked per sender and read again from the
+//! ledger after a rejection.
+//!
+//! zkApp commands cannot be signed by minimina, a share of the traffic can be taken from
+//! commands signed beforehand (e.g. with `zkapp_test_transaction`), each sent once.
+//!
+//! Inclusion is tracked by polling the recent blocks of the best chain.
+
+use crate::{
+    backend::Backend,
+    directory_manager::{DirectoryManager, NETWORK_KEYPAIRS},
+    graphql::GraphQl,
+    keys::MINA_PRIVKEY_PASS,
+    output::load::{Percentiles, Report},
+};
+use log::{info, warn};
+use minimina::graphql_client::{types::Payment, Client};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Result},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Interval between two polls of the best chain, and two summaries
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of blocks of the best chain searched for included transactions
+const POLL_DEPTH: u32 = 10;
+
+pub struct Config {
+    /// Transactions per second
+    pub tps: f64,
+    pub duration: Duration,
+    /// Sending accounts, all accounts with a key pair when empty
+    pub senders: Vec<String>,
+    /// Nodes to send through, all nodes with a graphql endpoint when empty
+    pub nodes: Vec<String>,
+    /// Amount of each payment, in nanomina
+    pub amount: u64,
+    /// Fee of each payment, in nanomina
+    pub fee: u64,
+    /// Share of the transactions taken from `zkapp_commands`
+    pub zkapp_ratio: f64,
+    /// Signed zkApp commands, as `ZkappCommandInput` JSON
+    pub zkapp_commands: Vec<Value>,
+    /// How long to wait for the inclusion of the transactions after the last one was sent
+    pub inclusion_timeout: Duration,
+}
+
+impl Config {
+    /// Number of transactions to send
+    fn total(&self) -> u64 {
+        (self.tps * self.duration.as_secs_f64()).round() as u64
+    }
+
+    /// Whether transaction `index` is a zkApp command, spreading them evenly over the run
+    fn is_zkapp(&self, index: u64) -> bool {
+        let zkapps_until = |index: u64| (index as f64 * self.zkapp_ratio).floor();
+        zkapps_until(index + 1) > zkapps_until(index)
+    }
+}
+
+struct Sender {
+    name: String,
+    public_key: String,
+    /// Index of the node the sender sends through
+    node: usize,
+    /// Nonce of the next transaction, read from the ledger when unknown
+    nonce: Option<u64>,
+}
+
+/// Counts of a run, updated as transactions are sent and included
+#[derive(Default)]
+struct Tally {
+    submitted: usize,
+    payments: usize,
+    zkapp_commands: usize,
+    submit_latencies: Vec<f64>,
+    inclusion_latencies: Vec<f64>,
+    /// Accepted transactions not included yet, by hash
+    pending: HashMap<String, Instant>,
+    errors: BTreeMap<String, usize>,
+}
+
+impl Tally {
+    fn accepted(&self) -> usize {
+        self.payments + self.zkapp_commands
+    }
+
+    fn included(&self) -> usize {
+        self.inclusion_latencies.len()
+    }
+
+    /// Records the transactions of the recent blocks of the best chain as included
+    fn poll_inclusion(&mut self, client: &Client) {
+        let blocks = match client.best_chain(POLL_DEPTH) {
+            Ok(blocks) => blocks,
+            Err(e) => {
+                warn!("Failed to poll the best chain: {e}");
+                return;
+            }
+        };
+        let hashes = blocks.iter().flat_map(|block| {
+            let transactions = &block.transactions;
+            transactions
+                .user_commands
+                .iter()
+                .chain(&transactions.zkapp_commands)
+        });
+        for command in hashes {
+            if let Some(sent_at) = self.pending.remove(&command.hash) {
+                self.inclusion_latencies
+                    .push(sent_at.elapsed().as_secs_f64());
+            }
+        }
+    }
+
+    fn summary(&self, total: u64, start: Instant) -> String {
+        format!(
+            "submitted {}/{total}, accepted {}, included {}, {:.1} tps",
+            self.submitted,
+            self.accepted(),
+            self.included(),
+            self.accepted() as f64 / start.elapsed().as_secs_f64()
+        )
+    }
+}
+
+/// Runs load on network `network_id`
+pub struct Generator<'a> {
+    pub backend: &'a dyn Backend,
+    pub directory_manager: &'a DirectoryManager,
+    pub graphql: &'a GraphQl,
+    pub network_id: &'a str,
+}
+
+impl Generator<'_> {
+    pub fn run(&self, config: &Config) -> Result<Report> {
+        let endpoints = self
+            .graphql
+            .node_endpoints(self.network_id, &config.nodes)?;
+        let clients: Vec<Client> = endpoints.values().map(|e| Client::new(e)).collect();
+        let mut senders = self.senders(config, &clients)?;
+        let mut zkapp_commands = config.zkapp_commands.iter();
+
+        let total = config.total();
+        info!(
+            "Sending {total} transactions from {} senders through {} nodes.",
+            senders.len(),
+            clients.len()
+        );
+        let mut tally = Tally::default();
+        let start = Instant::now();
+        let mut last_poll = start;
+        for index in 0..total {
+            let scheduled = start + Duration::from_secs_f64(index as f64 / config.tps);
+            if let Some(wait) = scheduled.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+            let sender_index = index as usize % senders.len();
+            let client = &clients[senders[sender_index].node];
+            let zkapp_command = config
+                .is_zkapp(index)
+                .then(|| zkapp_command_or_warn(&mut zkapp_commands))
+                .flatten();
+
+            let sent_at = Instant::now();
+            let sent = match zkapp_command {
+                Some(zkapp_command) => client
+                    .send_zkapp(zkapp_command)
+                    .map(|sent| (sent.hash, true)),
+                None => {
+                    let recipient = senders[(sender_index + 1) % senders.len()]
+                        .public_key
+                        .clone();
+                    send_payment(client, &mut senders[sender_index], recipient, config)
+                        .map(|hash| (hash, false))
+                }
+            };
+            tally.submitted += 1;
+            tally
+                .submit_latencies
+                .push(sent_at.elapsed().as_secs_f64() * 1000.0);
+            match sent {
+                Ok((hash, zkapp)) => {
+                    if zkapp {
+                        tally.zkapp_commands += 1;
+                    } else {
+                        tally.payments += 1;
+                    }
+                    tally.pending.insert(hash, sent_at);
+                }
+                Err(e) => *tally.errors.entry(e.to_string()).or_default() += 1,
+            }
+
+            if last_poll.elapsed() >= POLL_INTERVAL {
+                tally.poll_inclusion(&clients[0]);
+                info!("{}", tally.summary(total, start));
+                last_poll = Instant::now();
+            }
+        }
+        let duration = start.elapsed();
+
+        let waiting_since = Instant::now();
+        while !tally.pending.is_empty() && waiting_since.elapsed() < config.inclusion_timeout {
+            tally.poll_inclusion(&clients[0]);
+            info!(
+                "{}, waiting for {} to be included",
+                tally.summary(total, start),
+                tally.pending.len()
+            );
+            if !tally.pending.is_empty() {
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+
+        Ok(Report {
+            network_id: self.network_id.to_string(),
+            target_tps: config.tps,
+            duration_secs: duration.as_secs_f64(),
+            achieved_tps: tally.accepted() as f64 / duration.as_secs_f64().max(f64::EPSILON),
+            senders: senders.len(),
+            nodes: endpoints.into_keys().collect(),
+            submitted: tally.submitted,
+            accepted: tally.accepted(),
+            included: tally.included(),
+            payments: tally.payments,
+            zkapp_commands: tally.zkapp_commands,
+            submit_latency_ms: percentiles(&mut tally.submit_latencies),
+            inclusion_latency_secs: percentiles(&mut tally.inclusion_latencies),
+            errors: tally.errors,
+        })
+    }
+
+    /// Imports and unlocks the key pairs of the senders on their nodes
+    fn senders(&self, config: &Config, clients: &[Client]) -> Result<Vec<Sender>> {
+        let names = if config.senders.is_empty() {
+            self.directory_manager
+                .network_keypair_names(self.network_id)?
+        } else {
+            config.senders.clone()
+        };
+        if names.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No key pair in network '{}'", self.network_id),
+            ));
+        }
+
+        let mut senders = vec![];
+        for (index, name) in names.into_iter().enumerate() {
+            let (file, public_key) = self
+                .directory_manager
+                .network_keypair(self.network_id, &name)?;
+            let path = self
+                .backend
+                .service_path(&Path::new(NETWORK_KEYPAIRS).join(file));
+            let node = index % clients.len();
+            clients[node].import_account(&path, MINA_PRIVKEY_PASS)?;
+            clients[node].unlock_account(&public_key, MINA_PRIVKEY_PASS)?;
+            senders.push(Sender {
+                name,
+                public_key,
+                node,
+                nonce: None,
+            });
+        }
+        Ok(senders)
+    }
+}
+
+fn zkapp_command_or_warn<'a>(commands: &mut impl Iterator<Item = &'a Value>) -> Option<&'a Value> {
+    let command = commands.next();
+    if command.is_none() {
+        warn!("No signed zkApp command left, sending a payment instead.");
+    }
+    command
+}
+
+/// Sends a payment of `sender` with its next nonce, returning its hash
+fn send_payment(
+    client: &Client,
+    sender: &mut Sender,
+    to: String,
+    config: &Config,
+) -> Result<String> {
+    let nonce = match sender.nonce {
+        Some(nonce) => nonce,
+        None => {
+            let account = client.account(&sender.public_key)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Account '{}' is not in the ledger", sender.name),
+                )
+            })?;
+            account.inferred_nonce.or(account.nonce).unwrap_or_default()
+        }
+    };
+    let payment = Payment {
+        from: sender.public_key.clone(),
+        to,
+        amount: config.amount,
+        fee: config.fee,
+        nonce: Some(nonce as u32),
+        memo: None,
+    };
+    match client.send_payment(&payment) {
+        Ok(sent) => {
+            sender.nonce = Some(nonce + 1);
+            Ok(sent.hash)
+        }
+        Err(e) => {
+            sender.nonce = None;
+            Err(e)
+        }
+    }
+}
+
+/// Nearest-rank percentiles of `values`, `None` if empty
+fn percentiles(values: &mut [f64]) -> Option<Percentiles> {
+    values.sort_by(f64::total_cmp);
+    let rank = |p: f64| {
+        let index = ((p / 100.0 * values.len() as f64).ceil() as usize).max(1) - 1;
+        values[index]
+    };
+    (!values.is_empty()).then(|| Percentiles {
+        p50: rank(50.0),
+        p90: rank(90.0),
+        p99: rank(99.0),
+        max: values[values.len() - 1],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+    use serde_json::json;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+    };
+    use tempdir::TempDir;
+
+    fn config(tps: f64, secs: u64, zkapp_ratio: f64) -> Config {
+        Config {
+            tps,
+            duration: Duration::from_secs(secs),
+            senders: vec![],
+            nodes: vec![],
+            amount: 1_000_000,
+            fee: 10_000_000,
+            zkapp_ratio,
+            zkapp_commands: vec![],
+            inclusion_timeout: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_mix() {
+        assert_eq!(config(2.5, 60, 0.0).total(), 150);
+        assert_eq!(config(0.5, 3, 0.0).total(), 2);
+
+        let count = |config: &Config| (0..100).filter(|i| config.is_zkapp(*i)).count();
+        assert_eq!(count(&config(1.0, 1, 0.0)), 0);
+        assert_eq!(count(&config(1.0, 1, 0.25)), 25);
+        assert_eq!(count(&config(1.0, 1, 1.0)), 100);
+        let quarter = config(1.0, 1, 0.25);
+        let zkapps: Vec<u64> = (0..8).filter(|i| quarter.is_zkapp(*i)).collect();
+        assert_eq!(zkapps, [3, 7]);
+    }
+
+    #[test]
+    fn test_percentiles() {
+        assert_eq!(percentiles(&mut []), None);
+
+        let mut values: Vec<f64> = (1..=100).rev().map(f64::from).collect();
+        let p = percentiles(&mut values).unwrap();
+        assert_eq!((p.p50, p.p90, p.p99, p.max), (50.0, 90.0, 99.0, 100.0));
+
+        let p = percentiles(&mut [3.5]).unwrap();
+        assert_eq!((p.p50, p.max), (3.5, 3.5));
+    }
+
+    /// Answers one request per response with `responses` in order, on a local endpoint.
+    /// The bodies of the requests are sent to the returned receiver.
+    fn serve(responses: Vec<Value>) -> (String, mpsc::Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/graphql", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                sender.send(serde_json::from_slice(&body).unwrap()).unwrap();
+
+                let response = response.to_string();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                    response.len()
+                )
+                .unwrap();
+            }
+        });
+        (endpoint, receiver)
+    }
+
+    fn imported(public_key: &str) -> Vec<Value> {
+        vec![
+            json!({ "data": { "importAccount": {
+                "publicKey": public_key, "alreadyImported": false
+            } } }),
+            json!({ "data": { "unlockAccount": { "publicKey": public_key } } }),
+        ]
+    }
+
+    fn account(public_key: &str, inferred_nonce: u64) -> Value {
+        json!({ "data": { "account": {
+            "publicKey": public_key,
+            "balance": { "total": "1500000000", "liquid": null, "locked": null },
+            "nonce": "0",
+            "inferredNonce": inferred_nonce.to_string(),
+            "delegate": null
+        } } })
+    }
+
+    fn payment(hash: &str, nonce: u64, from: &str, to: &str) -> Value {
+        json!({ "data": { "sendPayment": { "payment": {
+            "id": "Ckpid", "hash": hash, "kind": "PAYMENT", "nonce": nonce,
+            "from": from, "to": to, "amount": "1000000", "fee": "10000000", "memo": ""
+        } } } })
+    }
+
+    fn best_chain(hashes: &[&str]) -> Value {
+        let user_commands: Vec<Value> = hashes.iter().map(|hash| json!({ "hash": hash })).collect();
+        json!({ "data": { "bestChain": [{
+            "stateHash": "3NKtip",
+            "creator": "B62qa",
+            "commandTransactionCount": hashes.len(),
+            "protocolState": {
+                "previousStateHash": "3NKparent",
+                "consensusState": {
+                    "blockHeight": "12", "epoch": "0", "slot": "12", "slotSinceGenesis": "12",
+                    "stakingEpochData": { "seed": "2vaStaking" },
+                    "nextEpochData": { "seed": "2vaNext" }
+                }
+            },
+            "transactions": { "userCommands": user_commands, "zkappCommands": [] }
+        }] } })
+    }
+
+    #[test]
+    fn test_run() {
+        let tempdir = TempDir::new("test_load").expect("Cannot create temporary directory");
+        let directory_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        let keypairs = directory_manager
+            .generate_dir_structure("test")
+            .unwrap()
+            .join(NETWORK_KEYPAIRS);
+        std::fs::write(keypairs.join("sender-a.pub"), "B62qa\n").unwrap();
+        std::fs::write(keypairs.join("sender-b.pub"), "B62qb\n").unwrap();
+
+        // sender-a sends through node-a, sender-b through node-b, the best chain is polled on
+        // node-a; the first payment of sender-b is rejected and its nonce read again
+        let mut node_a = imported("B62qa");
+        node_a.extend([
+            account("B62qa", 5),
+            payment("5Ja5", 5, "B62qa", "B62qb"),
+            payment("5Ja6", 6, "B62qa", "B62qb"),
+            best_chain(&["5Ja5", "5Jb2", "5Ja6"]),
+        ]);
+        let mut node_b = imported("B62qb");
+        node_b.extend([
+            account("B62qb", 2),
+            json!({ "data": null, "errors": [{ "message": "Insufficient fee" }] }),
+            account("B62qb", 2),
+            payment("5Jb2", 2, "B62qb", "B62qa"),
+        ]);
+        let (endpoint_a, requests_a) = serve(node_a);
+        let (endpoint_b, requests_b) = serve(node_b);
+        let node = |endpoint: &str| {
+            json!({
+                "graphql_uri": endpoint, "private_key": null, "node_type": "Block_producer"
+            })
+        };
+        let info = json!({
+            "network_id": "test",
+            "nodes": { "node-a": node(&endpoint_a), "node-b": node(&endpoint_b) }
+        });
+        std::fs::write(
+            directory_manager.network_file_path("test"),
+            info.to_string(),
+        )
+        .unwrap();
+
+        let backend = MockBackend::default();
+        let graphql = GraphQl::new(directory_manager.clone());
+        let generator = Generator {
+            backend: &backend,
+            directory_manager: &directory_manager,
+            graphql: &graphql,
+            network_id: "test",
+        };
+        let report = generator
+            .run(&Config {
+                tps: 100.0,
+                duration: Duration::from_millis(40),
+                inclusion_timeout: Duration::from_secs(60),
+                ..config(0.0, 0, 0.0)
+            })
+            .unwrap();
+
+        assert_eq!(report.senders, 2);
+        assert_eq!(report.nodes, ["node-a", "node-b"]);
+        assert_eq!(report.submitted, 4);
+        assert_eq!((report.accepted, report.payments), (3, 3));
+        assert_eq!(report.included, 3);
+        assert_eq!(report.errors.len(), 1);
+        let (error, count) = report.errors.iter().next().unwrap();
+        assert!(error.contains("Insufficient fee"));
+        assert_eq!(*count, 1);
+
+        let payments = |requests: mpsc::Receiver<Value>| -> Vec<(String, String, String)> {
+            requests
+                .try_iter()
+                .filter(|request| request["query"].as_str().unwrap().contains("sendPayment"))
+                .map(|request| {
+                    let input = &request["variables"]["input"];
+                    let field = |name: &str| input[name].as_str().unwrap().to_string();
+                    (field("from"), field("to"), field("nonce"))
+                })
+                .collect()
+        };
+        let payment = |from: &str, to: &str, nonce: &str| {
+            (from.to_string(), to.to_string(), nonce.to_string())
+        };
+        assert_eq!(
+            payments(requests_a),
+            [
+                payment("B62qa", "B62qb", "5"),
+                payment("B62qa", "B62qb", "6")
+            ]
+        );
+        assert_eq!(
+            payments(requests_b),
+            [
+                payment("B62qb", "B62qa", "2"),
+                payment("B62qb", "B62qa", "2")
+            ]
+        );
+    }
+}
@@ -1,33 +1,53 @@
+mod backend;
+mod bundle;
+mod chaos;
 mod cli;
+mod commands;
 mod directory_manager;
 mod docker;
+mod faults;
+mod fork;
 mod genesis_ledger;
+mod git_build;
 mod graphql;
 mod keys;
+mod load;
 mod native;
 mod output;
+mod scenario;
 mod service;
+mod snapshot;
 mod topology;
+mod topology_validation;
+mod tx;
+mod upgrade;
 mod utils;
+mod wait;
 
 use crate::{
     genesis_ledger::*,
     keys::{KeysManager, NodeKey},
-    native::{keys::NativeKeysManager, manager::NativeManager, mina_locator},
+    native::{
+        keys::NativeKeysManager,
+        manager::NativeManager,
+        mina_locator,
+        supervisor::{RestartPolicy, Supervisor},
+    },
     output::{network, node},
     service::{ServiceConfig, ServiceType},
-    utils::fetch_schema,
 };
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use cli::{
-    Cli, Command, CommandWithNetworkId, CommandWithNodeId, DefaultLogLevel, ExecutionMode,
-    NetworkCommand, NodeCommand,
+    ChaosCommand, Cli, Command, CommandWithNetworkId, CommandWithNodeId, DefaultLogLevel,
+    ExecutionMode, LoadCommand, NetworkCommand, NodeCommand, ScenarioCommand, TopologyCommand,
+    TxCommand,
 };
 use directory_manager::DirectoryManager;
-use docker::manager::{ContainerState, DockerManager};
+use docker::manager::DockerManager;
 use env_logger::{Builder, Env};
 use graphql::GraphQl;
 use log::{error, info, warn};
+use minimina::graphql_client::types::TransactionStatus;
 use std::{
     collections::HashMap,
     io::{Error, ErrorKind, Result},
@@ -56,6 +76,11 @@ fn main() -> Result<()> {
     let cli: Cli = Cli::parse();
     Builder::from_env(Env::default().default_filter_or(cli.command.log_level())).init();
 
+    // topology commands only read files, they need neither docker nor mina binaries
+    if let Command::Topology(topology_cmd) = &cli.command {
+        return handle_topology_command(topology_cmd);
+    }
+
     let directory_manager = DirectoryManager::new();
     let mode = cli.mode;
     let bin_path = resolve_bin_path(&mode, cli.bin_path)?;
@@ -88,7 +113,7 @@ fn main() -> Result<()> {
                 )?;
 
                 // build services from topology file
-                let services = handle_topology(
+                let mut services = handle_topology(
                     &cmd,
                     &directory_manager,
                     &network_id,
@@ -96,30 +121,28 @@ fn main() -> Result<()> {
                     libp2p_keys_opt,
                 )?;
 
+                // build the images or binaries of the services' git refs
+                if let Err(e) = handle_git_builds(&cmd, &directory_manager, &mode, &mut services) {
+                    return exit_with(format!("Failed to build git refs with error: {e}"));
+                }
+
                 // copy libp2p + network keys
                 if let Err(e) = directory_manager.copy_all_network_keys(&network_id, &services) {
                     return exit_with(format!("Failed to copy keys with error: {e}"));
                 }
 
-                match mode {
-                    ExecutionMode::Docker => {
-                        // generate docker compose
-                        if let Err(e) = docker.compose_generate_file(&services) {
-                            return exit_with(format!(
-                                "Failed to generate docker-compose.yaml with error: {e}"
-                            ));
-                        }
-                        create_network(&docker, &directory_manager, &network_id, &services)
-                    }
-                    ExecutionMode::Native => {
-                        let native = NativeManager::new(&network_path, native_bin(&bin_path));
-                        if let Err(e) = native.generate_config(&services) {
-                            return exit_with(format!(
-                                "Failed to generate native config with error: {e}"
-                            ));
-                        }
-                        create_network_native(&native, &directory_manager, &network_id, &services)
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::create_network(
+                    backend.as_ref(),
+                    &directory_manager,
+                    &network_id,
+                    &services,
+                ) {
+                    Ok(network) => {
+                        println!("{network}");
+                        Ok(())
                     }
+                    Err(e) => exit_with(format!("Failed to create network '{network_id}': {e}")),
                 }
             }
 
@@ -146,35 +169,16 @@ fn main() -> Result<()> {
                 let network_path = directory_manager.network_path(&network_id);
                 check_network_exists(&network_id)?;
 
-                let docker = DockerManager::new(&network_path);
-                let ls_out = match docker.compose_ls() {
-                    Ok(out) => out,
-                    Err(e) => {
-                        let error_message = format!(
-                            "Failed to get status from docker compose ls for network '{network_id}': {e}."
-                        );
-                        return exit_with(error_message);
-                    }
-                };
-
-                let ps_out = match docker.compose_ps(None) {
-                    Ok(out) => out,
-                    Err(e) => {
-                        let error_message = format!(
-                            "Failed to get status from docker compose ps for network '{network_id}': {e}."
-                        );
-                        return exit_with(error_message);
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::network_status(backend.as_ref(), &directory_manager, &network_id) {
+                    Ok(status) => {
+                        println!("{status}");
+                        Ok(())
                     }
-                };
-
-                let compose_file_path = docker.compose_path.to_str().unwrap();
-                let mut status = network::Status::new(&network_id);
-                status.update_from_compose_ls(ls_out, compose_file_path);
-                status.update_from_compose_ps(ps_out);
-                status.network_dir = network_path.into_os_string().into_string().unwrap();
-
-                println!("{status}");
-                Ok(())
+                    Err(e) => exit_with(format!(
+                        "Failed to get status for network '{network_id}': {e}."
+                    )),
+                }
             }
 
             NetworkCommand::Delete(cmd) => {
@@ -183,36 +187,13 @@ fn main() -> Result<()> {
 
                 let network_path = directory_manager.network_path(&network_id);
 
-                // Stop processes/containers first
-                match mode {
-                    ExecutionMode::Docker => {
-                        let docker = DockerManager::new(&network_path);
-                        if let Err(e) = docker.compose_down(None, true, true) {
-                            let error_message =
-                                format!("Failed to delete network '{network_id}': {e}");
-                            return exit_with(error_message);
-                        }
-                    }
-                    ExecutionMode::Native => {
-                        let native = NativeManager::new(&network_path, native_bin(&bin_path));
-                        if let Err(e) = native.destroy() {
-                            let error_message =
-                                format!("Failed to delete network '{network_id}': {e}");
-                            return exit_with(error_message);
-                        }
-                    }
-                }
-
-                match directory_manager.delete_network_directory(&network_id) {
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::delete_network(backend.as_ref(), &directory_manager, &network_id) {
                     Ok(_) => {
                         println!("{}", network::Delete { network_id });
                         Ok(())
                     }
-                    Err(e) => {
-                        let error_message =
-                            format!("Failed to delete network directory for '{network_id}': {e}");
-                        exit_with(error_message)
-                    }
+                    Err(e) => exit_with(format!("Failed to delete network '{network_id}': {e}")),
                 }
             }
 
@@ -244,41 +225,26 @@ fn main() -> Result<()> {
                     warn!("{e} In case network is unstable consider updating by running 'network create' again.");
                 }
 
-                match mode {
-                    ExecutionMode::Docker => {
-                        let docker = DockerManager::new(&network_path);
-                        match docker.compose_start_all() {
-                            Ok(output) => {
-                                if cmd.verbose {
-                                    println!("Status: {}", output.status);
-                                    println!("Stdout: {}", String::from_utf8_lossy(&output.stdout));
-                                    println!("Stderr: {}", String::from_utf8_lossy(&output.stderr));
-                                }
-                                println!("{}", network::Start { network_id });
-                                Ok(())
-                            }
-                            Err(e) => {
-                                let error_message =
-                                    format!("Failed to start network '{network_id}': {e}");
-                                exit_with(error_message)
-                            }
-                        }
-                    }
-                    ExecutionMode::Native => {
-                        let native = NativeManager::new(&network_path, native_bin(&bin_path));
-                        let services = directory_manager.get_services_info(&network_id)?;
-                        match native.start_all(&services, &network_id) {
-                            Ok(_) => {
-                                println!("{}", network::Start { network_id });
-                                Ok(())
-                            }
-                            Err(e) => {
-                                let error_message =
-                                    format!("Failed to start network '{network_id}': {e}");
-                                exit_with(error_message)
-                            }
+                if cmd.supervise && mode == ExecutionMode::Docker {
+                    return exit_with("--supervise is only supported in native mode".to_string());
+                }
+
+                if cmd.supervise {
+                    let native = NativeManager::new(&network_path, native_bin(&bin_path));
+                    let services = directory_manager.get_services_info(&network_id)?;
+                    return supervise_network_native(&cmd, &native, network_id, &services);
+                }
+
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::start_network(backend.as_ref(), &directory_manager, &network_id) {
+                    Ok(output) => {
+                        if cmd.verbose {
+                            println!("Output: {output}");
                         }
+                        println!("{}", network::Start { network_id });
+                        Ok(())
                     }
+                    Err(e) => exit_with(format!("Failed to start network '{network_id}': {e}")),
                 }
             }
 
@@ -288,120 +254,326 @@ fn main() -> Result<()> {
 
                 let network_path = directory_manager.network_path(&network_id);
 
-                match mode {
-                    ExecutionMode::Docker => {
-                        let docker = DockerManager::new(&network_path);
-                        match docker.compose_stop_all() {
-                            Ok(_) => {
-                                println!("{}", network::Stop { network_id });
-                                Ok(())
-                            }
-                            Err(e) => {
-                                let error_message =
-                                    format!("Failed to stop network '{network_id}': {e}");
-                                exit_with(error_message)
-                            }
-                        }
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::stop_network(backend.as_ref(), &directory_manager, &network_id) {
+                    Ok(_) => {
+                        println!("{}", network::Stop { network_id });
+                        Ok(())
                     }
-                    ExecutionMode::Native => {
-                        let native = NativeManager::new(&network_path, native_bin(&bin_path));
-                        match native.stop_all() {
-                            Ok(_) => {
-                                println!("{}", network::Stop { network_id });
-                                Ok(())
-                            }
-                            Err(e) => {
-                                let error_message =
-                                    format!("Failed to stop network '{network_id}': {e}");
-                                exit_with(error_message)
-                            }
-                        }
+                    Err(e) => exit_with(format!("Failed to stop network '{network_id}': {e}")),
+                }
+            }
+
+            NetworkCommand::Snapshot(cmd) => {
+                let network_id = cmd.network_id.network_id;
+                check_network_exists(&network_id)?;
+
+                let network_path = directory_manager.network_path(&network_id);
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::snapshot_network(
+                    backend.as_ref(),
+                    &directory_manager,
+                    &network_id,
+                    &cmd.name,
+                    &mode,
+                ) {
+                    Ok(snapshot) => {
+                        println!("{snapshot}");
+                        Ok(())
                     }
+                    Err(e) => exit_with(format!(
+                        "Failed to snapshot network '{network_id}' as '{}': {e}",
+                        cmd.name
+                    )),
                 }
             }
-        },
 
-        Command::Node(node_cmd) => match node_cmd {
-            NodeCommand::Start(cmd) => {
-                let node_id = cmd.node_args.node_id().to_string();
-                let network_id = cmd.node_args.network_id().to_string();
-                let container = format!("{node_id}-{network_id}");
+            NetworkCommand::Restore(cmd) => {
+                let snapshot_path = directory_manager.snapshot_path(&cmd.name);
+                let (staging, manifest) =
+                    match snapshot::Staging::unpack::<snapshot::Manifest>(&snapshot_path) {
+                        Ok(unpacked) => unpacked,
+                        Err(e) => {
+                            return exit_with(format!(
+                                "Failed to read snapshot '{}' from '{}': {e}",
+                                cmd.name,
+                                snapshot_path.display()
+                            ))
+                        }
+                    };
+                let network_id = cmd.network_id.unwrap_or(manifest.network_id.clone());
+
                 let network_path = directory_manager.network_path(&network_id);
-                let docker = DockerManager::new(&network_path);
-                let nodes = docker.compose_ps(None)?;
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::restore_network(
+                    backend.as_ref(),
+                    &directory_manager,
+                    &staging,
+                    &manifest,
+                    &network_id,
+                    &mode,
+                ) {
+                    Ok(restore) => {
+                        println!("{restore}");
+                        Ok(())
+                    }
+                    Err(e) => exit_with(format!(
+                        "Failed to restore snapshot '{}' as network '{network_id}': {e}",
+                        cmd.name
+                    )),
+                }
+            }
 
-                let mut _fresh_state;
+            NetworkCommand::Clone(cmd) => {
+                check_network_exists(&cmd.from)?;
 
-                _fresh_state = match docker.filter_container_by_name(nodes, &container) {
-                    Some(node) => match node.state {
-                        ContainerState::Running => {
-                            warn!("Node '{node_id}' is already running in network '{network_id}'.");
-                            false
-                        }
-                        ContainerState::Created => {
-                            info!("Starting node '{node_id}' in network '{network_id}' for the first time.");
-                            true
+                let network_path = directory_manager.network_path(&cmd.to);
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::clone_network(
+                    backend.as_ref(),
+                    &directory_manager,
+                    &cmd.from,
+                    &cmd.to,
+                    cmd.reset_genesis_timestamp,
+                ) {
+                    Ok(network) => {
+                        println!("{network}");
+                        Ok(())
+                    }
+                    Err(e) => exit_with(format!(
+                        "Failed to clone network '{}' as '{}': {e}",
+                        cmd.from, cmd.to
+                    )),
+                }
+            }
+
+            NetworkCommand::Export(cmd) => {
+                let network_id = cmd.network_id();
+                check_network_exists(network_id)?;
+
+                match commands::export_network(&directory_manager, network_id, &cmd.output()) {
+                    Ok(export) => {
+                        println!("{export}");
+                        Ok(())
+                    }
+                    Err(e) => exit_with(format!("Failed to export network '{network_id}': {e}")),
+                }
+            }
+
+            NetworkCommand::Import(cmd) => {
+                let (staging, manifest) =
+                    match snapshot::Staging::unpack::<bundle::Manifest>(&cmd.file) {
+                        Ok(unpacked) => unpacked,
+                        Err(e) => {
+                            return exit_with(format!(
+                                "Failed to read bundle '{}': {e}",
+                                cmd.file.display()
+                            ))
                         }
-                        container_state => {
-                            info!(
-                                "Node '{node_id}' is {} in network '{network_id}'.",
-                                container_state
-                            );
-                            false
+                    };
+                let network_id = cmd.network_id.unwrap_or(manifest.network_id.clone());
+
+                let network_path = directory_manager.network_path(&network_id);
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::import_network(
+                    backend.as_ref(),
+                    &directory_manager,
+                    &staging,
+                    &manifest,
+                    &network_id,
+                ) {
+                    Ok(network) => {
+                        println!("{network}");
+                        Ok(())
+                    }
+                    Err(e) => exit_with(format!(
+                        "Failed to import bundle '{}' as network '{network_id}': {e}",
+                        cmd.file.display()
+                    )),
+                }
+            }
+
+            NetworkCommand::Wait(cmd) => {
+                let network_id = cmd.network_id();
+                check_network_exists(network_id)?;
+
+                let graphql = GraphQl::new(directory_manager.clone());
+                match wait::wait(
+                    &graphql,
+                    network_id,
+                    &cmd.nodes,
+                    &cmd.until,
+                    std::time::Duration::from_secs(cmd.timeout),
+                ) {
+                    Ok(report) => {
+                        println!("{report}");
+                        if !report.met {
+                            exit(1);
                         }
-                    },
-                    None => {
-                        let error =
-                            format!("Node '{node_id}' does not exist in network '{network_id}'.");
-                        return handle_start_error(&node_id, error.as_str());
+                        Ok(())
+                    }
+                    Err(e) => exit_with(format!(
+                        "Failed to wait for {} on network '{network_id}': {e}",
+                        cmd.until
+                    )),
+                }
+            }
+
+            NetworkCommand::Partition(cmd) => {
+                let network_id = cmd.network_id();
+                check_network_exists(network_id)?;
+
+                let faults = faults::Faults {
+                    groups: cmd
+                        .groups
+                        .clone()
+                        .map(|groups| groups.0)
+                        .unwrap_or_default(),
+                    links: cmd.links.clone(),
+                };
+                let network_path = directory_manager.network_path(network_id);
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::partition_network(
+                    backend.as_ref(),
+                    &directory_manager,
+                    network_id,
+                    &faults,
+                ) {
+                    Ok(faults) => {
+                        println!("{faults}");
+                        Ok(())
+                    }
+                    Err(e) => exit_with(format!(
+                        "Failed to partition network '{network_id}': {e}. Run 'network heal' to remove partial faults."
+                    )),
+                }
+            }
+
+            NetworkCommand::Upgrade(cmd) => {
+                let network_id = cmd.network_id();
+                check_network_exists(network_id)?;
+
+                match (&mode, &cmd.bin) {
+                    (ExecutionMode::Docker, Some(_)) => {
+                        return exit_with("--bin is only supported in native mode".to_string())
                     }
+                    (ExecutionMode::Native, None) => {
+                        return exit_with("Native mode upgrades binaries, use --bin".to_string())
+                    }
+                    _ => {}
+                }
+                let upgrade = upgrade::Upgrade {
+                    release: upgrade::Release {
+                        image: cmd.image.clone(),
+                        archive_image: cmd.archive_image.clone(),
+                        bin_path: cmd.bin.clone(),
+                    },
+                    roles: cmd.roles.clone(),
+                    nodes: cmd.nodes.clone(),
+                    batch: cmd.batch as usize,
+                    wait_synced: cmd
+                        .wait_synced
+                        .then(|| std::time::Duration::from_secs(cmd.timeout)),
+                };
+                let network_path = directory_manager.network_path(network_id);
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                let graphql = GraphQl::new(directory_manager.clone());
+                let upgrader = upgrade::Upgrader {
+                    backend: backend.as_ref(),
+                    directory_manager: &directory_manager,
+                    graphql: &graphql,
+                    network_id,
                 };
+                match upgrader.run(&upgrade) {
+                    Ok(upgraded) => {
+                        println!("{upgraded}");
+                        Ok(())
+                    }
+                    Err(e) => exit_with(format!("Failed to upgrade network '{network_id}': {e}")),
+                }
+            }
 
-                if cmd.fresh_state {
-                    info!("Starting node '{node_id}' in network '{network_id}' with fresh state.");
-                    docker.compose_down(Some(container.clone()), true, false)?;
-                    docker.compose_create(Some(container.clone()))?;
-                    _fresh_state = true;
+            NetworkCommand::Fork(cmd) => {
+                let network_id = cmd.network_id();
+                check_network_exists(network_id)?;
+
+                match (&mode, &cmd.bin, &cmd.image) {
+                    (ExecutionMode::Docker, Some(_), _) => {
+                        return exit_with("--bin is only supported in native mode".to_string())
+                    }
+                    (ExecutionMode::Native, _, Some(_)) => {
+                        return exit_with("Native mode forks to binaries, use --bin".to_string())
+                    }
+                    _ => {}
                 }
+                let fork = fork::Fork {
+                    node: cmd.node.clone(),
+                    slot: cmd
+                        .slot
+                        .map(|slot| (slot, std::time::Duration::from_secs(cmd.timeout))),
+                    successor: cmd.successor.clone(),
+                    release: upgrade::Release {
+                        image: cmd.image.clone(),
+                        archive_image: cmd.archive_image.clone(),
+                        bin_path: cmd.bin.clone(),
+                    },
+                };
+                let network_path = directory_manager.network_path(network_id);
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                let successor_path = directory_manager.network_path(&cmd.successor);
+                let successor_backend =
+                    backend::for_mode(&mode, &successor_path, bin_path.as_deref());
+                let graphql = GraphQl::new(directory_manager.clone());
+                let forker = fork::Forker {
+                    backend: backend.as_ref(),
+                    successor_backend: successor_backend.as_ref(),
+                    directory_manager: &directory_manager,
+                    graphql: &graphql,
+                    network_id,
+                };
+                match forker.run(&fork) {
+                    Ok(forked) => {
+                        println!("{forked}");
+                        Ok(())
+                    }
+                    Err(e) => exit_with(format!("Failed to fork network '{network_id}': {e}")),
+                }
+            }
+
+            NetworkCommand::Heal(cmd) => {
+                let network_id = cmd.network_id;
+                check_network_exists(&network_id)?;
 
-                if cmd.import_accounts {
-                    warn!("Importing accounts for node '{node_id}' in network '{network_id}'. This can take a moment...");
-                    import_all_accounts(&docker, &directory_manager, &node_id, &network_id)?;
+                let network_path = directory_manager.network_path(&network_id);
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::heal_network(backend.as_ref(), &directory_manager, &network_id) {
+                    Ok(faults) => {
+                        println!("{faults}");
+                        Ok(())
+                    }
+                    Err(e) => exit_with(format!("Failed to heal network '{network_id}': {e}")),
                 }
+            }
+        },
 
-                match docker.compose_start(vec![&container]) {
-                    Ok(out) => {
-                        if out.status.success() {
-                            if cmd.graphql_filtered_logs {
-                                warn!("Waiting for graphql server to be operational so I can request filtered logs. This can take a moment...");
-                                let gql = GraphQl::new(directory_manager.clone());
-                                if let Some(gql_ep) = gql.get_endpoint(&node_id, &network_id) {
-                                    gql.wait_for_server(&gql_ep)?;
-                                    gql.request_filtered_logs(&gql_ep)?;
-                                }
-                            }
-
-                            if cmd.node_args.raw_output {
-                                println!(
-                                    "Node '{node_id}' on network '{network_id}' \
-                                          has been started. {}",
-                                    String::from_utf8_lossy(&out.stdout)
-                                );
-                            } else {
-                                println!(
-                                    "{}",
-                                    node::Start {
-                                        // fresh_state,
-                                        node_id,
-                                        network_id,
-                                    }
-                                )
-                            }
-
-                            Ok(())
-                        } else {
-                            handle_start_error(&node_id, String::from_utf8_lossy(&out.stderr))
-                        }
+        Command::Node(node_cmd) => match node_cmd {
+            NodeCommand::Start(cmd) => {
+                let node_id = cmd.node_args.node_id().to_string();
+                let network_id = cmd.node_args.network_id().to_string();
+                let network_path = directory_manager.network_path(&network_id);
+
+                check_network_exists(&network_id)?;
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::start_node(
+                    backend.as_ref(),
+                    &directory_manager,
+                    &node_id,
+                    &network_id,
+                    cmd.fresh_state,
+                    cmd.import_accounts,
+                ) {
+                    Ok(stdout) => {
+                        report_node_started(&cmd, &directory_manager, node_id, network_id, &stdout)
                     }
                     Err(e) => handle_start_error(&node_id, e),
                 }
@@ -410,35 +582,34 @@ fn main() -> Result<()> {
             NodeCommand::Stop(cmd) => {
                 let node_id = cmd.node_id().to_string();
                 let network_id = cmd.network_id().to_string();
-                let container = format!("{node_id}-{network_id}");
                 let network_path = directory_manager.network_path(&network_id);
-                let docker = DockerManager::new(&network_path);
 
-                match docker.compose_stop(vec![&container]) {
-                    Ok(out) => {
-                        if out.status.success() {
-                            if cmd.raw_output {
-                                println!(
-                                    "Node '{node_id}' on network '{network_id}' \
-                                          has been stopped. {}",
-                                    String::from_utf8_lossy(&out.stdout)
-                                );
-                            } else {
-                                println!(
-                                    "{}",
-                                    node::Stop {
-                                        node_id,
-                                        network_id,
-                                    }
-                                )
-                            }
-                            Ok(())
-                        } else {
-                            handle_stop_error(&node_id, String::from_utf8_lossy(&out.stderr))
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                let stdout = match commands::stop_node(
+                    backend.as_ref(),
+                    &directory_manager,
+                    &node_id,
+                    &network_id,
+                ) {
+                    Ok(stdout) => stdout,
+                    Err(e) => return handle_stop_error(&node_id, e),
+                };
+
+                if cmd.raw_output {
+                    println!(
+                        "Node '{node_id}' on network '{network_id}' \
+                              has been stopped. {stdout}"
+                    );
+                } else {
+                    println!(
+                        "{}",
+                        node::Stop {
+                            node_id,
+                            network_id,
                         }
-                    }
-                    Err(e) => handle_stop_error(&node_id, e),
+                    )
                 }
+                Ok(())
             }
 
             NodeCommand::Logs(cmd) => {
@@ -446,160 +617,96 @@ fn main() -> Result<()> {
                 let network_id = cmd.network_id();
                 let network_path = directory_manager.network_path(network_id);
 
-                match mode {
-                    ExecutionMode::Docker => {
-                        let docker = DockerManager::new(&network_path);
-                        let services = directory_manager
-                            .get_services_info(network_id)
-                            .expect("Failed to get services info");
-                        match docker.run_docker_logs(node_id, network_id) {
-                            Ok(output) => {
-                                if output.status.success() {
-                                    info!(
-                                        "Successfully got logs for '{node_id}' on '{network_id}'"
-                                    );
-                                    let out = if is_node_uptime_service(services, node_id) {
-                                        &output.stderr
-                                    } else {
-                                        &output.stdout
-                                    };
-                                    if cmd.raw_output {
-                                        println!("{}", String::from_utf8_lossy(out));
-                                    } else {
-                                        println!(
-                                            "{}",
-                                            output::node::Logs {
-                                                logs: String::from_utf8_lossy(out).into(),
-                                                network_id: network_id.into(),
-                                                node_id: node_id.into(),
-                                            }
-                                        )
-                                    }
-                                } else {
-                                    let error_message = format!(
-                                        "Failed to get logs for '{node_id}' on '{network_id}': {}",
-                                        String::from_utf8_lossy(&output.stderr)
-                                    );
-                                    return exit_with(error_message);
-                                }
-                            }
-                            Err(e) => error!("Error while running 'docker logs {node_id}'{e}"),
-                        }
-                    }
-                    ExecutionMode::Native => {
-                        let native = NativeManager::new(&network_path, native_bin(&bin_path));
-                        match native.service_logs(node_id) {
-                            Ok(logs) => {
-                                if cmd.raw_output {
-                                    println!("{logs}");
-                                } else {
-                                    println!(
-                                        "{}",
-                                        output::node::Logs {
-                                            logs,
-                                            network_id: network_id.into(),
-                                            node_id: node_id.into(),
-                                        }
-                                    )
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::node_logs(backend.as_ref(), &directory_manager, node_id, network_id)
+                {
+                    Ok(logs) => {
+                        info!("Successfully got logs for '{node_id}' on '{network_id}'");
+                        if cmd.raw_output {
+                            println!("{logs}");
+                        } else {
+                            println!(
+                                "{}",
+                                output::node::Logs {
+                                    logs,
+                                    network_id: network_id.into(),
+                                    node_id: node_id.into(),
                                 }
-                            }
-                            Err(e) => error!("Error getting logs for '{node_id}': {e}"),
+                            )
                         }
+                        Ok(())
                     }
+                    Err(e) => exit_with(format!(
+                        "Failed to get logs for '{node_id}' on '{network_id}': {e}"
+                    )),
                 }
-
-                Ok(())
             }
 
             NodeCommand::DumpArchiveData(cmd) => {
                 let network_id = cmd.network_id();
                 let node_id = cmd.node_id();
                 let network_path = directory_manager.network_path(cmd.network_id());
-                let docker = DockerManager::new(&network_path);
-                let services = directory_manager
-                    .get_services_info(network_id)
-                    .expect("Failed to get services info");
 
                 check_network_exists(network_id)?;
 
-                if !is_node_archive(services, node_id) {
-                    let error_message = format!(
-                        "Node '{node_id}' is not an archive node in '{network_id}' network."
-                    );
-                    return exit_with(error_message);
-                }
-
-                match docker.compose_dump_archive_data(network_id) {
-                    Ok(output) => {
-                        if output.status.success() {
-                            info!("Successfully dumped archive data for node '{node_id}', network '{network_id}'");
-                            if cmd.raw_output {
-                                println!("{}", String::from_utf8_lossy(&output.stdout));
-                            } else {
-                                println!(
-                                    "{}",
-                                    output::node::ArchiveData {
-                                        data: String::from_utf8_lossy(&output.stdout).into(),
-                                        network_id: network_id.into(),
-                                        node_id: node_id.into(),
-                                    }
-                                )
-                            }
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::dump_archive_data(
+                    backend.as_ref(),
+                    &directory_manager,
+                    node_id,
+                    network_id,
+                ) {
+                    Ok(data) => {
+                        info!("Successfully dumped archive data for node '{node_id}', network '{network_id}'");
+                        if cmd.raw_output {
+                            println!("{data}");
                         } else {
-                            let error_message = format!(
-                                "Failed to dump archive data for node '{node_id}', network '{network_id}': {}",
-                                String::from_utf8_lossy(&output.stderr)
-                            );
-                            return exit_with(error_message);
+                            println!(
+                                "{}",
+                                output::node::ArchiveData {
+                                    data,
+                                    network_id: network_id.into(),
+                                    node_id: node_id.into(),
+                                }
+                            )
                         }
+                        Ok(())
                     }
-                    Err(e) => {
-                        return exit_with(format!(
-                            "Error while dumping archive data for node '{node_id}', network_id '{network_id}': {e}"
-                        ))
-                    }
+                    Err(e) => exit_with(format!(
+                        "Failed to dump archive data for node '{node_id}', network '{network_id}': {e}"
+                    )),
                 }
-
-                Ok(())
             }
 
             NodeCommand::DumpPrecomputedBlocks(cmd) => {
                 let node_id = cmd.node_id();
                 let network_id = cmd.network_id();
                 let network_path = directory_manager.network_path(cmd.network_id());
-                let docker = DockerManager::new(&network_path);
 
                 check_network_exists(network_id)?;
 
-                match docker.compose_dump_precomputed_blocks(node_id, network_id) {
-                    Ok(output) => {
-                        if output.status.success() {
-                            info!("Successfully dumped precomputed blocks for '{node_id}' on '{network_id}'");
-                            if cmd.raw_output {
-                                println!("{}", String::from_utf8_lossy(&output.stdout));
-                            } else {
-                                println!(
-                                    "{}",
-                                    output::node::PrecomputedBlocks {
-                                        blocks: String::from_utf8_lossy(&output.stdout).into(),
-                                        network_id: network_id.into(),
-                                        node_id: node_id.into(),
-                                    }
-                                )
-                            }
-                        } else {
-                            let error_message = format!(
-                                "Failed to dump precomputed blocks for '{node_id}' on '{network_id}': {}", String::from_utf8_lossy(&output.stderr)
-                            );
-                            return exit_with(error_message);
-                        }
-                    }
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                let blocks = match backend.dump_precomputed_blocks(node_id) {
+                    Ok(blocks) => blocks,
                     Err(e) => {
-                        let error_message = format!(
-                            "Failed to dump precomputed blocks for '{node_id}' on '{network_id}': {e}"
-                        );
-                        return exit_with(error_message);
+                        return exit_with(format!(
+                        "Failed to dump precomputed blocks for '{node_id}' on '{network_id}': {e}"
+                    ))
                     }
+                };
+
+                info!("Successfully dumped precomputed blocks for '{node_id}' on '{network_id}'");
+                if cmd.raw_output {
+                    println!("{blocks}");
+                } else {
+                    println!(
+                        "{}",
+                        output::node::PrecomputedBlocks {
+                            blocks,
+                            network_id: network_id.into(),
+                            node_id: node_id.into(),
+                        }
+                    )
                 }
 
                 Ok(())
@@ -610,251 +717,326 @@ fn main() -> Result<()> {
                 let node_id = cmd.node_args.node_id();
                 let network_id = cmd.node_args.network_id();
                 let network_path = directory_manager.network_path(cmd.node_args.network_id());
-                let docker = DockerManager::new(&network_path);
-                let services = directory_manager
-                    .get_services_info(network_id)
-                    .expect("Failed to get services info");
                 check_network_exists(network_id)?;
 
-                if !is_node_archive(services, node_id) {
-                    let error_message = format!(
-                        "Node '{node_id}' is not an archive node in '{network_id}' network."
-                    );
-                    return exit_with(error_message);
-                }
-
-                if let Err(e) = genesis_ledger::set_slot_since_genesis(&network_path, start_slot) {
-                    let error_message = format!(
-                        "Failed to set slot since genesis to '{start_slot}' for node '{node_id}' on network '{network_id}': {e}"
-                    );
-                    return exit_with(error_message);
-                }
-
-                let archive_service_id = format!("{node_id}-service");
-                match docker.compose_run_replayer(&archive_service_id, network_id) {
-                    Ok(output) => {
-                        if output.status.success() {
-                            info!("Successfully ran replayer for node '{node_id}' on network '{network_id}' \
-                                    and start_slot_since_genesis '{start_slot}'");
-                            if cmd.node_args.raw_output {
-                                println!("{}", String::from_utf8_lossy(&output.stdout));
-                            } else {
-                                println!(
-                                    "{}",
-                                    output::node::ReplayerLogs {
-                                        logs: String::from_utf8_lossy(&output.stdout).into(),
-                                        network_id: network_id.into(),
-                                        node_id: node_id.into(),
-                                    }
-                                )
-                            }
+                let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+                match commands::run_replayer(
+                    backend.as_ref(),
+                    &directory_manager,
+                    node_id,
+                    network_id,
+                    start_slot,
+                ) {
+                    Ok(logs) => {
+                        info!("Successfully ran replayer for node '{node_id}' on network '{network_id}' \
+                                and start_slot_since_genesis '{start_slot}'");
+                        if cmd.node_args.raw_output {
+                            println!("{logs}");
                         } else {
-                            let error_message = format!(
-                                "Failed to run replayer for node '{node_id}' on network '{network_id}' \
-                                  and start_slot_since_genesis '{start_slot}': {}",
-                                String::from_utf8_lossy(&output.stderr)
-                            );
-                            return exit_with(error_message);
+                            println!(
+                                "{}",
+                                output::node::ReplayerLogs {
+                                    logs,
+                                    network_id: network_id.into(),
+                                    node_id: node_id.into(),
+                                }
+                            )
                         }
+                        Ok(())
                     }
-                    Err(e) => {
-                        return exit_with(format!(
-                            "Error while running replayer for node '{node_id}' on network '{network_id}' \
-                              and start_slot_since_genesis '{start_slot}': {e}"
-                        ));
-                    }
+                    Err(e) => exit_with(format!(
+                        "Failed to run replayer for node '{node_id}' on network '{network_id}' \
+                          and start_slot_since_genesis '{start_slot}': {e}"
+                    )),
                 }
-
-                Ok(())
             }
         },
-    }
-}
 
-fn create_network(
-    docker: &DockerManager,
-    directory_manager: &DirectoryManager,
-    network_id: &str,
-    services: &[ServiceConfig],
-) -> Result<()> {
-    match docker.compose_create(None) {
-        Ok(output) => {
-            if !output.status.success() {
-                let error_message = format!(
-                    "Failed to create network '{network_id}' with 'docker compose create': {}",
-                    String::from_utf8_lossy(&output.stderr)
-                );
-                return exit_with(error_message);
+        Command::Scenario(ScenarioCommand::Run(cmd)) => {
+            let scenario = match scenario::Scenario::from_file(&cmd.file) {
+                Ok(scenario) => scenario,
+                Err(e) => {
+                    return exit_with(format!(
+                        "Failed to read scenario '{}': {e}",
+                        cmd.file.display()
+                    ))
+                }
+            };
+            let network_id = cmd.network_id.unwrap_or(scenario.network_id.clone());
+
+            let mut global_args = vec!["--mode".to_string(), mode_name(&mode)];
+            if let Some(bin_path) = &bin_path {
+                global_args.extend(["--bin-path".to_string(), bin_path.display().to_string()]);
             }
-            info!("Successfully created docker-compose for network '{network_id}'!");
-
-            // if we have archive node we need to:
-            //  - create input file for replayer (for run-replayer command)
-            //  - create database and apply schema scripts
-            if let Some(archive_node) = ServiceConfig::get_archive_node(services) {
-                // generate input file for mina-replayer
-                default::LedgerGenerator::generate_replayer_input(
-                    &directory_manager.network_path(network_id),
-                )?;
+            let network_path = directory_manager.network_path(&network_id);
+            let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+            let runner = scenario::Runner {
+                backend: backend.as_ref(),
+                directory_manager: &directory_manager,
+                graphql: GraphQl::new(directory_manager.clone()),
+                network_id: &network_id,
+                global_args,
+            };
+            let run = runner.run(&scenario);
+
+            if let Some(junit_path) = &cmd.junit {
+                if let Err(e) = std::fs::write(junit_path, scenario::junit(&run)) {
+                    return exit_with(format!(
+                        "Failed to write JUnit report '{}': {e}",
+                        junit_path.display()
+                    ));
+                }
+            }
+            println!("{run}");
+            if !run.passed {
+                exit(1);
+            }
+            Ok(())
+        }
 
-                // create archive database and apply schema scripts
-                // start postgres container
-                let postgres_name = format!("postgres-{network_id}");
-                let error_message =
-                    format!("Failed to start postgres container in network '{network_id}'.");
+        Command::Tx(tx_cmd) => {
+            let (args, kind) = match tx_cmd {
+                TxCommand::Payment(cmd) => (cmd.tx, tx::Kind::Payment { amount: cmd.amount }),
+                TxCommand::Delegate(cmd) => (cmd, tx::Kind::Delegation),
+            };
+            let network_id = args.network_id();
+            check_network_exists(network_id)?;
 
-                match docker.compose_start(vec![&postgres_name]) {
-                    Ok(out) => {
-                        if out.status.success() {
-                            info!("Successfully started postgres container in network '{network_id}'!");
-                        } else {
-                            return exit_with(format!(
-                                "{}: {}",
-                                error_message,
-                                String::from_utf8_lossy(&out.stderr)
-                            ));
-                        }
+            let network_path = directory_manager.network_path(network_id);
+            let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+            let graphql = GraphQl::new(directory_manager.clone());
+            let sender = tx::Sender {
+                backend: backend.as_ref(),
+                directory_manager: &directory_manager,
+                graphql: &graphql,
+                network_id,
+            };
+            let transaction = tx::Transaction {
+                kind,
+                from: args.from.clone(),
+                to: args.to.clone(),
+                fee: args.fee,
+                memo: args.memo.clone(),
+            };
+            let wait = args
+                .wait
+                .then(|| std::time::Duration::from_secs(args.timeout));
+            match sender.send(args.node.as_deref(), &transaction, wait) {
+                Ok(sent) => {
+                    println!("{sent}");
+                    if wait.is_some() && sent.status != TransactionStatus::Included {
+                        exit(1);
                     }
-                    Err(e) => return exit_with(format!("{error_message}: {e}")),
-                };
-
-                // make sure postgres is running
-                container_is_running(docker, &postgres_name)?;
-
-                // create database
-                let cmd = ["createdb", "-U", "postgres", "archive"];
-                docker.exec(&postgres_name, &cmd)?;
-
-                // apply schema scripts
-                let scripts = archive_node.archive_schema_files.as_ref().unwrap();
-                apply_schema_scripts(
-                    docker.clone(),
-                    &postgres_name,
-                    scripts,
-                    &directory_manager.network_path(network_id),
-                )?;
-
-                // stop postgres
-                docker.compose_stop(vec![&postgres_name])?;
+                    Ok(())
+                }
+                Err(e) => exit_with(format!(
+                    "Failed to send transaction from '{}' to '{}' on network '{network_id}': {e}",
+                    args.from, args.to
+                )),
             }
+        }
 
-            // generate network.json and services.json
-            if let Err(e) = directory_manager.save_network_info(network_id, services) {
-                error!("Error generating network.json: {e}")
-            }
+        Command::Load(LoadCommand::Run(cmd)) => {
+            let network_id = cmd.network_id();
+            check_network_exists(network_id)?;
 
-            if let Err(e) = directory_manager.save_services_info(network_id, services) {
-                error!("Error generating services.json: {e}")
-            }
+            let zkapp_commands = match &cmd.zkapp_commands {
+                Some(path) => match std::fs::read_to_string(path)
+                    .and_then(|json| Ok(serde_json::from_str::<Vec<serde_json::Value>>(&json)?))
+                {
+                    Ok(zkapp_commands) => zkapp_commands,
+                    Err(e) => {
+                        return exit_with(format!(
+                            "Failed to read zkApp commands '{}': {e}",
+                            path.display()
+                        ))
+                    }
+                },
+                None => vec![],
+            };
+            let config = load::Config {
+                tps: cmd.tps,
+                duration: std::time::Duration::from_secs(cmd.duration),
+                senders: cmd.senders.clone(),
+                nodes: cmd.nodes.clone(),
+                amount: cmd.amount,
+                fee: cmd.fee,
+                zkapp_ratio: cmd.zkapp_ratio,
+                zkapp_commands,
+                inclusion_timeout: std::time::Duration::from_secs(cmd.inclusion_timeout),
+            };
 
-            println!("{}", output::generate_network_info(services, network_id));
+            let network_path = directory_manager.network_path(network_id);
+            let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+            let graphql = GraphQl::new(directory_manager.clone());
+            let generator = load::Generator {
+                backend: backend.as_ref(),
+                directory_manager: &directory_manager,
+                graphql: &graphql,
+                network_id,
+            };
+            let report = match generator.run(&config) {
+                Ok(report) => report,
+                Err(e) => {
+                    return exit_with(format!("Failed to run load on network '{network_id}': {e}"))
+                }
+            };
+            if let Some(report_path) = &cmd.report {
+                if let Err(e) = std::fs::write(report_path, report.to_string()) {
+                    return exit_with(format!(
+                        "Failed to write load report '{}': {e}",
+                        report_path.display()
+                    ));
+                }
+            }
+            println!("{report}");
             Ok(())
         }
-        Err(e) => {
-            let error_message = format!(
-                "Failed to register network '{network_id}' with 'docker compose create': {e}"
-            );
-            exit_with(error_message)
-        }
-    }
-}
-
-fn container_is_running(docker: &DockerManager, container_name: &str) -> Result<()> {
-    let mut container_running = false;
-    let mut retries = 0;
 
-    while !container_running && retries < TIMEOUT_IN_SECS {
-        let containers = docker.compose_ps(None)?;
-        let container = docker.filter_container_by_name(containers, container_name);
+        Command::Chaos(ChaosCommand::Run(cmd)) => {
+            let network_id = cmd.network_id();
+            check_network_exists(network_id)?;
+
+            let config = chaos::Config {
+                seed: cmd.seed.unwrap_or_else(|| {
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map(|now| now.as_nanos() as u64)
+                        .unwrap_or_default()
+                }),
+                roles: cmd.roles.clone(),
+                nodes: cmd.nodes.clone(),
+                duration_secs: cmd.duration,
+                interval: cmd.interval,
+                downtime: cmd.downtime,
+                max_down: cmd.max_down as usize,
+                fresh_state_ratio: cmd.fresh_state_ratio,
+            };
+            let network_path = directory_manager.network_path(network_id);
+            let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+            let runner = chaos::Runner {
+                backend: backend.as_ref(),
+                directory_manager: &directory_manager,
+                network_id,
+            };
+            match runner.run(&config, &cmd.events) {
+                Ok(log) => report_chaos_run(log.summary(&cmd.events)),
+                Err(e) => exit_with(format!(
+                    "Failed to run chaos with seed {} on network '{network_id}': {e}",
+                    config.seed
+                )),
+            }
+        }
 
-        if let Some(container) = container {
-            if container.state == ContainerState::Running {
-                container_running = true;
+        Command::Chaos(ChaosCommand::Replay(cmd)) => {
+            let log = match chaos::EventLog::from_file(&cmd.file) {
+                Ok(log) => log,
+                Err(e) => {
+                    return exit_with(format!(
+                        "Failed to read chaos events '{}': {e}",
+                        cmd.file.display()
+                    ))
+                }
+            };
+            let network_id = cmd.network_id.unwrap_or(log.network_id.clone());
+            check_network_exists(&network_id)?;
+
+            let network_path = directory_manager.network_path(&network_id);
+            let backend = backend::for_mode(&mode, &network_path, bin_path.as_deref());
+            let runner = chaos::Runner {
+                backend: backend.as_ref(),
+                directory_manager: &directory_manager,
+                network_id: &network_id,
+            };
+            match runner.replay(&log, &cmd.events) {
+                Ok(log) => report_chaos_run(log.summary(&cmd.events)),
+                Err(e) => exit_with(format!(
+                    "Failed to replay chaos events '{}' on network '{network_id}': {e}",
+                    cmd.file.display()
+                )),
             }
         }
 
-        retries += 1;
-        std::thread::sleep(std::time::Duration::from_secs(1));
+        Command::Topology(_) => unreachable!("topology commands are handled first"),
     }
+}
 
-    if !container_running {
-        return exit_with(format!(
-            "Failed to start container '{container_name}' within {TIMEOUT_IN_SECS}s",
-        ));
+/// Prints the summary of a chaos run, exiting with 1 if an action failed
+fn report_chaos_run(run: output::chaos::Run) -> Result<()> {
+    println!("{run}");
+    if run.errors > 0 {
+        exit(1);
     }
-
     Ok(())
 }
 
-#[allow(dead_code)]
-fn wait_for_daemon(
-    docker: &DockerManager,
-    node_id: &str,
-    network_id: &str,
-    client_port: u16,
-) -> Result<()> {
-    let mut retries = 0;
-    let mut daemon_running = false;
-    info!("Waiting for daemon to start for node '{node_id}' on network '{network_id}'...");
-    while !daemon_running && retries < TIMEOUT_IN_SECS {
-        let out = docker.compose_client_status(node_id, network_id, client_port)?;
-        if out.status.success() {
-            daemon_running = true;
-        } else {
-            retries += 1;
-            std::thread::sleep(std::time::Duration::from_secs(1));
+fn handle_topology_command(cmd: &TopologyCommand) -> Result<()> {
+    match cmd {
+        TopologyCommand::Validate(args) => {
+            let findings = topology_validation::validate(&args.file);
+            let valid = findings
+                .iter()
+                .all(|finding| finding.severity == topology_validation::Severity::Warning);
+
+            println!(
+                "{}",
+                output::topology::Validate {
+                    file: args.file.display().to_string(),
+                    valid,
+                    findings,
+                }
+            );
+            if !valid {
+                exit(1);
+            }
+            Ok(())
         }
     }
-    if !daemon_running {
-        return exit_with(format!(
-            "Failed to start daemon for node '{node_id}' on network '{network_id}' within {TIMEOUT_IN_SECS}s",
-        ));
-    }
-    Ok(())
 }
 
-/// Applies provided schema `scripts` to the postgres db, `postgres_name`
-fn apply_schema_scripts(
-    docker: DockerManager,
-    postgres_name: &str,
-    scripts: &Vec<String>,
+/// Generates a genesis ledger for the default network:
+/// 1 seed, 2 bps, and a snark coordinator with one woker
+fn generate_default_genesis_ledger(
+    cmd: &cli::CreateNetworkArgs,
+    bp_keys_opt: &mut Option<HashMap<String, NodeKey>>,
+    libp2p_keys_opt: &mut Option<HashMap<String, NodeKey>>,
     network_path: &Path,
+    docker_image: &str,
+    mode: &ExecutionMode,
+    bin_path: Option<&Path>,
 ) -> Result<()> {
-    // copy scripts first
-    for script in scripts {
-        let file_path = fetch_schema(script, network_path.to_path_buf()).unwrap();
-        let file_name = file_path.file_name().unwrap().to_str().unwrap();
-        let docker_file_path = Path::new("/tmp").join(file_path.file_name().unwrap());
-
-        info!("Copying schema script: {}", file_name);
-        docker.cp(postgres_name, &file_path, &docker_file_path)?;
-    }
+    info!("Genesis ledger not provided. Generating default genesis ledger.");
 
-    // then apply scripts 1 by 1
-    for script in scripts {
-        let file_path = fetch_schema(script, network_path.to_path_buf()).unwrap();
-        let file_name = file_path.file_name().unwrap().to_str().unwrap();
-        let docker_file_path = Path::new("/tmp").join(file_path.file_name().unwrap());
-        let cmd = [
-            "psql",
-            "-U",
-            "postgres",
-            "-d",
-            "archive",
-            "-f",
-            docker_file_path.to_str().unwrap(),
-        ];
-
-        info!("Applying schema script: {}", file_name);
-        docker.exec(postgres_name, &cmd)?;
-    }
+    generate_default_keys(
+        bp_keys_opt,
+        libp2p_keys_opt,
+        network_path,
+        docker_image,
+        mode,
+        bin_path,
+    )?;
 
-    Ok(())
+    // generate default genesis ledger
+    let timing = cmd.timing.timing_spec();
+    match default::LedgerGenerator::generate(
+        network_path,
+        bp_keys_opt.as_ref().unwrap(),
+        timing.as_ref(),
+        cmd.protocol.protocol_constants().block_window_duration_ms(),
+    ) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::InvalidData => {
+            exit_with(format!("Invalid block producer timing: {e}"))
+        }
+        Err(e) => {
+            error!("Error generating default ledger: {e}");
+            Ok(())
+        }
+    }
 }
 
-/// Generates a genesis ledger for the default network:
-/// 1 seed, 2 bps, and a snark coordinator with one woker
-fn generate_default_genesis_ledger(
+/// Generates the key-pairs and libp2p keys of the default network's services
+fn generate_default_keys(
     bp_keys_opt: &mut Option<HashMap<String, NodeKey>>,
     libp2p_keys_opt: &mut Option<HashMap<String, NodeKey>>,
     network_path: &Path,
@@ -862,8 +1044,6 @@ fn generate_default_genesis_ledger(
     mode: &ExecutionMode,
     bin_path: Option<&Path>,
 ) -> Result<()> {
-    info!("Genesis ledger not provided. Generating default genesis ledger.");
-
     // set default services to generate keys for
     let seeds = vec!["mina-seed-1"];
     let block_producers = vec!["mina-bp-1", "mina-bp-2"];
@@ -880,57 +1060,183 @@ fn generate_default_genesis_ledger(
     .concat();
 
     // generate key-pairs for default services based on mode
+    *bp_keys_opt = Some(
+        generate_bp_key_pairs(&all_services, network_path, docker_image, mode, bin_path).map_err(
+            |e| {
+                Error::other(format!(
+                    "Failed to generate key pairs for mina services: {e}"
+                ))
+            },
+        )?,
+    );
+    let libp2p_keys = match mode {
+        ExecutionMode::Docker => {
+            KeysManager::new(network_path, docker_image).generate_libp2p_key_pairs(&all_services)
+        }
+        ExecutionMode::Native => NativeKeysManager::new(
+            network_path,
+            bin_path.expect("native mode guarantees bin_path is resolved"),
+        )
+        .generate_libp2p_key_pairs(&all_services),
+    };
+    *libp2p_keys_opt = Some(libp2p_keys.map_err(|e| {
+        Error::other(format!(
+            "Failed to generate libp2p key pairs for mina services: {e}"
+        ))
+    })?);
+
+    Ok(())
+}
+
+/// Generates key-pairs named `names` in `network-keypairs` with the key generator of `mode`
+fn generate_bp_key_pairs(
+    names: &[&str],
+    network_path: &Path,
+    docker_image: &str,
+    mode: &ExecutionMode,
+    bin_path: Option<&Path>,
+) -> Result<HashMap<String, NodeKey>> {
     match mode {
         ExecutionMode::Docker => {
-            let keys_manager = KeysManager::new(network_path, docker_image);
-            *bp_keys_opt = Some(keys_manager.generate_bp_key_pairs(&all_services).map_err(
-                |e| {
-                    Error::other(format!(
-                        "Failed to generate key pairs for mina services: {e}"
-                    ))
-                },
-            )?);
-            *libp2p_keys_opt = Some(
-                keys_manager
-                    .generate_libp2p_key_pairs(&all_services)
-                    .map_err(|e| {
-                        Error::other(format!(
-                            "Failed to generate libp2p key pairs for mina services: {e}"
-                        ))
-                    })?,
-            );
+            KeysManager::new(network_path, docker_image).generate_bp_key_pairs(names)
+        }
+        ExecutionMode::Native => NativeKeysManager::new(
+            network_path,
+            bin_path.expect("native mode guarantees bin_path is resolved"),
+        )
+        .generate_bp_key_pairs(names),
+    }
+}
+
+/// Generates the genesis ledger from the ledger spec at `ledger_spec_path` and keys for its accounts.
+/// Nodes get an account too: those of the topology, or of the default network without one.
+fn generate_spec_genesis_ledger(
+    cmd: &cli::CreateNetworkArgs,
+    ledger_spec_path: &Path,
+    bp_keys_opt: &mut Option<HashMap<String, NodeKey>>,
+    libp2p_keys_opt: &mut Option<HashMap<String, NodeKey>>,
+    network_path: &Path,
+    mode: &ExecutionMode,
+    bin_path: Option<&Path>,
+) -> Result<()> {
+    let ledger_spec = match spec::LedgerSpec::from_file(ledger_spec_path) {
+        Ok(ledger_spec) => ledger_spec,
+        Err(e) => {
+            return exit_with(format!(
+                "Failed to read ledger spec '{}': {e}",
+                ledger_spec_path.display()
+            ))
         }
-        ExecutionMode::Native => {
-            let keys_manager = NativeKeysManager::new(
+    };
+
+    let node_keys: HashMap<String, String> = match &cmd.topology {
+        Some(topology_path) => match topology::Topology::new(topology_path) {
+            Ok(topology) => topology.public_keys(),
+            Err(e) => {
+                return exit_with(format!(
+                    "Failed to read topology '{}': {e}",
+                    topology_path.display()
+                ))
+            }
+        },
+        None => {
+            generate_default_keys(
+                bp_keys_opt,
+                libp2p_keys_opt,
                 network_path,
-                bin_path.expect("native mode guarantees bin_path is resolved"),
-            );
-            *bp_keys_opt = Some(keys_manager.generate_bp_key_pairs(&all_services).map_err(
-                |e| {
-                    Error::other(format!(
-                        "Failed to generate key pairs for mina services: {e}"
-                    ))
-                },
-            )?);
-            *libp2p_keys_opt = Some(
-                keys_manager
-                    .generate_libp2p_key_pairs(&all_services)
-                    .map_err(|e| {
-                        Error::other(format!(
-                            "Failed to generate libp2p key pairs for mina services: {e}"
-                        ))
-                    })?,
-            );
+                DEFAULT_DAEMON_DOCKER_IMAGE,
+                mode,
+                bin_path,
+            )?;
+            bp_keys_opt
+                .iter()
+                .flatten()
+                .map(|(name, key)| (name.clone(), key.key_string.clone()))
+                .collect()
         }
-    }
+    };
 
-    // generate default genesis ledger
-    if let Err(e) = default::LedgerGenerator::generate(network_path, bp_keys_opt.as_ref().unwrap())
-    {
-        error!("Error generating default ledger: {e}");
+    info!(
+        "Generating keys for the accounts of ledger spec '{}'.",
+        ledger_spec_path.display()
+    );
+    let account_names = ledger_spec.account_names();
+    let account_names: Vec<&str> = account_names.iter().map(String::as_str).collect();
+    let account_keys = generate_bp_key_pairs(
+        &account_names,
+        network_path,
+        DEFAULT_DAEMON_DOCKER_IMAGE,
+        mode,
+        bin_path,
+    )
+    .map_err(|e| Error::other(format!("Failed to generate key pairs for accounts: {e}")))?;
+
+    match spec::LedgerGenerator::generate(
+        network_path,
+        &ledger_spec,
+        &node_keys,
+        &account_keys,
+        cmd.protocol.protocol_constants().block_window_duration_ms(),
+    ) {
+        Ok(()) => Ok(()),
+        Err(e) => exit_with(format!("Failed to generate genesis ledger from spec: {e}")),
     }
+}
 
-    Ok(())
+/// Imports the genesis ledger from the exported ledger at `ledger_path`.
+/// Block producers take over accounts: those of the topology, or of the default network without one.
+fn import_genesis_ledger(
+    cmd: &cli::CreateNetworkArgs,
+    ledger_path: &Path,
+    bp_keys_opt: &mut Option<HashMap<String, NodeKey>>,
+    libp2p_keys_opt: &mut Option<HashMap<String, NodeKey>>,
+    network_path: &Path,
+    mode: &ExecutionMode,
+    bin_path: Option<&Path>,
+) -> Result<()> {
+    let block_producer_keys: Vec<(String, String)> = match &cmd.topology {
+        Some(topology_path) => match topology::Topology::new(topology_path) {
+            Ok(topology) => topology.block_producer_keys(),
+            Err(e) => {
+                return exit_with(format!(
+                    "Failed to read topology '{}': {e}",
+                    topology_path.display()
+                ))
+            }
+        },
+        None => {
+            generate_default_keys(
+                bp_keys_opt,
+                libp2p_keys_opt,
+                network_path,
+                DEFAULT_DAEMON_DOCKER_IMAGE,
+                mode,
+                bin_path,
+            )?;
+            let mut keys: Vec<(String, String)> = bp_keys_opt
+                .iter()
+                .flatten()
+                .filter(|(name, _)| name.starts_with("mina-bp-"))
+                .map(|(name, key)| (name.clone(), key.key_string.clone()))
+                .collect();
+            keys.sort();
+            keys
+        }
+    };
+
+    match import::LedgerImporter::generate(
+        network_path,
+        ledger_path,
+        cmd.ledger_subset(),
+        &cmd.replace_key,
+        &block_producer_keys,
+    ) {
+        Ok(()) => Ok(()),
+        Err(e) => exit_with(format!(
+            "Failed to import genesis ledger from '{}': {e}",
+            ledger_path.display()
+        )),
+    }
 }
 
 /// Generates a topology file for the default network:
@@ -1084,7 +1390,10 @@ fn check_network_exists(network_id: &str) -> Result<()> {
 
 /// Handles `network_id`'s genesis ledger
 ///
-/// If no genesis ledger is provided, a default ledger will be generated
+/// An exported ledger is imported, its largest delegates taken over by the block producers.
+/// A ledger spec is turned into a ledger with generated account keys.
+/// If neither a genesis ledger nor a spec is provided, a default ledger will be generated.
+/// The given protocol constants are then written into the ledger's runtime config.
 fn handle_genesis_ledger(
     cmd: &cli::CreateNetworkArgs,
     directory_manager: &DirectoryManager,
@@ -1096,6 +1405,59 @@ fn handle_genesis_ledger(
 ) -> Result<()> {
     let network_path = directory_manager.network_path(network_id);
 
+    if let Some(ledger_path) = &cmd.ledger_from {
+        import_genesis_ledger(
+            cmd,
+            ledger_path,
+            bp_keys_opt,
+            libp2p_keys_opt,
+            &network_path,
+            mode,
+            bin_path,
+        )?;
+    } else if let Some(ledger_spec_path) = &cmd.ledger_spec {
+        generate_spec_genesis_ledger(
+            cmd,
+            ledger_spec_path,
+            bp_keys_opt,
+            libp2p_keys_opt,
+            &network_path,
+            mode,
+            bin_path,
+        )?;
+    } else {
+        copy_or_generate_genesis_ledger(
+            cmd,
+            directory_manager,
+            network_id,
+            bp_keys_opt,
+            libp2p_keys_opt,
+            mode,
+            bin_path,
+        )?;
+    }
+
+    let protocol_constants = cmd.protocol.protocol_constants();
+    if protocol_constants != ProtocolConstants::default() {
+        info!("Writing protocol constants to the runtime config.");
+        if let Err(e) = protocol_constants.write(&network_path) {
+            return exit_with(format!("Failed to write protocol constants: {e}"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies the given genesis ledger to `network_id`'s directory, or generates the default one
+fn copy_or_generate_genesis_ledger(
+    cmd: &cli::CreateNetworkArgs,
+    directory_manager: &DirectoryManager,
+    network_id: &str,
+    bp_keys_opt: &mut Option<HashMap<String, NodeKey>>,
+    libp2p_keys_opt: &mut Option<HashMap<String, NodeKey>>,
+    mode: &ExecutionMode,
+    bin_path: Option<&Path>,
+) -> Result<()> {
     match &cmd.genesis_ledger {
         Some(genesis_ledger_path) => {
             if cmd.topology.is_none() {
@@ -1114,9 +1476,10 @@ fn handle_genesis_ledger(
             directory_manager.overwrite_genesis_timestamp(network_id, genesis_ledger_path)
         }
         None => generate_default_genesis_ledger(
+            cmd,
             bp_keys_opt,
             libp2p_keys_opt,
-            &network_path,
+            &directory_manager.network_path(network_id),
             DEFAULT_DAEMON_DOCKER_IMAGE,
             mode,
             bin_path,
@@ -1136,13 +1499,11 @@ fn create_services(
     match topology::Topology::new(topology_path) {
         Ok(topology) => {
             let peer_list_file = directory_manager.peer_list_file(network_id);
-            let services = topology.services(&peer_list_file);
+            let services = topology.services(&peer_list_file)?;
             let peers: Vec<&ServiceConfig> = ServiceConfig::get_seeds(&services);
             directory_manager.create_peer_list_file(network_id, &peers)?;
 
-            if let Some(uptime_service_backend) =
-                ServiceConfig::get_uptime_service_backend(&services)
-            {
+            for uptime_service_backend in ServiceConfig::get_uptime_service_backends(&services) {
                 match directory_manager
                     .copy_uptime_service_config(network_id, uptime_service_backend)
                 {
@@ -1173,6 +1534,37 @@ fn create_services(
     }
 }
 
+/// Builds the `git_build` refs of `services` from the mina checkout of `--mina-repo`
+/// or `$MINIMINA_MINA_REPO`
+fn handle_git_builds(
+    cmd: &cli::CreateNetworkArgs,
+    directory_manager: &DirectoryManager,
+    mode: &ExecutionMode,
+    services: &mut [ServiceConfig],
+) -> Result<()> {
+    if services.iter().all(|service| service.git_build.is_none()) {
+        return Ok(());
+    }
+    let repo = cmd
+        .mina_repo
+        .clone()
+        .or_else(|| std::env::var_os(git_build::MINA_REPO_ENV).map(PathBuf::from))
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The topology has git builds, provide a mina checkout with --mina-repo or ${}",
+                    git_build::MINA_REPO_ENV
+                ),
+            )
+        })?;
+    git_build::Builder {
+        repo,
+        builds_dir: directory_manager.builds_path(),
+    }
+    .apply(mode, services)
+}
+
 /// Creates service configs for the nodes specified in the topology file of the given `cmd`
 fn handle_topology(
     cmd: &cli::CreateNetworkArgs,
@@ -1183,9 +1575,12 @@ fn handle_topology(
 ) -> Result<Vec<ServiceConfig>> {
     match &cmd.topology {
         Some(topology_path) => {
-            if cmd.genesis_ledger.is_none() {
+            if cmd.genesis_ledger.is_none()
+                && cmd.ledger_spec.is_none()
+                && cmd.ledger_from.is_none()
+            {
                 error!(
-                    "Must provide a genesis ledger with a topology file, \
+                    "Must provide a genesis ledger, ledger spec or exported ledger with a topology file, \
                      keys will be incompatible otherwise."
                 );
 
@@ -1291,66 +1686,35 @@ fn check_execution_environment(mode: &ExecutionMode) -> Result<()> {
     }
 }
 
-fn create_network_native(
+/// Starts a native network under a supervisor, either in the foreground or as a detached watchdog
+fn supervise_network_native(
+    cmd: &cli::StartNetworkArgs,
     native: &NativeManager,
-    directory_manager: &DirectoryManager,
-    network_id: &str,
+    network_id: String,
     services: &[ServiceConfig],
 ) -> Result<()> {
-    // For native mode, create is a no-op (processes start on 'network start')
-    native.create(None)?;
-    info!("Successfully prepared native network '{network_id}'!");
-
-    // Handle archive node setup with local postgres
-    if let Some(archive_node) = ServiceConfig::get_archive_node(services) {
-        default::LedgerGenerator::generate_replayer_input(
-            &directory_manager.network_path(network_id),
-        )?;
-
-        // Create database using local psql
-        info!("Creating archive database using local postgres...");
-        let createdb_out = std::process::Command::new("createdb")
-            .args(["-U", "postgres", "archive"])
-            .output()?;
+    let policy = RestartPolicy::new(cmd.max_restarts, cmd.restart_backoff_secs);
 
-        if !createdb_out.status.success() {
-            warn!(
-                "createdb may have failed (database might already exist): {}",
-                String::from_utf8_lossy(&createdb_out.stderr)
-            );
+    if cmd.detach {
+        if let Err(e) = native.spawn_supervisor(&network_id, &policy) {
+            return exit_with(format!(
+                "Failed to start supervisor for network '{network_id}': {e}"
+            ));
         }
-
-        // Apply schema scripts directly via psql
-        if let Some(scripts) = &archive_node.archive_schema_files {
-            let network_path = directory_manager.network_path(network_id);
-            for script in scripts {
-                let file_path = fetch_schema(script, network_path.clone()).unwrap();
-                info!("Applying schema script: {}", file_path.display());
-                let psql_out = std::process::Command::new("psql")
-                    .args(["-U", "postgres", "-d", "archive", "-f"])
-                    .arg(&file_path)
-                    .output()?;
-
-                if !psql_out.status.success() {
-                    warn!(
-                        "psql schema application may have failed: {}",
-                        String::from_utf8_lossy(&psql_out.stderr)
-                    );
-                }
-            }
-        }
-    }
-
-    // generate network.json and services.json
-    if let Err(e) = directory_manager.save_network_info(network_id, services) {
-        error!("Error generating network.json: {e}")
+        println!("{}", network::Start { network_id });
+        return Ok(());
     }
 
-    if let Err(e) = directory_manager.save_services_info(network_id, services) {
-        error!("Error generating services.json: {e}")
+    let supervisor = Supervisor::new(native, &network_id, policy);
+    println!(
+        "{}",
+        network::Start {
+            network_id: network_id.clone()
+        }
+    );
+    if let Err(e) = supervisor.run(services) {
+        return exit_with(format!("Failed to supervise network '{network_id}': {e}"));
     }
-
-    println!("{}", output::generate_network_info(services, network_id));
     Ok(())
 }
 
@@ -1386,6 +1750,14 @@ fn resolve_bin_path(mode: &ExecutionMode, provided: Option<PathBuf>) -> Result<O
     }
 }
 
+/// Name of `mode` on the command line
+fn mode_name(mode: &ExecutionMode) -> String {
+    mode.to_possible_value()
+        .expect("execution modes are not skipped")
+        .get_name()
+        .to_string()
+}
+
 /// Unwrap the bin_path inside a native-mode branch.
 /// `resolve_bin_path` guarantees `Some(_)` when the mode is native.
 fn native_bin(bin_path: &Option<PathBuf>) -> &Path {
@@ -1404,57 +1776,44 @@ fn handle_start_error(node_id: &str, error: impl ToString) -> Result<()> {
     exit_with(error_message)
 }
 
-fn is_node_uptime_service(services: Vec<ServiceConfig>, node_id: &str) -> bool {
-    if let Some(uptime) = ServiceConfig::get_uptime_service_backend(&services) {
-        if uptime.service_name == node_id {
-            return true;
-        }
-    }
-    false
-}
-
-fn is_node_archive(services: Vec<ServiceConfig>, node_id: &str) -> bool {
-    if let Some(archive) = ServiceConfig::get_archive_node(&services) {
-        if archive.service_name == node_id {
-            return true;
-        }
-    }
-    false
-}
-
-fn import_all_accounts(
-    docker: &DockerManager,
+/// Requests filtered logs if asked to and prints the node start output
+fn report_node_started(
+    cmd: &cli::StartNodeCommandArgs,
     directory_manager: &DirectoryManager,
-    node_id: &str,
-    network_id: &str,
+    node_id: String,
+    network_id: String,
+    stdout: &str,
 ) -> Result<()> {
-    let account_files = directory_manager.get_network_keypair_files(network_id)?;
-    for account_file in account_files {
-        let out = docker.compose_import_account(node_id, network_id, &account_file);
-        match out {
-            Ok(output) => {
-                if output.status.success() {
-                    info!(
-                        "Successfully imported account from file '{account_file}' \
-                        for node '{node_id}' on network '{network_id}'",
-                    );
-                } else {
-                    let error_message = format!(
-                        "Failed to import account from file '{account_file}' \
-                        for node '{node_id}' on network '{network_id}': {}",
-                        String::from_utf8_lossy(&output.stderr),
-                    );
-                    return exit_with(error_message);
-                }
-            }
-            Err(e) => {
-                let error_message = format!(
-                    "Failed to import account from file '{account_file}' \
-                    for node '{node_id}' on network '{network_id}': {e}",
-                );
-                return exit_with(error_message);
+    if cmd.graphql_filtered_logs {
+        warn!("Waiting for graphql server to be operational so I can request filtered logs. This can take a moment...");
+        let gql = GraphQl::new(directory_manager.clone());
+        if let Some(gql_ep) = gql.get_endpoint(&node_id, &network_id) {
+            if let Err(e) = gql
+                .wait_for_server(&gql_ep)
+                .and_then(|_| gql.request_filtered_logs(&gql_ep))
+            {
+                return exit_with(format!(
+                    "Failed to request filtered logs from '{gql_ep}': {e}"
+                ));
             }
         }
     }
+
+    if cmd.node_args.raw_output {
+        println!(
+            "Node '{node_id}' on network '{network_id}' \
+                  has been started. {stdout}"
+        );
+    } else {
+        println!(
+            "{}",
+            node::Start {
+                // fresh_state,
+                node_id,
+                network_id,
+            }
+        )
+    }
+
     Ok(())
 }
@@ -3,3 +3,4 @@ pub mod manager;
 pub mod mina_locator;
 pub mod port_manager;
 pub mod process_tracker;
+pub mod supervisor;
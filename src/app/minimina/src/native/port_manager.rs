@@ -1,7 +1,11 @@
-use crate::service::{ServiceConfig, ServiceType};
+use crate::service::{ServiceConfig, ServiceType, MAX_PORT};
+use std::collections::{HashMap, HashSet};
 use std::io;
 use std::net::TcpListener;
 
+/// Port of the postgres server backing the archive nodes
+pub const POSTGRES_PORT: u16 = 5432;
+
 pub fn check_ports_available(ports: &[u16]) -> io::Result<()> {
     for &port in ports {
         TcpListener::bind(format!("127.0.0.1:{}", port)).map_err(|_| {
@@ -14,22 +18,152 @@ pub fn check_ports_available(ports: &[u16]) -> io::Result<()> {
     Ok(())
 }
 
-pub fn collect_all_ports(services: &[ServiceConfig]) -> Vec<u16> {
+/// Ports of the services run in native mode, which skips uptime service backends
+pub fn collect_all_ports(services: &[ServiceConfig]) -> io::Result<Vec<u16>> {
+    let mut ports = vec![];
+    for service in services
+        .iter()
+        .filter(|service| service.service_type != ServiceType::UptimeServiceBackend)
+    {
+        ports.extend(service_ports(service)?);
+    }
+    Ok(ports)
+}
+
+/// Ports bound by a single service: its daemon port range, archive port and postgres,
+/// or the host port of an uptime service backend
+pub fn service_ports(service: &ServiceConfig) -> io::Result<Vec<u16>> {
     let mut ports = Vec::new();
+    if service.client_port.is_some() {
+        ports.extend(service.daemon_ports()?.all());
+    }
+    if let Some(archive_port) = service.archive_port {
+        ports.push(archive_port);
+    }
+    if service.service_type == ServiceType::ArchiveNode {
+        ports.push(POSTGRES_PORT);
+    }
+    if let Some(uptime_service_port) = service.uptime_service_port {
+        ports.push(uptime_service_port);
+    }
+    Ok(ports)
+}
+
+/// Ports bound more than once, as `(port, first service, second service)`.
+/// A service can conflict with itself when its explicit ports overlap its derived ones.
+/// Postgres is shared by all archive nodes and never conflicts.
+pub fn find_port_conflicts(services: &[ServiceConfig]) -> io::Result<Vec<(u16, &str, &str)>> {
+    let mut owners: HashMap<u16, &str> = HashMap::new();
+    let mut conflicts = vec![];
     for service in services {
-        if let Some(client_port) = service.client_port {
-            ports.push(client_port);
-            ports.push(client_port + 1);
-            ports.push(client_port + 2);
-            ports.push(client_port + 3);
-            ports.push(client_port + 4);
-        }
-        if let Some(archive_port) = service.archive_port {
-            ports.push(archive_port);
+        for port in service_ports(service)?
+            .into_iter()
+            .filter(|port| *port != POSTGRES_PORT)
+        {
+            if let Some(owner) = owners.insert(port, &service.service_name) {
+                conflicts.push((port, owner, service.service_name.as_str()));
+            }
         }
-        if service.service_type == ServiceType::ArchiveNode {
-            ports.push(5432);
+    }
+    Ok(conflicts)
+}
+
+/// Rejects services binding the same port, either within one service or across services
+pub fn check_port_conflicts(services: &[ServiceConfig]) -> io::Result<()> {
+    match find_port_conflicts(services)?.first() {
+        Some((port, first, second)) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("Port {port} is used by both '{first}' and '{second}'"),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Smallest multiple of 1000 by which the ports of `services` can be shifted
+/// so that none of them is in `used` or above [`MAX_PORT`]
+pub fn free_port_offset(services: &[ServiceConfig], used: &HashSet<u16>) -> io::Result<u16> {
+    let mut ports: Vec<u32> = vec![];
+    for service in services {
+        ports.extend(
+            service_ports(service)?
+                .into_iter()
+                .filter(|port| *port != POSTGRES_PORT)
+                .map(u32::from),
+        );
+    }
+    let max_port = ports.iter().copied().max().unwrap_or_default();
+    (0..)
+        .map(|k| k * 1000)
+        .take_while(|offset| max_port + offset <= u32::from(MAX_PORT))
+        .find(|offset| {
+            ports
+                .iter()
+                .all(|port| !used.contains(&((port + offset) as u16)))
+        })
+        .map(|offset| offset as u16)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "No free port range left for the services",
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn daemon(name: &str, client_port: u16) -> ServiceConfig {
+        ServiceConfig {
+            service_name: name.to_string(),
+            client_port: Some(client_port),
+            ..Default::default()
         }
     }
-    ports
+
+    #[test]
+    fn test_service_ports_follow_explicit_ports() {
+        let service = ServiceConfig {
+            rest_port: Some(9000),
+            metrics_port: Some(9100),
+            ..daemon("mina-bp-1", 7000)
+        };
+        assert_eq!(
+            service_ports(&service).unwrap(),
+            [7000, 9000, 7002, 9100, 9101]
+        );
+    }
+
+    #[test]
+    fn test_check_port_conflicts() {
+        let services = [daemon("mina-seed-1", 7000), daemon("mina-bp-1", 7005)];
+        assert!(check_port_conflicts(&services).is_ok());
+
+        let services = [daemon("mina-seed-1", 7000), daemon("mina-bp-1", 7003)];
+        let err = check_port_conflicts(&services).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Port 7003 is used by both 'mina-seed-1' and 'mina-bp-1'"
+        );
+
+        // a port set explicitly may clash with a derived one of the same service
+        let services = [ServiceConfig {
+            rest_port: Some(7002),
+            ..daemon("mina-bp-1", 7000)
+        }];
+        assert!(check_port_conflicts(&services).is_err());
+    }
+
+    #[test]
+    fn test_free_port_offset() {
+        let services = [daemon("mina-seed-1", 3100), daemon("mina-bp-1", 3105)];
+        assert_eq!(free_port_offset(&services, &HashSet::new()).unwrap(), 0);
+
+        let used = HashSet::from([3100, 4109]);
+        assert_eq!(free_port_offset(&services, &used).unwrap(), 2000);
+
+        let services = [daemon("mina-seed-1", 65000)];
+        let used = HashSet::from([65003]);
+        assert!(free_port_offset(&services, &used).is_err());
+    }
 }
@@ -3,6 +3,7 @@ use nix::unistd::Pid;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
+    fs::File,
     io,
     path::{Path, PathBuf},
 };
@@ -14,6 +15,23 @@ pub struct ProcessRecord {
     pub started_at: String,
     pub log_file: PathBuf,
     pub config_dir: PathBuf,
+    /// Number of times the supervisor has restarted the service
+    #[serde(default)]
+    pub restart_count: u32,
+    /// Exit code of the last crash seen by the supervisor (128 + signal if killed)
+    #[serde(default)]
+    pub last_exit_code: Option<i32>,
+}
+
+/// Liveness of a tracked process
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessState {
+    /// The recorded PID is alive and still runs the tracked service
+    Running,
+    /// The recorded PID is no longer alive
+    Exited,
+    /// The recorded PID is alive but has been reused by an unrelated process
+    StalePid,
 }
 
 pub struct ProcessTracker {
@@ -38,21 +56,43 @@ impl ProcessTracker {
         Ok(records)
     }
 
+    /// Replaces the records, through a rename so that readers never see a partial file
     pub fn save(&self, records: &HashMap<String, ProcessRecord>) -> io::Result<()> {
         let data = serde_json::to_string_pretty(records).map_err(io::Error::other)?;
-        std::fs::write(&self.path, data)
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, data)?;
+        std::fs::rename(tmp_path, &self.path)
     }
 
-    pub fn add(&self, record: ProcessRecord) -> io::Result<()> {
+    /// Runs `f` on the records and saves them, holding an exclusive lock on
+    /// `processes.json.lock` so that the CLI commands and the supervisor, which update
+    /// the records from different processes, don't lose each other's changes
+    pub fn update<T>(
+        &self,
+        f: impl FnOnce(&mut HashMap<String, ProcessRecord>) -> T,
+    ) -> io::Result<T> {
+        let lock = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path.with_extension("json.lock"))?;
+        lock.lock()?;
         let mut records = self.load()?;
-        records.insert(record.service_name.clone(), record);
-        self.save(&records)
+        let result = f(&mut records);
+        self.save(&records)?;
+        Ok(result)
+    }
+
+    pub fn add(&self, record: ProcessRecord) -> io::Result<()> {
+        self.update(|records| {
+            records.insert(record.service_name.clone(), record);
+        })
     }
 
     pub fn remove(&self, service_name: &str) -> io::Result<()> {
-        let mut records = self.load()?;
-        records.remove(service_name);
-        self.save(&records)
+        self.update(|records| {
+            records.remove(service_name);
+        })
     }
 
     pub fn get(&self, service_name: &str) -> io::Result<Option<ProcessRecord>> {
@@ -67,4 +107,138 @@ impl ProcessTracker {
     pub fn is_alive(pid: u32) -> bool {
         signal::kill(Pid::from_raw(pid as i32), None).is_ok()
     }
+
+    /// Determine the state of a tracked process.
+    ///
+    /// Every native service is launched with `-config-directory <config_dir>`,
+    /// so a live PID whose command line no longer mentions the record's config
+    /// directory has been reused. Where `/proc` is unavailable a live PID is
+    /// assumed to be ours.
+    pub fn state(record: &ProcessRecord) -> ProcessState {
+        if !Self::is_alive(record.pid) {
+            return ProcessState::Exited;
+        }
+
+        match std::fs::read(format!("/proc/{}/cmdline", record.pid)) {
+            Ok(cmdline) => {
+                let cmdline = String::from_utf8_lossy(&cmdline);
+                if cmdline.contains(&*record.config_dir.to_string_lossy()) {
+                    ProcessState::Running
+                } else {
+                    ProcessState::StalePid
+                }
+            }
+            Err(_) => ProcessState::Running,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn record(pid: u32, config_dir: &Path) -> ProcessRecord {
+        ProcessRecord {
+            pid,
+            service_name: "mina-bp-1".to_string(),
+            started_at: chrono::Local::now().to_rfc3339(),
+            log_file: PathBuf::from("/tmp/mina-bp-1.log"),
+            config_dir: config_dir.to_path_buf(),
+            restart_count: 0,
+            last_exit_code: None,
+        }
+    }
+
+    #[test]
+    fn test_add_get_remove() {
+        let tempdir =
+            TempDir::new("test_add_get_remove").expect("Cannot create temporary directory");
+        let tracker = ProcessTracker::new(tempdir.path());
+        tracker.add(record(42, Path::new("/cfg"))).unwrap();
+
+        assert_eq!(tracker.get("mina-bp-1").unwrap().unwrap().pid, 42);
+        tracker.remove("mina-bp-1").unwrap();
+        assert!(tracker.get("mina-bp-1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_concurrent_updates() {
+        let tempdir =
+            TempDir::new("test_concurrent_updates").expect("Cannot create temporary directory");
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let network_path = tempdir.path().to_path_buf();
+                std::thread::spawn(move || {
+                    let tracker = ProcessTracker::new(&network_path);
+                    for j in 0..10 {
+                        let mut record = record(i * 100 + j, Path::new("/cfg"));
+                        record.service_name = format!("mina-bp-{i}-{j}");
+                        tracker.add(record).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(
+            ProcessTracker::new(tempdir.path()).list().unwrap().len(),
+            80
+        );
+    }
+
+    #[test]
+    fn test_state_running() {
+        let config_dir = "/tmp/minimina-test/config-directory/mina-bp-1";
+        let child = std::process::Command::new("sh")
+            .args(["-c", "sleep 30", config_dir])
+            .spawn()
+            .unwrap();
+        let child = ChildGuard(child);
+        let record = record(child.0.id(), Path::new(config_dir));
+
+        // the command line is only visible once the child has exec'd
+        let mut state = ProcessTracker::state(&record);
+        for _ in 0..50 {
+            if state == ProcessState::Running {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            state = ProcessTracker::state(&record);
+        }
+        assert_eq!(state, ProcessState::Running);
+    }
+
+    #[test]
+    fn test_state_stale_pid() {
+        // the test runner itself is alive but is not a mina service
+        let state = ProcessTracker::state(&record(
+            std::process::id(),
+            Path::new("/not/a/minimina/config-directory"),
+        ));
+        if Path::new("/proc").exists() {
+            assert_eq!(state, ProcessState::StalePid);
+        } else {
+            assert_eq!(state, ProcessState::Running);
+        }
+    }
+
+    #[test]
+    fn test_state_exited() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        let state = ProcessTracker::state(&record(pid, Path::new("/cfg")));
+        assert_eq!(state, ProcessState::Exited);
+    }
+
+    struct ChildGuard(std::process::Child);
+
+    impl Drop for ChildGuard {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
 }
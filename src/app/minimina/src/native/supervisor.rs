@@ -0,0 +1,539 @@
+//! # Supervisor Module
+//!
+//! Keeps the services of a native network alive. The supervisor spawns every
+//! service itself so it can reap them, and when one exits unexpectedly it:
+//! - records the exit code and restart count in `processes.json`,
+//! - rotates the service's log to `<service>.log.<n>`,
+//! - restarts it after an exponential backoff, up to a maximum number of restarts.
+//!
+//! Services that are stopped on purpose (`node stop` removes them from the
+//! tracker) are dropped from supervision instead of being restarted. Services
+//! restarted by another command (`node start`, `network upgrade`, chaos) get a new
+//! pid in the tracker: the supervisor adopts that process instead of starting a
+//! second copy, and restarts it from the current `services.json` when it exits.
+//!
+//! Interrupted with Ctrl-C (SIGINT), the supervisor stops its services and removes
+//! them from the tracker before exiting, so none is left recorded as running.
+
+use crate::directory_manager::SERVICES_JSON;
+use crate::native::manager::NativeManager;
+use crate::native::port_manager;
+use crate::native::process_tracker::{ProcessRecord, ProcessState, ProcessTracker};
+use crate::service::{ServiceConfig, ServiceType};
+use log::{error, info, warn};
+use nix::sys::signal::{self, SigHandler, Signal};
+use nix::unistd::Pid;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Result};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How often the supervisor polls its children
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Upper bound for the delay between two restarts of the same service
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How long services get to exit on SIGTERM before they are killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Set when the supervisor receives SIGINT
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: nix::libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// When and how often crashed services are restarted
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestartPolicy {
+    pub max_restarts: u32,
+    pub initial_backoff: Duration,
+}
+
+impl RestartPolicy {
+    pub fn new(max_restarts: u32, initial_backoff_secs: u64) -> Self {
+        RestartPolicy {
+            max_restarts,
+            initial_backoff: Duration::from_secs(initial_backoff_secs),
+        }
+    }
+
+    /// Delay before restart number `attempt` (0-based): the initial backoff
+    /// doubled for every previous attempt, capped at [`MAX_BACKOFF`]
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        self.initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(MAX_BACKOFF)
+            .min(MAX_BACKOFF)
+    }
+}
+
+/// Process of a supervised service
+enum Process {
+    /// Spawned by the supervisor, which reaps it
+    Child(Child),
+    /// Started by another command, watched through its pid
+    Adopted(ProcessRecord),
+}
+
+impl Process {
+    fn pid(&self) -> u32 {
+        match self {
+            Process::Child(child) => child.id(),
+            Process::Adopted(record) => record.pid,
+        }
+    }
+
+    /// Whether the process still runs, reaping it if it is a child
+    fn is_running(&mut self) -> bool {
+        match self {
+            Process::Child(child) => matches!(child.try_wait(), Ok(None)),
+            Process::Adopted(record) => ProcessTracker::state(record) == ProcessState::Running,
+        }
+    }
+}
+
+/// A supervised service and its current process
+struct Supervised {
+    service: ServiceConfig,
+    process: Option<Process>,
+    /// Pid of the last process of the service, running or not
+    pid: u32,
+    restart_count: u32,
+    restart_at: Option<Instant>,
+}
+
+pub struct Supervisor<'a> {
+    native: &'a NativeManager,
+    network_id: String,
+    policy: RestartPolicy,
+}
+
+impl<'a> Supervisor<'a> {
+    pub fn new(native: &'a NativeManager, network_id: &str, policy: RestartPolicy) -> Self {
+        Supervisor {
+            native,
+            network_id: network_id.to_string(),
+            policy,
+        }
+    }
+
+    /// Start all services and supervise them until none is left to supervise
+    pub fn run(&self, services: &[ServiceConfig]) -> Result<()> {
+        let ports = port_manager::collect_all_ports(services)?;
+        port_manager::check_ports_available(&ports)?;
+
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+        unsafe { signal::signal(Signal::SIGINT, SigHandler::Handler(on_interrupt)) }
+            .map_err(io::Error::from)?;
+
+        fs::write(
+            self.native.supervisor_pid_path(),
+            std::process::id().to_string(),
+        )?;
+
+        let mut supervised: HashMap<String, Supervised> = HashMap::new();
+        for service in services {
+            if service.service_type == ServiceType::UptimeServiceBackend {
+                warn!(
+                    "Skipping uptime service backend '{}' in native mode",
+                    service.service_name
+                );
+                continue;
+            }
+            let child = self.native.spawn_service(service, &self.network_id)?;
+            supervised.insert(
+                service.service_name.clone(),
+                Supervised {
+                    service: service.clone(),
+                    pid: child.id(),
+                    process: Some(Process::Child(child)),
+                    restart_count: 0,
+                    restart_at: None,
+                },
+            );
+        }
+
+        info!(
+            "Supervising {} services of network '{}'",
+            supervised.len(),
+            self.network_id
+        );
+
+        while !supervised.is_empty() {
+            std::thread::sleep(POLL_INTERVAL);
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                info!(
+                    "Interrupted, stopping the services of network '{}'",
+                    self.network_id
+                );
+                self.stop_all(supervised);
+                let _ = fs::remove_file(self.native.supervisor_pid_path());
+                return Ok(());
+            }
+            supervised.retain(|name, entry| self.poll(name, entry));
+        }
+
+        info!(
+            "No services left to supervise in network '{}'",
+            self.network_id
+        );
+        let _ = fs::remove_file(self.native.supervisor_pid_path());
+        Ok(())
+    }
+
+    /// Stops the supervised services, removing them from the tracker first so that
+    /// their records do not outlive them. Records of processes the supervisor does
+    /// not own, e.g. replaced meanwhile, are left alone.
+    fn stop_all(&self, supervised: HashMap<String, Supervised>) {
+        let tracker = ProcessTracker::new(&self.native.network_path);
+        let untracked = tracker.update(|records| {
+            records.retain(|name, record| {
+                supervised
+                    .get(name)
+                    .is_none_or(|entry| entry.pid != record.pid)
+            })
+        });
+        if let Err(e) = untracked {
+            error!("Failed to remove the services from the tracker: {e}");
+        }
+
+        let mut processes: Vec<Process> = supervised
+            .into_values()
+            .filter_map(|entry| entry.process)
+            .collect();
+        for process in &processes {
+            let _ = signal::kill(Pid::from_raw(process.pid() as i32), Signal::SIGTERM);
+        }
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while Instant::now() < deadline {
+            processes.retain_mut(|process| process.is_running());
+            if processes.is_empty() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        for mut process in processes {
+            let _ = signal::kill(Pid::from_raw(process.pid() as i32), Signal::SIGKILL);
+            if let Process::Child(child) = &mut process {
+                let _ = child.wait();
+            }
+        }
+    }
+
+    /// Check a single service, restarting it when due.
+    /// Returns `false` once the service should no longer be supervised.
+    fn poll(&self, name: &str, entry: &mut Supervised) -> bool {
+        match entry.process.as_mut() {
+            Some(Process::Child(child)) => match child.try_wait() {
+                Ok(None) => return true,
+                Ok(Some(status)) => {
+                    entry.process = None;
+                    return self.handle_exit(name, entry, exit_code(status));
+                }
+                Err(e) => {
+                    error!("Failed to poll service '{name}': {e}");
+                    return true;
+                }
+            },
+            Some(Process::Adopted(record)) => {
+                if ProcessTracker::state(record) == ProcessState::Running {
+                    return true;
+                }
+                // not our child: its exit code is unknown
+                entry.process = None;
+                return self.handle_exit(name, entry, -1);
+            }
+            None => {}
+        }
+
+        match entry.restart_at {
+            Some(restart_at) if Instant::now() >= restart_at => self.restart(name, entry),
+            _ => true,
+        }
+    }
+
+    /// Handles the exit of the process `entry.pid`. It crashed if it is still the
+    /// recorded process of the service, otherwise the service was stopped or restarted.
+    fn handle_exit(&self, name: &str, entry: &mut Supervised, exit_code: i32) -> bool {
+        let tracker = ProcessTracker::new(&self.native.network_path);
+        let restart_count = entry.restart_count;
+        let pid = entry.pid;
+        let recorded = tracker.update(|records| match records.get_mut(name) {
+            Some(record) if record.pid == pid => {
+                record.last_exit_code = Some(exit_code);
+                record.restart_count = restart_count;
+                Ok(())
+            }
+            record => Err(record.cloned()),
+        });
+        match recorded {
+            Ok(Ok(())) => {}
+            Ok(Err(record)) => return self.untracked(name, entry, record),
+            Err(e) => {
+                error!("Failed to record exit of '{name}': {e}");
+                return false;
+            }
+        }
+
+        if entry.restart_count >= self.policy.max_restarts {
+            error!(
+                "Service '{name}' exited with code {exit_code} and reached the \
+                 maximum of {} restarts, giving up",
+                self.policy.max_restarts
+            );
+            return false;
+        }
+
+        let backoff = self.policy.backoff(entry.restart_count);
+        warn!(
+            "Service '{name}' exited with code {exit_code}, restarting in {}s",
+            backoff.as_secs()
+        );
+        entry.restart_at = Some(Instant::now() + backoff);
+        true
+    }
+
+    /// Follows a service whose process is no longer `entry.pid` in the tracker:
+    /// drops it when stopped, adopts the process that replaced it otherwise
+    fn untracked(&self, name: &str, entry: &mut Supervised, record: Option<ProcessRecord>) -> bool {
+        match record {
+            // removed by `node stop`/`network stop`: it was meant to exit
+            None => {
+                info!("Service '{name}' was stopped, no longer supervising it");
+                false
+            }
+            Some(record) => {
+                info!(
+                    "Service '{name}' was restarted as pid {}, supervising the new process",
+                    record.pid
+                );
+                entry.restart_at = None;
+                entry.pid = record.pid;
+                entry.process = Some(Process::Adopted(record));
+                true
+            }
+        }
+    }
+
+    fn restart(&self, name: &str, entry: &mut Supervised) -> bool {
+        entry.restart_at = None;
+
+        // the service may have been stopped or restarted during the backoff
+        let tracker = ProcessTracker::new(&self.native.network_path);
+        match tracker.get(name) {
+            Ok(Some(record)) if record.pid == entry.pid => {}
+            Ok(record) => return self.untracked(name, entry, record),
+            Err(e) => {
+                error!("Failed to read process record of '{name}': {e}");
+                return false;
+            }
+        }
+
+        entry.restart_count += 1;
+        if let Err(e) = self.native.rotate_log(name, entry.restart_count) {
+            warn!("Failed to rotate log of '{name}': {e}");
+        }
+        // restarted with its current config, which `network upgrade` may have changed
+        match self.current_config(name) {
+            Ok(Some(service)) => entry.service = service,
+            Ok(None) => {}
+            Err(e) => warn!("Failed to reload the config of '{name}', using the last one: {e}"),
+        }
+
+        let last_exit_code = tracker
+            .get(name)
+            .ok()
+            .flatten()
+            .and_then(|record| record.last_exit_code);
+
+        match self.native.spawn_service(&entry.service, &self.network_id) {
+            Ok(child) => {
+                entry.pid = child.id();
+                entry.process = Some(Process::Child(child));
+                let restart_count = entry.restart_count;
+                let recorded = tracker.update(|records| {
+                    if let Some(record) = records.get_mut(name) {
+                        record.restart_count = restart_count;
+                        record.last_exit_code = last_exit_code;
+                    }
+                });
+                if let Err(e) = recorded {
+                    error!("Failed to record restart of '{name}': {e}");
+                }
+                info!(
+                    "Restarted service '{name}' (restart {}/{})",
+                    entry.restart_count, self.policy.max_restarts
+                );
+                true
+            }
+            Err(e) => {
+                error!("Failed to restart service '{name}': {e}");
+                if entry.restart_count >= self.policy.max_restarts {
+                    return false;
+                }
+                entry.restart_at = Some(Instant::now() + self.policy.backoff(entry.restart_count));
+                true
+            }
+        }
+    }
+
+    /// Config of service `name` in the network's `services.json`
+    fn current_config(&self, name: &str) -> Result<Option<ServiceConfig>> {
+        let services: Vec<ServiceConfig> = serde_json::from_str(&fs::read_to_string(
+            self.native.network_path.join(SERVICES_JSON),
+        )?)?;
+        Ok(services.into_iter().find(|s| s.service_name == name))
+    }
+}
+
+/// Exit code of a process, using the shell convention `128 + signal` for killed processes
+fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(-1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let policy = RestartPolicy::new(10, 2);
+        assert_eq!(policy.backoff(0), Duration::from_secs(2));
+        assert_eq!(policy.backoff(1), Duration::from_secs(4));
+        assert_eq!(policy.backoff(3), Duration::from_secs(16));
+        assert_eq!(policy.backoff(5), MAX_BACKOFF);
+        assert_eq!(policy.backoff(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let status = std::process::Command::new("sh")
+            .args(["-c", "exit 3"])
+            .status()
+            .unwrap();
+        assert_eq!(exit_code(status), 3);
+
+        let status = std::process::Command::new("sh")
+            .args(["-c", "kill -9 $$"])
+            .status()
+            .unwrap();
+        assert_eq!(exit_code(status), 137);
+    }
+
+    #[test]
+    fn test_exit_of_stopped_or_restarted_services() {
+        let tempdir = TempDir::new("test_supervisor").expect("Cannot create temporary directory");
+        let native = NativeManager::new(tempdir.path(), Path::new("/nonexistent/bin"));
+        let supervisor = Supervisor::new(&native, "test", RestartPolicy::new(3, 0));
+        let tracker = ProcessTracker::new(tempdir.path());
+        let record = |pid| ProcessRecord {
+            pid,
+            service_name: "mina-bp-1".to_string(),
+            started_at: String::new(),
+            log_file: native.log_file_path("mina-bp-1"),
+            config_dir: tempdir.path().join("mina-bp-1"),
+            restart_count: 0,
+            last_exit_code: None,
+        };
+        let mut entry = Supervised {
+            service: ServiceConfig {
+                service_name: "mina-bp-1".to_string(),
+                ..Default::default()
+            },
+            process: None,
+            pid: 42,
+            restart_count: 0,
+            restart_at: None,
+        };
+
+        // still the recorded process: a crash
+        tracker.add(record(42)).unwrap();
+        assert!(supervisor.handle_exit("mina-bp-1", &mut entry, 1));
+        assert!(entry.restart_at.is_some());
+        let recorded = tracker.get("mina-bp-1").unwrap().unwrap();
+        assert_eq!(recorded.last_exit_code, Some(1));
+
+        // stopped during the backoff: not restarted
+        tracker.remove("mina-bp-1").unwrap();
+        assert!(!supervisor.restart("mina-bp-1", &mut entry));
+        assert!(entry.process.is_none());
+
+        // restarted by another command: the new process is adopted
+        tracker.add(record(43)).unwrap();
+        entry.restart_at = Some(Instant::now());
+        assert!(supervisor.handle_exit("mina-bp-1", &mut entry, 0));
+        assert_eq!(entry.pid, 43);
+        assert!(entry.restart_at.is_none());
+        assert!(matches!(entry.process, Some(Process::Adopted(ref r)) if r.pid == 43));
+        assert_eq!(
+            tracker.get("mina-bp-1").unwrap().unwrap().last_exit_code,
+            None
+        );
+
+        // stopped
+        tracker.remove("mina-bp-1").unwrap();
+        assert!(!supervisor.handle_exit("mina-bp-1", &mut entry, 0));
+    }
+
+    #[test]
+    fn test_interrupt_stops_and_untracks_services() {
+        use crate::backend::{mock::service, Backend};
+        use std::os::unix::fs::PermissionsExt;
+
+        let tempdir =
+            TempDir::new("test_supervisor_interrupt").expect("Cannot create temporary directory");
+        let bin = tempdir.path().join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join("mina"), "#!/bin/sh\nexec sleep 30\n").unwrap();
+        fs::set_permissions(bin.join("mina"), fs::Permissions::from_mode(0o755)).unwrap();
+        let native = NativeManager::new(&tempdir.path().join("test"), &bin);
+        let services = [
+            service("mina-seed-1", ServiceType::Seed),
+            service("mina-bp-1", ServiceType::BlockProducer),
+        ];
+        Backend::create(&native, &services).unwrap();
+        let tracker = ProcessTracker::new(&native.network_path);
+
+        std::thread::scope(|scope| {
+            let supervisor = Supervisor::new(&native, "test", RestartPolicy::new(3, 0));
+            let running = scope.spawn(move || supervisor.run(&services));
+            while tracker.list().unwrap().len() < 2 {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            let pids: Vec<u32> = tracker.list().unwrap().values().map(|r| r.pid).collect();
+
+            signal::raise(Signal::SIGINT).unwrap();
+            running.join().unwrap().unwrap();
+
+            assert!(tracker.list().unwrap().is_empty());
+            assert!(pids.iter().all(|pid| !ProcessTracker::is_alive(*pid)));
+            assert!(!native.supervisor_pid_path().exists());
+        });
+    }
+
+    #[test]
+    fn test_rotate_log() {
+        let tempdir = TempDir::new("test_rotate_log").expect("Cannot create temporary directory");
+        let native = NativeManager::new(tempdir.path(), Path::new("/usr/bin"));
+        fs::create_dir_all(tempdir.path().join("logs")).unwrap();
+        fs::write(native.log_file_path("mina-bp-1"), "first run").unwrap();
+
+        native.rotate_log("mina-bp-1", 1).unwrap();
+
+        assert!(!native.log_file_path("mina-bp-1").exists());
+        let rotated = tempdir.path().join("logs").join("mina-bp-1.log.1");
+        assert_eq!(fs::read_to_string(rotated).unwrap(), "first run");
+    }
+}
@@ -7,6 +7,11 @@
 //! - `network`: Structures and implementations for serializing output related to various network operations like
 //!   creation, start, listing, stopping, and more.
 //! - `node`: Structures and implementations for serializing output concerning node information and various node-related actions.
+//! - `chaos`: Structures for serializing the summaries of chaos runs.
+//! - `load`: Structures for serializing the reports of load runs.
+//! - `scenario`: Structures for serializing the results of scenario runs.
+//! - `tx`: Structures for serializing the transactions sent.
+//! - `topology`: Structures for serializing the results of topology file checks.
 //! - `Error`: Represents an error structure to be serialized into JSON format with an accompanying error message.
 //!
 //! This module also offers utility functions such as `generate_network_info` and implements display
@@ -18,7 +23,12 @@ use std::collections::HashMap;
 pub mod network {
     use serde::{Deserialize, Serialize};
 
-    use crate::docker::manager::{ComposeInfo, ContainerInfo};
+    use crate::docker::manager::{ComposeInfo, ContainerInfo, ContainerState};
+    use crate::native::{
+        port_manager,
+        process_tracker::{ProcessRecord, ProcessState},
+    };
+    use crate::service::ServiceConfig;
 
     #[derive(Debug, Serialize, Deserialize, PartialEq)]
     pub struct Create {
@@ -74,6 +84,9 @@ pub mod network {
         pub network_dir: String,
         pub docker_compose_file: String,
         pub services: Vec<super::node::Status>,
+        /// Network faults active on the network, see `network partition`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub faults: Option<crate::faults::Faults>,
     }
 
     impl Status {
@@ -84,6 +97,7 @@ pub mod network {
                 network_dir: "unknown".to_string(),
                 docker_compose_file: "unknown".to_string(),
                 services: vec![],
+                faults: None,
             }
         }
 
@@ -124,15 +138,158 @@ pub mod network {
                     status,
                     // command,
                     docker_image,
+                    pid: None,
+                    uptime_secs: None,
+                    log_file: None,
+                    ports: vec![],
                 });
             });
         }
+
+        /// Build the status of a native network from its services and tracked processes
+        pub fn update_from_native(
+            &mut self,
+            services: &[ServiceConfig],
+            processes: Vec<(ProcessRecord, ProcessState)>,
+        ) -> std::io::Result<()> {
+            let now = chrono::Local::now();
+            let mut running = 0;
+
+            for service in services {
+                let process = processes
+                    .iter()
+                    .find(|(record, _)| record.service_name == service.service_name);
+
+                let (state, pid, uptime_secs, log_file) = match process {
+                    Some((record, ProcessState::Running)) => {
+                        running += 1;
+                        let uptime_secs = chrono::DateTime::parse_from_rfc3339(&record.started_at)
+                            .ok()
+                            .map(|started_at| {
+                                (now - started_at.with_timezone(&now.timezone())).num_seconds()
+                            });
+                        (
+                            ContainerState::Running,
+                            Some(record.pid),
+                            uptime_secs,
+                            Some(record.log_file.display().to_string()),
+                        )
+                    }
+                    Some((record, ProcessState::Exited)) => (
+                        ContainerState::Exited,
+                        Some(record.pid),
+                        None,
+                        Some(record.log_file.display().to_string()),
+                    ),
+                    Some((record, ProcessState::StalePid)) => (
+                        ContainerState::StalePid,
+                        Some(record.pid),
+                        None,
+                        Some(record.log_file.display().to_string()),
+                    ),
+                    None => (ContainerState::Exited, None, None, None),
+                };
+
+                self.services.push(super::node::Status {
+                    id: service.service_name.clone(),
+                    status: state.to_string(),
+                    state,
+                    docker_image: String::new(),
+                    pid,
+                    uptime_secs,
+                    log_file,
+                    ports: port_manager::service_ports(service)?,
+                });
+            }
+
+            self.status = if running > 0 {
+                format!("running({running})")
+            } else {
+                "not_running".to_string()
+            };
+            Ok(())
+        }
     }
 
     #[derive(Debug, Serialize, PartialEq)]
     pub struct Delete {
         pub network_id: String,
     }
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Snapshot {
+        pub network_id: String,
+        pub name: String,
+        pub file: String,
+    }
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Restore {
+        pub network_id: String,
+        pub name: String,
+    }
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Export {
+        pub network_id: String,
+        pub file: String,
+    }
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Wait {
+        pub network_id: String,
+        pub until: String,
+        /// Whether all nodes met the condition before the timeout
+        pub met: bool,
+        pub elapsed_secs: u64,
+        pub nodes: std::collections::BTreeMap<String, NodeTip>,
+    }
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Upgrade {
+        pub network_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub image: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub archive_image: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub bin_path: Option<String>,
+        /// Upgraded services, in the batches they were upgraded in
+        pub batches: Vec<Vec<String>>,
+    }
+
+    /// Network forked into a successor by `network fork`
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Fork {
+        pub network_id: String,
+        /// Node the fork point and ledgers were read from
+        pub node: String,
+        pub fork: crate::genesis_ledger::ForkConfig,
+        /// Number of accounts in the successor's genesis ledger
+        pub accounts: usize,
+        pub successor: Create,
+    }
+
+    /// Network faults active after `network partition` or `network heal`
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Faults {
+        pub network_id: String,
+        #[serde(flatten)]
+        pub faults: crate::faults::Faults,
+    }
+
+    /// Sync status and best tip of a node at its last poll
+    #[derive(Debug, Default, Clone, Serialize, PartialEq)]
+    pub struct NodeTip {
+        pub met: bool,
+        pub sync_status: Option<minimina::graphql_client::types::SyncStatus>,
+        pub height: Option<u64>,
+        pub epoch: Option<u64>,
+        /// Global slot since genesis
+        pub slot: Option<u64>,
+        /// Why the node could not be polled
+        pub error: Option<String>,
+    }
 }
 
 pub mod node {
@@ -156,6 +313,14 @@ pub mod node {
         pub status: String,
         // pub command: String,
         pub docker_image: String,
+        /// Process id (native mode only)
+        pub pid: Option<u32>,
+        /// Seconds since the process was started (native mode only)
+        pub uptime_secs: Option<i64>,
+        /// Path to the service's log file (native mode only)
+        pub log_file: Option<String>,
+        /// Ports bound by the service (native mode only)
+        pub ports: Vec<u16>,
     }
 
     #[derive(Debug, Serialize, PartialEq)]
@@ -200,33 +365,163 @@ pub mod node {
     }
 }
 
+pub mod scenario {
+    use serde::Serialize;
+
+    #[derive(Debug, Serialize, PartialEq, Clone, Copy)]
+    #[serde(rename_all = "lowercase")]
+    pub enum StepStatus {
+        Passed,
+        Failed,
+        /// Not run because an earlier step failed
+        Skipped,
+    }
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct StepResult {
+        pub step: String,
+        pub status: StepStatus,
+        pub duration_secs: f64,
+        pub message: Option<String>,
+    }
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Run {
+        pub scenario: String,
+        pub network_id: String,
+        /// `true` if every step passed
+        pub passed: bool,
+        pub steps: Vec<StepResult>,
+    }
+}
+
+pub mod chaos {
+    use serde::Serialize;
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Run {
+        pub network_id: String,
+        pub seed: u64,
+        /// Event file to replay the run from
+        pub events_file: String,
+        pub stops: usize,
+        pub starts: usize,
+        /// Starts with fresh state
+        pub fresh_starts: usize,
+        /// Actions which failed
+        pub errors: usize,
+    }
+}
+
+pub mod load {
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Report {
+        pub network_id: String,
+        pub target_tps: f64,
+        pub duration_secs: f64,
+        /// Transactions per second accepted by the nodes
+        pub achieved_tps: f64,
+        pub senders: usize,
+        pub nodes: Vec<String>,
+        pub submitted: usize,
+        /// Submitted transactions the nodes added to their pool
+        pub accepted: usize,
+        /// Accepted transactions seen in the best chain
+        pub included: usize,
+        pub payments: usize,
+        pub zkapp_commands: usize,
+        /// Time to submit a transaction, in milliseconds
+        pub submit_latency_ms: Option<Percentiles>,
+        /// Time from submission to inclusion, in seconds
+        pub inclusion_latency_secs: Option<Percentiles>,
+        /// Number of rejected transactions by error
+        pub errors: BTreeMap<String, usize>,
+    }
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Percentiles {
+        pub p50: f64,
+        pub p90: f64,
+        pub p99: f64,
+        pub max: f64,
+    }
+}
+
+pub mod tx {
+    use minimina::graphql_client::types::TransactionStatus;
+    use serde::Serialize;
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Sent {
+        pub network_id: String,
+        /// Node the transaction was sent through
+        pub node_id: String,
+        /// `payment` or `delegation`
+        pub kind: String,
+        pub id: String,
+        pub hash: String,
+        pub from: String,
+        pub to: String,
+        /// Amounts in mina
+        pub amount: Option<String>,
+        pub fee: String,
+        pub nonce: u64,
+        pub status: TransactionStatus,
+    }
+}
+
+pub mod topology {
+    use serde::Serialize;
+
+    use crate::topology_validation::Finding;
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub struct Validate {
+        pub file: String,
+        /// `true` if no finding is an error
+        pub valid: bool,
+        pub findings: Vec<Finding>,
+    }
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct Error {
     pub error_message: String,
 }
 
 impl ServiceConfig {
-    pub fn to_node_info(&self) -> node::Info {
-        node::Info {
-            graphql_uri: self
-                .client_port
-                .map(|port| format!("http://localhost:{}/graphql", port + 1)),
+    pub fn to_node_info(&self) -> std::io::Result<node::Info> {
+        let graphql_uri = match self.client_port {
+            Some(_) => Some(format!(
+                "http://localhost:{}/graphql",
+                self.daemon_ports()?.rest
+            )),
+            None => None,
+        };
+        Ok(node::Info {
+            graphql_uri,
             private_key: self.private_key.clone(),
             node_type: self.service_type.clone(),
-        }
+        })
     }
 }
 
-pub fn generate_network_info(services: &[ServiceConfig], network_id: &str) -> network::Create {
+pub fn generate_network_info(
+    services: &[ServiceConfig],
+    network_id: &str,
+) -> std::io::Result<network::Create> {
     let mut nodes: HashMap<String, node::Info> = HashMap::new();
     for service in services.iter() {
-        nodes.insert(service.service_name.clone(), service.to_node_info());
+        nodes.insert(service.service_name.clone(), service.to_node_info()?);
     }
 
-    network::Create {
+    Ok(network::Create {
         network_id: network_id.to_string(),
         nodes,
-    }
+    })
 }
 
 macro_rules! impl_display {
@@ -240,6 +535,11 @@ macro_rules! impl_display {
     };
 }
 
+impl_display!(scenario::Run);
+impl_display!(chaos::Run);
+impl_display!(load::Report);
+impl_display!(tx::Sent);
+impl_display!(topology::Validate);
 impl_display!(network::Create);
 impl_display!(network::Start);
 impl_display!(network::Stop);
@@ -247,6 +547,13 @@ impl_display!(network::Status);
 impl_display!(network::ListInfo);
 impl_display!(network::List);
 impl_display!(network::Delete);
+impl_display!(network::Snapshot);
+impl_display!(network::Restore);
+impl_display!(network::Export);
+impl_display!(network::Wait);
+impl_display!(network::Faults);
+impl_display!(network::Upgrade);
+impl_display!(network::Fork);
 impl_display!(node::Start);
 impl_display!(node::Stop);
 impl_display!(node::ArchiveData);
@@ -259,6 +566,7 @@ impl_display!(Error);
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::docker::manager::ContainerState;
 
     #[test]
     fn test_generate_network_info() {
@@ -310,6 +618,74 @@ mod tests {
                 .unwrap(),
             &serde_json::to_value("Block_producer").unwrap()
         );
-        assert_eq!(expect, generate_network_info(&services, network_id));
+        assert_eq!(
+            expect,
+            generate_network_info(&services, network_id).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_status_update_from_native() {
+        use crate::native::process_tracker::{ProcessRecord, ProcessState};
+        use std::path::PathBuf;
+
+        let services = vec![
+            ServiceConfig {
+                service_name: "mina-seed-1".to_string(),
+                service_type: ServiceType::Seed,
+                client_port: Some(3100),
+                ..Default::default()
+            },
+            ServiceConfig {
+                service_name: "mina-bp-1".to_string(),
+                client_port: Some(4000),
+                ..Default::default()
+            },
+            ServiceConfig {
+                service_name: "mina-bp-2".to_string(),
+                client_port: Some(4005),
+                ..Default::default()
+            },
+        ];
+        let record = |name: &str, pid: u32| ProcessRecord {
+            pid,
+            service_name: name.to_string(),
+            started_at: chrono::Local::now()
+                .checked_sub_signed(chrono::Duration::seconds(90))
+                .unwrap()
+                .to_rfc3339(),
+            log_file: PathBuf::from(format!("/logs/{name}.log")),
+            config_dir: PathBuf::from(format!("/config-directory/{name}")),
+            restart_count: 0,
+            last_exit_code: None,
+        };
+        let processes = vec![
+            (record("mina-seed-1", 100), ProcessState::Running),
+            (record("mina-bp-1", 101), ProcessState::StalePid),
+        ];
+
+        let mut status = network::Status::new("native");
+        status.update_from_native(&services, processes).unwrap();
+
+        assert_eq!(status.status, "running(1)");
+        assert_eq!(status.services.len(), 3);
+
+        let seed = &status.services[0];
+        assert_eq!(seed.state, ContainerState::Running);
+        assert_eq!(seed.pid, Some(100));
+        assert!(seed.uptime_secs.unwrap() >= 90);
+        assert_eq!(seed.log_file.as_deref(), Some("/logs/mina-seed-1.log"));
+        assert_eq!(seed.ports, vec![3100, 3101, 3102, 3103, 3104]);
+
+        let bp_1 = &status.services[1];
+        assert_eq!(bp_1.state, ContainerState::StalePid);
+        assert_eq!(bp_1.uptime_secs, None);
+
+        let bp_2 = &status.services[2];
+        assert_eq!(bp_2.state, ContainerState::Exited);
+        assert_eq!(bp_2.pid, None);
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["services"][1]["state"], "stale_pid");
     }
 }
@@ -0,0 +1,649 @@
+//! # Scenario Module
+//!
+//! A scenario is a YAML file with a list of steps run against one network:
+//!
+//! ```yaml
+//! name: payments
+//! network_id: scenario
+//! steps:
+//!   - create_network:
+//!       args: ["--topology", "topology.json"]
+//!   - start_network
+//!   - wait_for_height: { node: mina-bp-1, height: 3 }
+//!   - wait: { until: synced, timeout_secs: 300 }
+//!   - send_payment: { node: mina-bp-1, from: mina-bp-1, to: mina-bp-2, amount: 1.5mina, fee: 0.01mina }
+//! ```
+//!
+//! Steps run in order through the [`Backend`] of the network and the nodes' graphql endpoints.
+//! The first failing step fails the run, the remaining steps are skipped.
+//! Results are reported as [`scenario::Run`], or in the JUnit XML format with [`junit`].
+
+use crate::{
+    backend::Backend,
+    commands,
+    directory_manager::DirectoryManager,
+    faults::{Faults, Link},
+    graphql::GraphQl,
+    output::scenario::{self, StepResult, StepStatus},
+    tx,
+    wait::{self, Condition},
+};
+use log::info;
+use minimina::amounts::amount_dsl_to_nanomina;
+use serde::Deserialize;
+use std::{
+    fs,
+    io::{self, Result},
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default = "default_network_id")]
+    pub network_id: String,
+    #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+    pub steps: Vec<Step>,
+}
+
+fn default_network_id() -> String {
+    "default".to_string()
+}
+
+fn default_timeout_secs() -> u64 {
+    600
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    /// Run `network create` for the scenario's network with extra `args`
+    CreateNetwork {
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    StartNetwork,
+    StopNetwork,
+    DeleteNetwork,
+    StartNode {
+        node: String,
+        #[serde(default)]
+        fresh_state: bool,
+    },
+    StopNode {
+        node: String,
+    },
+    /// Wait until the best tip of `node` is at least `height` blocks long
+    WaitForHeight {
+        node: String,
+        height: u64,
+        #[serde(default = "default_timeout_secs")]
+        timeout_secs: u64,
+    },
+    /// Wait until `nodes`, or all nodes with a graphql endpoint, meet condition `until`,
+    /// see [`Condition`]
+    Wait {
+        until: Condition,
+        #[serde(default)]
+        nodes: Vec<String>,
+        #[serde(default = "default_timeout_secs")]
+        timeout_secs: u64,
+    },
+    /// Send a payment through `node` from account `from` to account or public key `to`.
+    /// Amounts use the amount DSL (`1.5mina`).
+    SendPayment {
+        node: String,
+        from: String,
+        to: String,
+        amount: String,
+        fee: String,
+    },
+    /// Check the value at JSON pointer `path` in the data of a graphql `query`,
+    /// or only that it is set without `equals`
+    AssertGraphql {
+        node: String,
+        query: String,
+        path: String,
+        equals: Option<serde_json::Value>,
+    },
+    /// Write the dump of an archive node's database to `output`
+    DumpArchiveData {
+        node: String,
+        output: PathBuf,
+    },
+    /// Replay the archive node's database, failing on errors the replayer logs
+    RunReplayer {
+        node: String,
+        #[serde(default)]
+        start_slot: u64,
+    },
+    /// Partition the services into `groups` and shape `links`, see `network partition`
+    Partition {
+        #[serde(default)]
+        groups: Vec<Vec<String>>,
+        #[serde(default)]
+        links: Vec<Link>,
+    },
+    /// Remove the partitions and link shaping
+    Heal,
+    Sleep {
+        secs: u64,
+    },
+}
+
+impl Scenario {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_yaml::from_str(&contents)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+}
+
+impl Step {
+    /// Short description of the step for reports
+    pub fn describe(&self) -> String {
+        match self {
+            Step::CreateNetwork { .. } => "create_network".to_string(),
+            Step::StartNetwork => "start_network".to_string(),
+            Step::StopNetwork => "stop_network".to_string(),
+            Step::DeleteNetwork => "delete_network".to_string(),
+            Step::StartNode { node, .. } => format!("start_node {node}"),
+            Step::StopNode { node } => format!("stop_node {node}"),
+            Step::WaitForHeight { node, height, .. } => {
+                format!("wait_for_height {node} {height}")
+            }
+            Step::Wait { until, .. } => format!("wait {until}"),
+            Step::SendPayment {
+                from, to, amount, ..
+            } => format!("send_payment {amount} from {from} to {to}"),
+            Step::AssertGraphql { node, path, .. } => format!("assert_graphql {node} {path}"),
+            Step::DumpArchiveData { node, .. } => format!("dump_archive_data {node}"),
+            Step::RunReplayer { node, .. } => format!("run_replayer {node}"),
+            Step::Partition { groups, links } => {
+                let groups = groups.iter().map(|group| group.join(","));
+                let mut parts = vec![groups.collect::<Vec<_>>().join("|")];
+                parts.extend(links.iter().map(Link::to_string));
+                parts.retain(|part| !part.is_empty());
+                format!("partition {}", parts.join(" "))
+            }
+            Step::Heal => "heal".to_string(),
+            Step::Sleep { secs } => format!("sleep {secs}"),
+        }
+    }
+}
+
+/// Runs the steps of scenarios against network `network_id`
+pub struct Runner<'a> {
+    pub backend: &'a dyn Backend,
+    pub directory_manager: &'a DirectoryManager,
+    pub graphql: GraphQl,
+    pub network_id: &'a str,
+    /// Global arguments of this run, like `--mode`, passed on to `network create`
+    pub global_args: Vec<String>,
+}
+
+impl Runner<'_> {
+    pub fn run(&self, scenario: &Scenario) -> scenario::Run {
+        info!(
+            "Running scenario '{}' on network '{}'.",
+            scenario.name, self.network_id
+        );
+        let mut failed = false;
+        let mut steps = vec![];
+        for step in &scenario.steps {
+            if failed {
+                steps.push(StepResult {
+                    step: step.describe(),
+                    status: StepStatus::Skipped,
+                    duration_secs: 0.0,
+                    message: None,
+                });
+                continue;
+            }
+
+            info!("Step: {}", step.describe());
+            let start = Instant::now();
+            let result = self.run_step(step);
+            let (status, message) = match result {
+                Ok(message) => (StepStatus::Passed, message),
+                Err(e) => {
+                    failed = true;
+                    (StepStatus::Failed, Some(e.to_string()))
+                }
+            };
+            steps.push(StepResult {
+                step: step.describe(),
+                status,
+                duration_secs: start.elapsed().as_secs_f64(),
+                message,
+            });
+        }
+
+        scenario::Run {
+            scenario: scenario.name.clone(),
+            network_id: self.network_id.to_string(),
+            passed: !failed,
+            steps,
+        }
+    }
+
+    fn run_step(&self, step: &Step) -> Result<Option<String>> {
+        let (backend, directory_manager, network_id) =
+            (self.backend, self.directory_manager, self.network_id);
+        match step {
+            Step::CreateNetwork { args } => self.create_network(args),
+            Step::StartNetwork => {
+                commands::start_network(backend, directory_manager, network_id).map(|_| None)
+            }
+            Step::StopNetwork => {
+                commands::stop_network(backend, directory_manager, network_id).map(|_| None)
+            }
+            Step::DeleteNetwork => {
+                commands::delete_network(backend, directory_manager, network_id).map(|_| None)
+            }
+            Step::StartNode { node, fresh_state } => commands::start_node(
+                backend,
+                directory_manager,
+                node,
+                network_id,
+                *fresh_state,
+                false,
+            )
+            .map(|_| None),
+            Step::StopNode { node } => {
+                commands::stop_node(backend, directory_manager, node, network_id).map(|_| None)
+            }
+            Step::WaitForHeight {
+                node,
+                height,
+                timeout_secs,
+            } => self.wait(
+                std::slice::from_ref(node),
+                &Condition::Height(*height),
+                Duration::from_secs(*timeout_secs),
+            ),
+            Step::Wait {
+                until,
+                nodes,
+                timeout_secs,
+            } => self.wait(nodes, until, Duration::from_secs(*timeout_secs)),
+            Step::SendPayment {
+                node,
+                from,
+                to,
+                amount,
+                fee,
+            } => self.send_payment(node, from, to, amount, fee),
+            Step::AssertGraphql {
+                node,
+                query,
+                path,
+                equals,
+            } => self.assert_graphql(node, query, path, equals.as_ref()),
+            Step::DumpArchiveData { node, output } => {
+                let data =
+                    commands::dump_archive_data(backend, directory_manager, node, network_id)?;
+                fs::write(output, data)?;
+                Ok(Some(format!(
+                    "Archive data written to '{}'",
+                    output.display()
+                )))
+            }
+            Step::RunReplayer { node, start_slot } => {
+                let logs = commands::run_replayer(
+                    backend,
+                    directory_manager,
+                    node,
+                    network_id,
+                    *start_slot,
+                )?;
+                match logs
+                    .lines()
+                    .find(|line| line.contains(r#""level":"Error""#))
+                {
+                    Some(error) => Err(io::Error::other(format!("Replayer error: {error}"))),
+                    None => Ok(None),
+                }
+            }
+            Step::Partition { groups, links } => {
+                let faults = Faults {
+                    groups: groups.clone(),
+                    links: links.clone(),
+                };
+                commands::partition_network(backend, directory_manager, network_id, &faults)
+                    .map(|_| None)
+            }
+            Step::Heal => {
+                commands::heal_network(backend, directory_manager, network_id).map(|_| None)
+            }
+            Step::Sleep { secs } => {
+                thread::sleep(Duration::from_secs(*secs));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Creating a network exits on errors, so it runs as a separate `minimina` process
+    fn create_network(&self, args: &[String]) -> Result<Option<String>> {
+        let output = Command::new(std::env::current_exe()?)
+            .args(&self.global_args)
+            .args(["network", "create", "--network-id", self.network_id])
+            .args(args)
+            .output()?;
+        if output.status.success() {
+            Ok(None)
+        } else {
+            Err(io::Error::other(format!(
+                "network create failed: {}",
+                String::from_utf8_lossy(&output.stdout).trim()
+            )))
+        }
+    }
+
+    fn wait(
+        &self,
+        nodes: &[String],
+        until: &Condition,
+        timeout: Duration,
+    ) -> Result<Option<String>> {
+        let report = wait::wait(&self.graphql, self.network_id, nodes, until, timeout)?;
+        if report.met {
+            return Ok(Some(format!("Met {until} after {}s", report.elapsed_secs)));
+        }
+        let waiting: Vec<String> = report
+            .nodes
+            .iter()
+            .filter(|(_, tip)| !tip.met)
+            .map(|(node, tip)| match &tip.error {
+                Some(error) => format!("{node} ({error})"),
+                None => format!("{node} (height {:?}, {:?})", tip.height, tip.sync_status),
+            })
+            .collect();
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "{until} not met within {}s by {}",
+                timeout.as_secs(),
+                waiting.join(", ")
+            ),
+        ))
+    }
+
+    fn send_payment(
+        &self,
+        node: &str,
+        from: &str,
+        to: &str,
+        amount: &str,
+        fee: &str,
+    ) -> Result<Option<String>> {
+        let invalid = |e: minimina::amounts::AmountParseError| {
+            io::Error::new(io::ErrorKind::InvalidInput, e.to_string())
+        };
+        let transaction = tx::Transaction {
+            kind: tx::Kind::Payment {
+                amount: amount_dsl_to_nanomina(amount).map_err(invalid)?,
+            },
+            from: from.to_string(),
+            to: to.to_string(),
+            fee: amount_dsl_to_nanomina(fee).map_err(invalid)?,
+            memo: None,
+        };
+        let sender = tx::Sender {
+            backend: self.backend,
+            directory_manager: self.directory_manager,
+            graphql: &self.graphql,
+            network_id: self.network_id,
+        };
+        let sent = sender.send(Some(node), &transaction, None)?;
+        Ok(Some(format!("Payment {}", sent.hash)))
+    }
+
+    fn assert_graphql(
+        &self,
+        node: &str,
+        query: &str,
+        path: &str,
+        equals: Option<&serde_json::Value>,
+    ) -> Result<Option<String>> {
+        let data = self.graphql.client(node, self.network_id)?.query(query)?;
+        check_value(&data, path, equals).map_err(io::Error::other)?;
+        Ok(None)
+    }
+}
+
+/// Checks the value at JSON pointer `path` in `data` against `equals`, or that it is set
+fn check_value(
+    data: &serde_json::Value,
+    path: &str,
+    equals: Option<&serde_json::Value>,
+) -> std::result::Result<(), String> {
+    match (data.pointer(path), equals) {
+        (None | Some(serde_json::Value::Null), None) => Err(format!("'{path}' is not set")),
+        (value, Some(expected)) if value != Some(expected) => Err(format!(
+            "'{path}' is {}, expected {expected}",
+            value.unwrap_or(&serde_json::Value::Null)
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Renders the results of a run as a JUnit XML test suite, with one test case per step
+pub fn junit(run: &scenario::Run) -> String {
+    let count = |status| run.steps.iter().filter(|s| s.status == status).count();
+    let time: f64 = run.steps.iter().map(|s| s.duration_secs).sum();
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{time:.3}\">\n",
+        xml_escape(&run.scenario),
+        run.steps.len(),
+        count(StepStatus::Failed),
+        count(StepStatus::Skipped),
+    );
+    for (i, step) in run.steps.iter().enumerate() {
+        let name = xml_escape(&format!("{:02} {}", i + 1, step.step));
+        let classname = xml_escape(&run.scenario);
+        let time = step.duration_secs;
+        match step.status {
+            StepStatus::Passed => xml.push_str(&format!(
+                "  <testcase name=\"{name}\" classname=\"{classname}\" time=\"{time:.3}\"/>\n"
+            )),
+            StepStatus::Failed => xml.push_str(&format!(
+                "  <testcase name=\"{name}\" classname=\"{classname}\" time=\"{time:.3}\">\n    \
+                 <failure message=\"{}\"/>\n  </testcase>\n",
+                xml_escape(step.message.as_deref().unwrap_or_default())
+            )),
+            StepStatus::Skipped => xml.push_str(&format!(
+                "  <testcase name=\"{name}\" classname=\"{classname}\" time=\"{time:.3}\">\n    \
+                 <skipped/>\n  </testcase>\n"
+            )),
+        }
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::mock::MockBackend,
+        docker::manager::ContainerState,
+        service::{ServiceConfig, ServiceType},
+    };
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_parse_scenario() {
+        let scenario = Scenario::from_file(Path::new("./tests/data/scenario/payments.yaml"))
+            .expect("Failed to parse scenario");
+        assert_eq!(scenario.name, "payments");
+        assert_eq!(scenario.network_id, "scenario");
+        assert_eq!(
+            scenario.steps[0],
+            Step::CreateNetwork {
+                args: vec!["--ledger-spec".into(), "ledger_spec.json".into()]
+            }
+        );
+        assert_eq!(scenario.steps[1], Step::StartNetwork);
+        assert_eq!(
+            scenario.steps[2],
+            Step::WaitForHeight {
+                node: "mina-bp-1".into(),
+                height: 3,
+                timeout_secs: 900,
+            }
+        );
+        assert_eq!(
+            scenario.steps[4],
+            Step::AssertGraphql {
+                node: "mina-seed-1".into(),
+                query: "{ daemonStatus { syncStatus } }".into(),
+                path: "/daemonStatus/syncStatus".into(),
+                equals: Some(serde_json::json!("SYNCED")),
+            }
+        );
+        assert!(scenario.steps.contains(&Step::Wait {
+            until: Condition::Synced,
+            nodes: vec![],
+            timeout_secs: 300,
+        }));
+        assert_eq!(scenario.steps.last(), Some(&Step::StopNetwork));
+
+        let err =
+            serde_yaml::from_str::<Scenario>("name: x\nsteps:\n  - wait: { until: height }\n");
+        assert!(err.is_err());
+
+        let err = serde_yaml::from_str::<Scenario>("name: x\nsteps:\n  - reboot_network\n");
+        assert!(err.is_err());
+
+        let faults = serde_yaml::from_str::<Scenario>(
+            "name: x\nsteps:\n  - partition:\n      groups: [[mina-bp-1], [mina-bp-2]]\n      \
+             links: [{ a: mina-bp-1, b: mina-seed-1, latency_ms: 200 }]\n  - heal\n",
+        )
+        .expect("Failed to parse faults");
+        assert_eq!(
+            faults.steps[0].describe(),
+            "partition mina-bp-1|mina-bp-2 mina-bp-1:mina-seed-1,latency=200ms"
+        );
+        assert_eq!(faults.steps[1], Step::Heal);
+    }
+
+    #[test]
+    fn test_run_scenario() {
+        let tempdir = TempDir::new("test_run_scenario").expect("Cannot create temporary directory");
+        let directory_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        directory_manager.generate_dir_structure("test").unwrap();
+        let services = vec![ServiceConfig {
+            service_name: "mina-bp-1".to_string(),
+            service_type: ServiceType::BlockProducer,
+            ..Default::default()
+        }];
+        directory_manager
+            .save_services_info("test", &services)
+            .unwrap();
+        let backend = MockBackend::with_state("mina-bp-1", ContainerState::Exited);
+        let runner = Runner {
+            backend: &backend,
+            directory_manager: &directory_manager,
+            graphql: GraphQl::new(directory_manager.clone()),
+            network_id: "test",
+            global_args: vec![],
+        };
+
+        let scenario = Scenario {
+            name: "restart".to_string(),
+            network_id: "test".to_string(),
+            steps: vec![
+                Step::StartNetwork,
+                Step::StopNode {
+                    node: "mina-bp-1".into(),
+                },
+                Step::StartNode {
+                    node: "mina-bp-1".into(),
+                    fresh_state: false,
+                },
+                Step::StartNode {
+                    node: "mina-bp-2".into(),
+                    fresh_state: false,
+                },
+                Step::StopNetwork,
+            ],
+        };
+        let run = runner.run(&scenario);
+
+        assert!(!run.passed);
+        let statuses: Vec<StepStatus> = run.steps.iter().map(|s| s.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                StepStatus::Passed,
+                StepStatus::Passed,
+                StepStatus::Passed,
+                StepStatus::Failed,
+                StepStatus::Skipped
+            ]
+        );
+        assert_eq!(
+            run.steps[3].message.as_deref(),
+            Some("Node 'mina-bp-2' does not exist in network 'test'.")
+        );
+        assert_eq!(
+            backend.calls(),
+            [
+                "start_all 1",
+                "stop_service mina-bp-1",
+                "start_service mina-bp-1"
+            ]
+        );
+
+        let xml = junit(&run);
+        assert!(xml.contains(r#"<testsuite name="restart" tests="5" failures="1" skipped="1""#));
+        assert!(xml.contains(r#"<failure message="Node &apos;mina-bp-2&apos; does not exist"#));
+        assert!(xml.contains(r#"<testcase name="05 stop_network" classname="restart""#));
+
+        // stopping a node that does not exist fails the step too
+        let scenario = Scenario {
+            name: "typo".to_string(),
+            network_id: "test".to_string(),
+            steps: vec![Step::StopNode {
+                node: "mina-bp-9".into(),
+            }],
+        };
+        let run = runner.run(&scenario);
+        assert_eq!(run.steps[0].status, StepStatus::Failed);
+        assert_eq!(
+            run.steps[0].message.as_deref(),
+            Some("Node 'mina-bp-9' does not exist in network 'test'.")
+        );
+        assert_eq!(backend.calls().len(), 3);
+    }
+
+    #[test]
+    fn test_check_value() {
+        let data = serde_json::json!({"daemonStatus": {"syncStatus": "SYNCED", "peers": null}});
+        let synced = serde_json::json!("SYNCED");
+        assert!(check_value(&data, "/daemonStatus/syncStatus", Some(&synced)).is_ok());
+        assert!(check_value(&data, "/daemonStatus/syncStatus", None).is_ok());
+        assert_eq!(
+            check_value(&data, "/daemonStatus/peers", None),
+            Err("'/daemonStatus/peers' is not set".to_string())
+        );
+        assert_eq!(
+            check_value(&data, "/daemonStatus/chainId", Some(&synced)),
+            Err(r#"'/daemonStatus/chainId' is null, expected "SYNCED""#.to_string())
+        );
+    }
+}
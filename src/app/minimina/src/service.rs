@@ -3,12 +3,18 @@
 //! This module provides structures and methods to hold and manage configurations for different Mina daemons.
 //! With these configurations, docker-compose files can be dynamically generated to deploy and manage nodes in the network.
 
+use clap::ValueEnum;
 use log::warn;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    docker::compose::CONFIG_DIRECTORY, genesis_ledger::GENESIS_LEDGER_JSON, topology::GitBuild,
+    backend::ARCHIVE_DB, docker::compose::CONFIG_DIRECTORY, genesis_ledger::GENESIS_LEDGER_JSON,
+    topology::GitBuild,
 };
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
@@ -28,13 +34,91 @@ pub enum ServiceType {
     UptimeServiceBackend,
 }
 
+/// Role of a mina daemon service, to select services on the command line
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    #[value(alias = "block_producer")]
+    BlockProducer,
+    #[value(alias = "snark_worker")]
+    SnarkWorker,
+    #[value(alias = "snark_coordinator")]
+    SnarkCoordinator,
+    Seed,
+    #[value(alias = "archive_node")]
+    ArchiveNode,
+}
+
+impl Role {
+    pub fn service_type(&self) -> ServiceType {
+        match self {
+            Role::BlockProducer => ServiceType::BlockProducer,
+            Role::SnarkWorker => ServiceType::SnarkWorker,
+            Role::SnarkCoordinator => ServiceType::SnarkCoordinator,
+            Role::Seed => ServiceType::Seed,
+            Role::ArchiveNode => ServiceType::ArchiveNode,
+        }
+    }
+}
+
+/// Names of the `services` given as `nodes`, or else of those with one of `roles`, every
+/// mina daemon when `roles` is empty too. Names are in the order of `services`, and at least
+/// one is selected.
+pub fn select_services(
+    services: &[ServiceConfig],
+    roles: &[Role],
+    nodes: &[String],
+) -> io::Result<Vec<String>> {
+    if let Some(unknown) = nodes
+        .iter()
+        .find(|node| !services.iter().any(|s| &s.service_name == *node))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Node '{unknown}' does not exist"),
+        ));
+    }
+    let selected: Vec<String> = services
+        .iter()
+        .filter(|service| {
+            if !nodes.is_empty() {
+                return nodes.contains(&service.service_name);
+            }
+            match roles.is_empty() {
+                true => service.service_type != ServiceType::UptimeServiceBackend,
+                false => roles
+                    .iter()
+                    .any(|role| role.service_type() == service.service_type),
+            }
+        })
+        .map(|service| service.service_name.clone())
+        .collect();
+    if selected.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No node with the given roles",
+        ));
+    }
+    Ok(selected)
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ServiceConfig {
     pub service_type: ServiceType,
     pub service_name: String,
     pub docker_image: Option<String>,
     pub git_build: Option<GitBuild>,
+    /// Native mode only: directory of the mina binaries of this service, `--bin-path` by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bin_path: Option<PathBuf>,
     pub client_port: Option<u16>,
+    /// Explicit daemon ports, derived from `client_port` when not set (see [`ServiceConfig::daemon_ports`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rest_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_port: Option<u16>,
     pub public_key: Option<String>,
     pub public_key_path: Option<String>,
     pub private_key: Option<String>,
@@ -48,6 +132,11 @@ pub struct ServiceConfig {
     /// Path to the file used by `mina daemon --peer-list-file PATH ...`
     pub peer_list_file: Option<PathBuf>,
 
+    //block producer specific
+    /// Name of the uptime service backend to submit to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime_service_backend: Option<String>,
+
     //snark coordinator specific
     pub snark_coordinator_fees: Option<String>,
     pub worker_nodes: Option<u16>,
@@ -62,14 +151,88 @@ pub struct ServiceConfig {
     pub archive_docker_image: Option<String>,
     pub archive_schema_files: Option<Vec<String>>,
     pub archive_port: Option<u16>,
+    /// Name of the archive node's database, set when the network is created
+    /// (see [`ServiceConfig::name_archive_dbs`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_db: Option<String>,
 
     //uptime service backend specific
     pub uptime_service_backend_app_config: Option<PathBuf>,
     pub uptime_service_backend_minasheets: Option<PathBuf>,
     pub uptime_service_other_config_files: Option<Vec<PathBuf>>,
+    /// Host port the backend is published on, it listens on [`UPTIME_SERVICE_PORT`] in its container
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime_service_port: Option<u16>,
+}
+
+/// Port an uptime service backend listens on in its container
+pub const UPTIME_SERVICE_PORT: u16 = 8080;
+/// Highest port a service may set, daemon ports are derived up to 4 above it (libp2p metrics)
+pub const MAX_PORT: u16 = u16::MAX - 4;
+
+/// Ports bound by a mina daemon
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DaemonPorts {
+    pub client: u16,
+    pub rest: u16,
+    pub external: u16,
+    pub metrics: u16,
+    pub libp2p_metrics: u16,
+}
+
+impl DaemonPorts {
+    pub fn all(&self) -> [u16; 5] {
+        [
+            self.client,
+            self.rest,
+            self.external,
+            self.metrics,
+            self.libp2p_metrics,
+        ]
+    }
 }
 
 impl ServiceConfig {
+    /// Daemon ports of this service.
+    /// Ports which are not set explicitly follow `client_port` (default 3100):
+    /// rest is +1, external +2, metrics +3 and libp2p metrics is metrics +1.
+    /// Fails if a derived port is above `u16::MAX`.
+    pub fn daemon_ports(&self) -> io::Result<DaemonPorts> {
+        let add = |port: u16, offset: u16| self.add_port(port, offset);
+        let client = self.client_port.unwrap_or(3100);
+        let metrics = match self.metrics_port {
+            Some(metrics) => metrics,
+            None => add(client, 3)?,
+        };
+        Ok(DaemonPorts {
+            client,
+            rest: match self.rest_port {
+                Some(rest) => rest,
+                None => add(client, 1)?,
+            },
+            external: match self.external_port {
+                Some(external) => external,
+                None => add(client, 2)?,
+            },
+            metrics,
+            libp2p_metrics: add(metrics, 1)?,
+        })
+    }
+
+    /// `port + offset`, failing if it is above `u16::MAX`
+    pub fn add_port(&self, port: u16, offset: u16) -> io::Result<u16> {
+        port.checked_add(offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Port {port} + {offset} of '{}' is above {}",
+                    self.service_name,
+                    u16::MAX
+                ),
+            )
+        })
+    }
+
     pub fn generate_peer(
         seed_name: &str,
         network_name: &str,
@@ -84,26 +247,22 @@ impl ServiceConfig {
     }
 
     /// Generate base daemon command common for most mina services
-    pub fn generate_base_command(&self) -> Vec<String> {
-        let client_port = self.client_port.unwrap_or(3100);
-        let rest_port = client_port + 1;
-        let external_port = rest_port + 1;
-        let metrics_port = external_port + 1;
-        let libp2p_metrics_port = metrics_port + 1;
-
-        vec![
+    pub fn generate_base_command(&self) -> io::Result<Vec<String>> {
+        let ports = self.daemon_ports()?;
+
+        Ok(vec![
             "daemon".to_string(),
             "-client-port".to_string(),
-            client_port.to_string(),
+            ports.client.to_string(),
             "-rest-port".to_string(),
-            rest_port.to_string(),
+            ports.rest.to_string(),
             "-insecure-rest-server".to_string(),
             "-external-port".to_string(),
-            external_port.to_string(),
+            ports.external.to_string(),
             "-metrics-port".to_string(),
-            metrics_port.to_string(),
+            ports.metrics.to_string(),
             "-libp2p-metrics-port".to_string(),
-            libp2p_metrics_port.to_string(),
+            ports.libp2p_metrics.to_string(),
             "-config-file".to_string(),
             format!("/local-network/{GENESIS_LEDGER_JSON}"),
             "-log-json".to_string(),
@@ -123,23 +282,23 @@ impl ServiceConfig {
             "true".to_string(),
             "-proof-level".to_string(),
             "full".to_string(),
-        ]
+        ])
     }
 
     /// Generate command for seed node
-    pub fn generate_seed_command(&self) -> String {
+    pub fn generate_seed_command(&self) -> io::Result<String> {
         assert_eq!(self.service_type, ServiceType::Seed);
 
-        let mut base_command = self.generate_base_command();
+        let mut base_command = self.generate_base_command()?;
         base_command.push("-seed".to_string());
 
         self.add_libp2p_command(&mut base_command);
-        base_command.join(" ")
+        Ok(base_command.join(" "))
     }
 
-    pub fn generate_archive_command(&self, archive_service_host: String) -> String {
+    pub fn generate_archive_command(&self, archive_service_host: String) -> io::Result<String> {
         assert_eq!(self.service_type, ServiceType::ArchiveNode);
-        let mut base_command = self.generate_base_command();
+        let mut base_command = self.generate_base_command()?;
 
         // Handling multiple peers
         self.add_peers_command(&mut base_command);
@@ -155,24 +314,26 @@ impl ServiceConfig {
         }
 
         self.add_libp2p_command(&mut base_command);
-        base_command.join(" ")
+        Ok(base_command.join(" "))
     }
 
     /// Generate command for block producer node
     pub fn generate_block_producer_command(
         &self,
         uptime_service_hostname: Option<String>,
-    ) -> String {
+    ) -> io::Result<String> {
         assert_eq!(self.service_type, ServiceType::BlockProducer);
 
-        let mut base_command = self.generate_base_command();
+        let mut base_command = self.generate_base_command()?;
 
         // Handling multiple peers
         self.add_peers_command(&mut base_command);
 
         if let Some(uptime_service_host) = &uptime_service_hostname {
             base_command.push("-uptime-url".to_string());
-            base_command.push(format!("http://{}:8080/v1/submit", uptime_service_host));
+            base_command.push(format!(
+                "http://{uptime_service_host}:{UPTIME_SERVICE_PORT}/v1/submit"
+            ));
         }
 
         if self.private_key_path.is_some() {
@@ -203,14 +364,14 @@ impl ServiceConfig {
         }
 
         self.add_libp2p_command(&mut base_command);
-        base_command.join(" ")
+        Ok(base_command.join(" "))
     }
 
     /// Generate command for snark coordinator node
-    pub fn generate_snark_coordinator_command(&self) -> String {
+    pub fn generate_snark_coordinator_command(&self) -> io::Result<String> {
         assert_eq!(self.service_type, ServiceType::SnarkCoordinator);
 
-        let mut base_command = self.generate_base_command();
+        let mut base_command = self.generate_base_command()?;
 
         base_command.push("-work-selection".to_string());
         base_command.push("seq".to_string());
@@ -238,7 +399,7 @@ impl ServiceConfig {
         }
 
         self.add_libp2p_command(&mut base_command);
-        base_command.join(" ")
+        Ok(base_command.join(" "))
     }
 
     /// Generate command for snark worker node
@@ -320,31 +481,295 @@ impl ServiceConfig {
             .collect()
     }
 
-    pub fn get_archive_node(services: &[Self]) -> Option<&Self> {
-        let mut archive_nodes = services
+    pub fn get_archive_nodes(services: &[Self]) -> Vec<&Self> {
+        services
             .iter()
-            .filter(|s| s.service_type == ServiceType::ArchiveNode);
+            .filter(|s| s.service_type == ServiceType::ArchiveNode)
+            .collect()
+    }
 
-        let first_node = archive_nodes.next();
+    /// Name of this archive node's database. Networks created before archive nodes had a
+    /// database each have a single archive node, whose database is [`ARCHIVE_DB`].
+    pub fn archive_db(&self) -> String {
+        self.archive_db
+            .clone()
+            .unwrap_or_else(|| ARCHIVE_DB.to_string())
+    }
 
-        if archive_nodes.next().is_some() {
-            panic!("There can only be one archive node in topology");
+    /// Gives each archive node of network `network_id` its own database, e.g.
+    /// `archive_test_mina_archive` for `mina-archive` of `test`. Names are scoped to the
+    /// network, as native networks share the local postgres.
+    pub fn name_archive_dbs(services: &mut [Self], network_id: &str) {
+        let identifier = |name: &str| name.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+        for service in services
+            .iter_mut()
+            .filter(|s| s.service_type == ServiceType::ArchiveNode)
+        {
+            service.archive_db = Some(format!(
+                "{ARCHIVE_DB}_{}_{}",
+                identifier(network_id),
+                identifier(&service.service_name)
+            ));
         }
+    }
 
-        first_node
+    pub fn get_uptime_service_backends(services: &[Self]) -> Vec<&Self> {
+        services
+            .iter()
+            .filter(|s| s.service_type == ServiceType::UptimeServiceBackend)
+            .collect()
     }
 
-    pub fn get_uptime_service_backend(services: &[Self]) -> Option<&Self> {
-        let mut uptime_service_backends = services
+    /// Moves every port bound on the host by `offset`, derived daemon ports included.
+    /// Snark workers follow their coordinator's port.
+    /// Fails if a shifted port is above [`MAX_PORT`].
+    pub fn shift_ports(&mut self, offset: u16) -> io::Result<()> {
+        let mut shifted = self.clone();
+        for port in [
+            &mut shifted.client_port,
+            &mut shifted.rest_port,
+            &mut shifted.external_port,
+            &mut shifted.metrics_port,
+            &mut shifted.archive_port,
+            &mut shifted.uptime_service_port,
+            &mut shifted.snark_coordinator_port,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *port = self.add_port(*port, offset)?;
+            if *port > MAX_PORT {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Port {port} of '{}' is above {MAX_PORT}", self.service_name),
+                ));
+            }
+        }
+        *self = shifted;
+        Ok(())
+    }
+
+    /// Points the services of network `from` at network `to`: the addresses of the
+    /// network's own peers (`/dns4/<service>-<network>/tcp/<port>/...`), following
+    /// the peers' current external ports, and the peer list file
+    pub fn rename_network(
+        services: &mut [Self],
+        from: &str,
+        to: &str,
+        peer_list_file: &Path,
+    ) -> io::Result<()> {
+        let external_ports: HashMap<String, u16> = services
             .iter()
-            .filter(|s| s.service_type == ServiceType::UptimeServiceBackend);
+            .map(|service| {
+                Ok((
+                    service.service_name.clone(),
+                    service.daemon_ports()?.external,
+                ))
+            })
+            .collect::<io::Result<_>>()?;
+        let from_suffix = format!("-{from}");
+
+        for service in services.iter_mut() {
+            for peer in service.peers.iter_mut().flatten() {
+                let Some(address) = peer.strip_prefix("/dns4/") else {
+                    continue;
+                };
+                let mut parts = address.splitn(4, '/');
+                let (Some(host), Some("tcp"), Some(_port), rest) =
+                    (parts.next(), parts.next(), parts.next(), parts.next())
+                else {
+                    continue;
+                };
+                let Some(name) = host.strip_suffix(&from_suffix) else {
+                    continue;
+                };
+                if let Some(external_port) = external_ports.get(name) {
+                    *peer = match rest {
+                        Some(rest) => format!("/dns4/{name}-{to}/tcp/{external_port}/{rest}"),
+                        None => format!("/dns4/{name}-{to}/tcp/{external_port}"),
+                    };
+                }
+            }
+            if service.peer_list_file.is_some() {
+                service.peer_list_file = Some(peer_list_file.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::service;
+
+    #[test]
+    fn test_select_services() {
+        let services = vec![
+            service("mina-seed-1", ServiceType::Seed),
+            service("mina-bp-1", ServiceType::BlockProducer),
+            service("mina-bp-2", ServiceType::BlockProducer),
+            service("mina-archive", ServiceType::ArchiveNode),
+            service("uptime", ServiceType::UptimeServiceBackend),
+        ];
+        let select = |roles: &[Role], nodes: &[&str]| {
+            let nodes: Vec<String> = nodes.iter().map(|node| node.to_string()).collect();
+            select_services(&services, roles, &nodes)
+        };
+        assert_eq!(
+            select(&[], &[]).unwrap(),
+            ["mina-seed-1", "mina-bp-1", "mina-bp-2", "mina-archive"]
+        );
+        assert_eq!(
+            select(&[Role::BlockProducer, Role::Seed], &[]).unwrap(),
+            ["mina-seed-1", "mina-bp-1", "mina-bp-2"]
+        );
+        // nodes are selected instead of the roles
+        assert_eq!(
+            select(&[Role::Seed], &["mina-archive", "mina-bp-1"]).unwrap(),
+            ["mina-bp-1", "mina-archive"]
+        );
+        let err = select(&[], &["mina-bp-3"]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let err = select(&[Role::SnarkWorker], &[]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_shift_ports() {
+        let mut seed = ServiceConfig {
+            client_port: Some(3100),
+            metrics_port: Some(6060),
+            ..Default::default()
+        };
+        seed.shift_ports(1000).unwrap();
+        assert_eq!(seed.client_port, Some(4100));
+        assert_eq!(seed.daemon_ports().unwrap().rest, 4101);
+        assert_eq!(seed.metrics_port, Some(7060));
+
+        let mut worker = ServiceConfig {
+            snark_coordinator_port: Some(7000),
+            ..Default::default()
+        };
+        worker.shift_ports(1000).unwrap();
+        assert_eq!(worker.client_port, None);
+        assert_eq!(worker.snark_coordinator_port, Some(8000));
+
+        // ports are left as they were when one would not fit
+        let err = seed.shift_ports(61_432).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(seed.client_port, Some(4100));
+        assert!(seed.shift_ports(u16::MAX).is_err());
+        assert_eq!(seed.metrics_port, Some(7060));
+    }
 
-        let first_backend = uptime_service_backends.next();
+    #[test]
+    fn test_daemon_ports_overflow() {
+        let service = ServiceConfig {
+            service_name: "mina-seed-1".to_string(),
+            client_port: Some(MAX_PORT),
+            ..Default::default()
+        };
+        assert_eq!(service.daemon_ports().unwrap().libp2p_metrics, u16::MAX);
+
+        let service = ServiceConfig {
+            client_port: Some(u16::MAX - 1),
+            ..service
+        };
+        let err = service.daemon_ports().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "Port 65534 + 3 of 'mina-seed-1' is above 65535"
+        );
+
+        let service = ServiceConfig {
+            client_port: Some(3100),
+            metrics_port: Some(u16::MAX),
+            ..service
+        };
+        assert!(service.daemon_ports().is_err());
+    }
+
+    #[test]
+    fn test_rename_network() {
+        let mut services = vec![
+            ServiceConfig {
+                service_name: "mina-bp-1".to_string(),
+                peers: Some(vec![
+                    "/dns4/mina-seed-1-old/tcp/3102/p2p/12D3KooW".to_string(),
+                    "/dns4/mina-seed-9-old/tcp/3102/p2p/12D3KooW".to_string(),
+                    "/ip4/127.0.0.1/tcp/3102/p2p/12D3KooW".to_string(),
+                ]),
+                ..Default::default()
+            },
+            ServiceConfig {
+                service_name: "mina-seed-1".to_string(),
+                client_port: Some(4100),
+                peer_list_file: Some(PathBuf::from("/home/.minimina/old/peer_list_file.txt")),
+                ..Default::default()
+            },
+        ];
+
+        let peer_list_file = Path::new("/home/.minimina/new/peer_list_file.txt");
+        ServiceConfig::rename_network(&mut services, "old", "new", peer_list_file).unwrap();
+
+        assert_eq!(
+            services[0].peers.as_deref().unwrap(),
+            [
+                "/dns4/mina-seed-1-new/tcp/4102/p2p/12D3KooW",
+                "/dns4/mina-seed-9-old/tcp/3102/p2p/12D3KooW",
+                "/ip4/127.0.0.1/tcp/3102/p2p/12D3KooW",
+            ]
+        );
+        assert_eq!(services[0].peer_list_file, None);
+        assert_eq!(services[1].peer_list_file.as_deref(), Some(peer_list_file));
+    }
 
-        if uptime_service_backends.next().is_some() {
-            panic!("There can only be one uptime service backend in topology");
+    #[test]
+    fn test_new_fields_are_optional() {
+        let legacy: ServiceConfig = serde_json::from_str(
+            r#"{"service_type": "Seed_node", "service_name": "mina-seed-1", "client_port": 3100}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy.daemon_ports().unwrap().rest, 3101);
+
+        let json = serde_json::to_value(&legacy).unwrap();
+        for field in [
+            "bin_path",
+            "rest_port",
+            "external_port",
+            "metrics_port",
+            "uptime_service_backend",
+            "archive_db",
+            "uptime_service_port",
+        ] {
+            assert!(json.get(field).is_none(), "{field} is serialized");
         }
+    }
 
-        first_backend
+    #[test]
+    fn test_archive_dbs() {
+        let legacy: ServiceConfig = serde_json::from_str(
+            r#"{"service_type": "Archive_node", "service_name": "mina-archive"}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy.archive_db(), ARCHIVE_DB);
+
+        let mut services = vec![
+            ServiceConfig {
+                service_name: "mina-archive-1".to_string(),
+                service_type: ServiceType::ArchiveNode,
+                ..Default::default()
+            },
+            ServiceConfig {
+                service_name: "mina-seed-1".to_string(),
+                service_type: ServiceType::Seed,
+                ..Default::default()
+            },
+        ];
+        ServiceConfig::name_archive_dbs(&mut services, "test-net");
+        assert_eq!(services[0].archive_db(), "archive_test_net_mina_archive_1");
+        assert_eq!(services[1].archive_db, None);
     }
 }
@@ -0,0 +1,154 @@
+//! # Snapshot Module
+//!
+//! A snapshot is a `<name>.tar.gz` file in the snapshots directory containing:
+//! - `manifest.json`: what was saved, see [`Manifest`]
+//! - `network/`: the network directory with its metadata, keys, genesis ledger,
+//!   `processes.json` and, in native mode, the daemons' config directories
+//! - `state/`: the state the backend keeps outside of the network directory,
+//!   saved by [`Backend::save_state`](crate::backend::Backend::save_state)
+//!
+//! Network bundles (see [`crate::bundle`]) share this layout, without the state.
+//! Archiving is done with the system `tar`.
+
+use crate::{backend::ensure_success, cli::ExecutionMode, utils::run_command};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Result},
+    path::{Path, PathBuf},
+};
+use tempdir::TempDir;
+
+pub const MANIFEST_JSON: &str = "manifest.json";
+const NETWORK_DIR: &str = "network";
+const STATE_DIR: &str = "state";
+
+/// Content of a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Manifest {
+    pub name: String,
+    /// Network the snapshot was taken of
+    pub network_id: String,
+    /// Mode the network ran in, a snapshot is restored in the same mode
+    pub mode: ExecutionMode,
+    pub created_at: String,
+    pub minimina_version: String,
+    pub services: Vec<String>,
+    /// Entries of the `state/` directory
+    pub state: Vec<String>,
+}
+
+/// Snapshot contents being assembled, or unpacked for a restore, in a temporary directory
+pub struct Staging {
+    dir: TempDir,
+}
+
+impl Staging {
+    pub fn new() -> Result<Self> {
+        let staging = Staging {
+            dir: TempDir::new("minimina-snapshot")?,
+        };
+        fs::create_dir_all(staging.network_dir())?;
+        fs::create_dir_all(staging.state_dir())?;
+        Ok(staging)
+    }
+
+    /// Unpack the snapshot archive at `path`
+    pub fn unpack<M: DeserializeOwned>(path: &Path) -> Result<(Self, M)> {
+        let staging = Staging {
+            dir: TempDir::new("minimina-restore")?,
+        };
+        tar(&["-xzf", path_str(path)?, "-C", path_str(staging.dir.path())?])?;
+        let manifest = fs::read_to_string(staging.dir.path().join(MANIFEST_JSON))?;
+        let manifest = serde_json::from_str(&manifest)?;
+        Ok((staging, manifest))
+    }
+
+    pub fn network_dir(&self) -> PathBuf {
+        self.dir.path().join(NETWORK_DIR)
+    }
+
+    pub fn state_dir(&self) -> PathBuf {
+        self.dir.path().join(STATE_DIR)
+    }
+
+    /// Write `manifest` and pack the staged contents into the archive at `path`
+    pub fn pack<M: Serialize>(&self, manifest: &M, path: &Path) -> Result<()> {
+        fs::write(
+            self.dir.path().join(MANIFEST_JSON),
+            serde_json::to_string_pretty(manifest)?,
+        )?;
+        tar(&[
+            "-czf",
+            path_str(path)?,
+            "-C",
+            path_str(self.dir.path())?,
+            ".",
+        ])
+    }
+}
+
+/// Copy the contents of directory `src` into `dest`, preserving permissions
+pub fn copy_dir(src: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest)?;
+    let src = src.join(".");
+    ensure_success(run_command("cp", &["-a", path_str(&src)?, path_str(dest)?])).map(|_| ())
+}
+
+fn tar(args: &[&str]) -> Result<()> {
+    ensure_success(run_command("tar", args)).map(|_| ())
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Path '{}' is not valid UTF-8", path.display()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_pack_unpack() {
+        let tempdir = TempDir::new("test_pack_unpack").expect("Cannot create temporary directory");
+        let network_path = tempdir.path().join("network");
+        fs::create_dir_all(network_path.join("network-keypairs")).unwrap();
+        let key_path = network_path.join("network-keypairs/mina-bp-1");
+        fs::write(&key_path, "secret").unwrap();
+        fs::set_permissions(&key_path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let staging = Staging::new().unwrap();
+        copy_dir(&network_path, &staging.network_dir()).unwrap();
+        fs::write(staging.state_dir().join("archive.sql"), "dump").unwrap();
+        let manifest = Manifest {
+            name: "epoch-2".to_string(),
+            network_id: "test".to_string(),
+            mode: ExecutionMode::Native,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            minimina_version: env!("CARGO_PKG_VERSION").to_string(),
+            services: vec!["mina-bp-1".to_string()],
+            state: vec!["archive.sql".to_string()],
+        };
+        let archive = tempdir.path().join("epoch-2.tar.gz");
+        staging.pack(&manifest, &archive).unwrap();
+
+        let (unpacked, unpacked_manifest): (_, Manifest) = Staging::unpack(&archive).unwrap();
+        assert_eq!(unpacked_manifest, manifest);
+        let key_path = unpacked.network_dir().join("network-keypairs/mina-bp-1");
+        assert_eq!(fs::read_to_string(&key_path).unwrap(), "secret");
+        let mode = fs::metadata(&key_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(
+            fs::read_to_string(unpacked.state_dir().join("archive.sql")).unwrap(),
+            "dump"
+        );
+
+        let error = Staging::unpack::<Manifest>(&tempdir.path().join("missing.tar.gz"));
+        assert!(error.is_err());
+    }
+}
@@ -1,12 +1,15 @@
-use crate::service::{ServiceConfig, ServiceType};
-use serde::{Deserialize, Serialize};
+use crate::service::{ServiceConfig, ServiceType, UPTIME_SERVICE_PORT};
+use minimina::amounts::convert_balance_to_decimal_mina;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt, io,
     path::{Path, PathBuf},
 };
 
 /// Type of git build
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum GitBuild {
     #[serde(rename = "commit")]
     Commit(String),
@@ -14,6 +17,19 @@ pub enum GitBuild {
     Tag(String),
 }
 
+/// Optional daemon ports of a topology entry.
+/// Omitted ports are assigned by [`Topology::services`].
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct PortsTopologyInfo {
+    pub client_port: Option<u16>,
+    pub rest_port: Option<u16>,
+    pub external_port: Option<u16>,
+    pub metrics_port: Option<u16>,
+}
+
+/// Port fields accepted by every daemon role
+const PORT_FIELDS: [&str; 4] = ["client_port", "rest_port", "external_port", "metrics_port"];
+
 /// Topology info for an archive node
 #[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct ArchiveTopologyInfo {
@@ -25,9 +41,12 @@ pub struct ArchiveTopologyInfo {
     pub archive_image: Option<String>,
     pub git_build: Option<GitBuild>,
     pub schema_files: Vec<PathBuf>,
+    pub archive_port: Option<u16>,
     pub libp2p_pass: String,
     pub libp2p_keyfile: PathBuf,
     pub libp2p_peerid: String,
+    #[serde(flatten)]
+    pub ports: PortsTopologyInfo,
 }
 
 /// Topology info for a block producer or seed node
@@ -40,9 +59,24 @@ pub struct NodeTopologyInfo {
     pub docker_image: Option<String>,
     pub git_build: Option<GitBuild>,
     pub privkey_path: Option<PathBuf>,
+    /// Uptime service backend a block producer submits to, `Some(None)` for an explicit `null`.
+    /// When omitted, the network's only backend is used, if it has exactly one.
+    #[serde(default, deserialize_with = "explicit_null")]
+    pub uptime_service_backend: Option<Option<String>>,
     pub libp2p_pass: String,
     pub libp2p_keyfile: PathBuf,
     pub libp2p_peerid: String,
+    #[serde(flatten)]
+    pub ports: PortsTopologyInfo,
+}
+
+/// Keeps an explicit `null` apart from an omitted field, which `#[serde(default)]` makes `None`
+fn explicit_null<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
 /// Topology info for a snark coordinator
@@ -59,6 +93,8 @@ pub struct SnarkCoordinatorTopologyInfo {
     pub libp2p_pass: String,
     pub libp2p_keyfile: PathBuf,
     pub libp2p_peerid: String,
+    #[serde(flatten)]
+    pub ports: PortsTopologyInfo,
 }
 
 /// Topology info for uptime service backend
@@ -70,6 +106,8 @@ pub struct UptimeServiceTopologyInfo {
     pub app_config_path: PathBuf,
     pub minasheets_path: PathBuf,
     pub other_config_files: Option<Vec<PathBuf>>,
+    /// Host port of the backend, assigned from 8080 when omitted
+    pub port: Option<u16>,
 }
 
 /// Each node variant's topology info
@@ -89,14 +127,116 @@ pub struct Topology {
     pub topology: HashMap<String, TopologyInfo>,
 }
 
+/// Why a single topology entry could not be parsed
+#[derive(Debug)]
+pub enum EntryError {
+    NotAnObject,
+    MissingRole,
+    UnknownRole(String),
+    Invalid(serde_json::Error),
+}
+
+impl fmt::Display for EntryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryError::NotAnObject => write!(f, "entry is not a JSON object"),
+            EntryError::MissingRole => write!(f, "missing field `role`"),
+            EntryError::UnknownRole(role) => write!(
+                f,
+                "unknown role {role}, expected one of: Seed_node, Block_producer, \
+                 Snark_coordinator, Archive_node, Uptime_service_backend"
+            ),
+            EntryError::Invalid(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// Libp2p fields shared by all daemon roles
+const LIBP2P_FIELDS: [&str; 3] = ["libp2p_pass", "libp2p_keyfile", "libp2p_peerid"];
+
 impl TopologyInfo {
-    fn to_service_config(
+    /// Fields of an entry with the given `role` as `(required, optional)`,
+    /// `None` if the role can't be declared in a topology file.
+    ///
+    /// `libp2p_keypair` is generated by Lucy next to `libp2p_keyfile` and ignored.
+    pub fn fields(role: &ServiceType) -> Option<(Vec<&'static str>, Vec<&'static str>)> {
+        let daemon = |required: &[&'static str], optional: &[&'static str]| {
+            let mut all_required = vec!["pk", "sk", "role"];
+            all_required.extend(required);
+            all_required.extend(LIBP2P_FIELDS);
+            let mut all_optional = vec!["docker_image", "git_build", "libp2p_keypair"];
+            all_optional.extend(PORT_FIELDS);
+            all_optional.extend(optional);
+            Some((all_required, all_optional))
+        };
+
+        match role {
+            ServiceType::Seed => daemon(&[], &["privkey_path"]),
+            ServiceType::BlockProducer => daemon(&[], &["privkey_path", "uptime_service_backend"]),
+            ServiceType::SnarkCoordinator => daemon(&["worker_nodes", "snark_worker_fee"], &[]),
+            ServiceType::ArchiveNode => {
+                daemon(&["schema_files"], &["archive_image", "archive_port"])
+            }
+            ServiceType::UptimeServiceBackend => Some((
+                vec!["role", "app_config_path", "minasheets_path"],
+                vec!["docker_image", "other_config_files", "port"],
+            )),
+            // workers are generated from their coordinator's `worker_nodes`
+            ServiceType::SnarkWorker => None,
+        }
+    }
+
+    /// The declared `role` of a topology entry
+    pub fn role(value: &Value) -> Result<ServiceType, EntryError> {
+        let role = value
+            .as_object()
+            .ok_or(EntryError::NotAnObject)?
+            .get("role")
+            .ok_or(EntryError::MissingRole)?;
+
+        serde_json::from_value::<ServiceType>(role.clone())
+            .ok()
+            .filter(|service_type| Self::fields(service_type).is_some())
+            .ok_or_else(|| EntryError::UnknownRole(role.to_string()))
+    }
+
+    /// Parse a topology entry against the node variant of its declared `role`
+    pub fn from_value(value: Value) -> Result<Self, EntryError> {
+        let info = match Self::role(&value)? {
+            ServiceType::ArchiveNode => serde_json::from_value(value).map(TopologyInfo::Archive),
+            ServiceType::SnarkCoordinator => {
+                serde_json::from_value(value).map(TopologyInfo::SnarkCoordinator)
+            }
+            ServiceType::UptimeServiceBackend => {
+                serde_json::from_value(value).map(TopologyInfo::UptimeServiceBackend)
+            }
+            _ => serde_json::from_value(value).map(TopologyInfo::Node),
+        };
+        info.map_err(EntryError::Invalid)
+    }
+
+    /// Service config of the entry, with only the ports set explicitly in the topology.
+    /// `uptime_service_backends` are the names of all uptime service backends in the topology.
+    pub(crate) fn to_service_config(
         &self,
         service_name: String,
         peer_list_file: &Path,
-        client_port: u16,
-        archive_port: u16,
+        uptime_service_backends: &[&String],
     ) -> ServiceConfig {
+        let ports = match self {
+            TopologyInfo::Archive(archive_info) => archive_info.ports.clone(),
+            TopologyInfo::Node(node_info) => node_info.ports.clone(),
+            TopologyInfo::SnarkCoordinator(snark_info) => snark_info.ports.clone(),
+            TopologyInfo::UptimeServiceBackend(_) => PortsTopologyInfo::default(),
+        };
+        let ports = ServiceConfig {
+            client_port: ports.client_port,
+            rest_port: ports.rest_port,
+            external_port: ports.external_port,
+            metrics_port: ports.metrics_port,
+            ..Default::default()
+        };
+
         match self {
             TopologyInfo::UptimeServiceBackend(uptime_service_info) => ServiceConfig {
                 service_type: ServiceType::UptimeServiceBackend,
@@ -109,6 +249,7 @@ impl TopologyInfo {
                     uptime_service_info.minasheets_path.clone(),
                 ),
                 uptime_service_other_config_files: uptime_service_info.other_config_files.clone(),
+                uptime_service_port: uptime_service_info.port,
 
                 ..Default::default()
             },
@@ -117,7 +258,6 @@ impl TopologyInfo {
                 service_name,
                 docker_image: archive_info.docker_image.clone(),
                 git_build: archive_info.git_build.clone(),
-                client_port: Some(client_port),
                 public_key: Some(archive_info.pk.clone()),
                 private_key: Some(archive_info.sk.clone()),
                 peer_list_file: Some(peer_list_file.to_path_buf()),
@@ -128,70 +268,178 @@ impl TopologyInfo {
                         .map(|path| path.to_str().unwrap().to_string())
                         .collect(),
                 ),
-                archive_port: Some(archive_port),
+                archive_port: archive_info.archive_port,
                 archive_docker_image: archive_info.archive_image.clone(),
                 libp2p_keypair_path: Some(archive_info.libp2p_keyfile.clone()),
                 libp2p_peerid: Some(archive_info.libp2p_peerid.clone()),
-                ..Default::default()
+                ..ports
             },
             TopologyInfo::Node(node_info) => ServiceConfig {
                 service_type: node_info.service_type.clone(),
                 service_name,
                 docker_image: node_info.docker_image.clone(),
                 git_build: node_info.git_build.clone(),
-                client_port: Some(client_port),
                 public_key: Some(node_info.pk.clone()),
                 private_key: Some(node_info.sk.clone()),
                 private_key_path: node_info.privkey_path.clone(),
+                uptime_service_backend: match (
+                    &node_info.service_type,
+                    &node_info.uptime_service_backend,
+                ) {
+                    (ServiceType::BlockProducer, Some(backend)) => backend.clone(),
+                    (ServiceType::BlockProducer, None) if uptime_service_backends.len() == 1 => {
+                        Some(uptime_service_backends[0].clone())
+                    }
+                    _ => None,
+                },
                 libp2p_keypair_path: Some(node_info.libp2p_keyfile.clone()),
                 libp2p_peerid: Some(node_info.libp2p_peerid.clone()),
                 peer_list_file: Some(peer_list_file.to_path_buf()),
-                ..Default::default()
+                ..ports
             },
             TopologyInfo::SnarkCoordinator(snark_info) => ServiceConfig {
                 service_type: snark_info.service_type.clone(),
                 service_name,
                 docker_image: snark_info.docker_image.clone(),
                 git_build: snark_info.git_build.clone(),
-                client_port: Some(client_port),
                 public_key: Some(snark_info.pk.clone()),
                 private_key: Some(snark_info.sk.clone()),
                 libp2p_keypair_path: Some(snark_info.libp2p_keyfile.clone()),
                 libp2p_peerid: Some(snark_info.libp2p_peerid.clone()),
                 peer_list_file: Some(peer_list_file.to_path_buf()),
-                snark_coordinator_fees: Some(snark_info.snark_worker_fee.clone()),
+                snark_coordinator_fees: Some(snark_worker_fee(&snark_info.snark_worker_fee)),
                 snark_worker_proof_level: Some("full".to_string()),
                 worker_nodes: Some(snark_info.worker_nodes),
-                ..Default::default()
+                ..ports
             },
         }
     }
 }
 
+/// Ports set explicitly for a service
+fn explicit_ports(service: &ServiceConfig) -> io::Result<Vec<u16>> {
+    let mut ports = match service.client_port {
+        Some(_) => service.daemon_ports()?.all().to_vec(),
+        None => [
+            service.rest_port,
+            service.external_port,
+            service.metrics_port,
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+    };
+    if let (None, Some(metrics)) = (service.client_port, service.metrics_port) {
+        ports.push(service.add_port(metrics, 1)?);
+    }
+    ports.extend(service.archive_port);
+    ports.extend(service.uptime_service_port);
+    Ok(ports)
+}
+
+/// Assign the ports not set explicitly, never handing out a port that is already taken
+fn assign_ports(services: &mut [ServiceConfig]) -> io::Result<()> {
+    let mut used: HashSet<u16> = HashSet::new();
+    for service in services.iter() {
+        used.extend(explicit_ports(service)?);
+    }
+    let mut client_port = 7070;
+    let mut archive_port = 3086;
+    let mut uptime_service_port = UPTIME_SERVICE_PORT;
+
+    for service in services.iter_mut() {
+        let own = explicit_ports(service)?;
+        let is_daemon = service.service_type != ServiceType::UptimeServiceBackend;
+
+        if is_daemon && service.client_port.is_none() {
+            loop {
+                client_port += 5;
+                service.client_port = Some(client_port);
+                let ports = service.daemon_ports()?.all();
+                if ports
+                    .iter()
+                    .all(|port| own.contains(port) || !used.contains(port))
+                {
+                    used.extend(ports);
+                    break;
+                }
+            }
+        }
+
+        if service.service_type == ServiceType::ArchiveNode && service.archive_port.is_none() {
+            while used.contains(&archive_port) {
+                archive_port += 1;
+            }
+            service.archive_port = Some(archive_port);
+            used.insert(archive_port);
+        }
+
+        if !is_daemon && service.uptime_service_port.is_none() {
+            while used.contains(&uptime_service_port) {
+                uptime_service_port += 1;
+            }
+            service.uptime_service_port = Some(uptime_service_port);
+            used.insert(uptime_service_port);
+        }
+    }
+    Ok(())
+}
+
+/// The daemon expects the snark worker fee in decimal mina.
+/// Amount DSL fees (e.g. `0.01mina`) are converted, anything else is passed on verbatim.
+fn snark_worker_fee(fee: &str) -> String {
+    convert_balance_to_decimal_mina(fee).unwrap_or_else(|_| fee.to_string())
+}
+
 impl Topology {
-    pub fn new(path: &Path) -> serde_json::Result<Self> {
-        let contents = std::fs::read_to_string(path).unwrap();
-        serde_json::from_str(&contents)
+    /// Read the topology file at `path`, parsing each node against its declared `role`
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let entries: HashMap<String, Value> = serde_json::from_str(&contents)?;
+
+        let topology = entries
+            .into_iter()
+            .map(|(name, value)| match TopologyInfo::from_value(value) {
+                Ok(info) => Ok((name, info)),
+                Err(e) => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("node '{name}': {e}"),
+                )),
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Topology { topology })
     }
 
-    pub fn services(&self, peer_list_file: &Path) -> Vec<ServiceConfig> {
-        let mut client_port = 7070;
-        let archive_port = 3086;
+    /// Service configs of all nodes, ordered by name.
+    ///
+    /// Ports omitted in the topology are assigned in that order, so they are stable across runs:
+    /// client ports 7075, 7080, ..., archive ports 3086, 3087, ... and uptime service backend
+    /// ports 8080, 8081, ..., skipping any port set explicitly.
+    /// Fails if a port derived from an explicit one is above `u16::MAX`.
+    pub fn services(&self, peer_list_file: &Path) -> io::Result<Vec<ServiceConfig>> {
+        let mut entries: Vec<(&String, &TopologyInfo)> = self.topology.iter().collect();
+        entries.sort_by_key(|(service_name, _)| *service_name);
 
-        let mut services: Vec<ServiceConfig> = self
-            .topology
+        let uptime_service_backends: Vec<&String> = entries
+            .iter()
+            .filter(|(_, info)| matches!(info, TopologyInfo::UptimeServiceBackend(_)))
+            .map(|(service_name, _)| *service_name)
+            .collect();
+
+        let mut services: Vec<ServiceConfig> = entries
             .iter()
             .map(|(service_name, service_info)| {
-                client_port += 5;
                 service_info.to_service_config(
-                    service_name.clone(),
+                    service_name.to_string(),
                     peer_list_file,
-                    client_port,
-                    archive_port,
+                    &uptime_service_backends,
                 )
             })
             .collect();
 
+        assign_ports(&mut services)?;
+
         let snark_coordinator_services: Vec<ServiceConfig> = services
             .iter()
             .filter(|service| service.service_type == ServiceType::SnarkCoordinator)
@@ -204,6 +452,7 @@ impl Topology {
                     service_type: ServiceType::SnarkWorker,
                     service_name: format!("{}-worker_{}", coordinator.service_name, i),
                     docker_image: coordinator.docker_image.clone(),
+                    git_build: coordinator.git_build.clone(),
                     snark_coordinator_port: coordinator.client_port,
                     snark_worker_proof_level: coordinator.snark_worker_proof_level.clone(),
                     snark_coordinator_host: Some(coordinator.service_name.clone()),
@@ -212,7 +461,41 @@ impl Topology {
             );
         }
 
-        services
+        Ok(services)
+    }
+
+    /// Public keys of the nodes by name
+    pub fn public_keys(&self) -> HashMap<String, String> {
+        self.topology
+            .iter()
+            .filter_map(|(name, info)| {
+                let pk = match info {
+                    TopologyInfo::Archive(archive_info) => &archive_info.pk,
+                    TopologyInfo::SnarkCoordinator(snark_info) => &snark_info.pk,
+                    TopologyInfo::Node(node_info) => &node_info.pk,
+                    TopologyInfo::UptimeServiceBackend(_) => return None,
+                };
+                Some((name.clone(), pk.clone()))
+            })
+            .collect()
+    }
+
+    /// Names and public keys of the block producers, sorted by name
+    pub fn block_producer_keys(&self) -> Vec<(String, String)> {
+        let mut keys: Vec<(String, String)> = self
+            .topology
+            .iter()
+            .filter_map(|(name, info)| match info {
+                TopologyInfo::Node(node_info)
+                    if node_info.service_type == ServiceType::BlockProducer =>
+                {
+                    Some((name.clone(), node_info.pk.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        keys.sort();
+        keys
     }
 
     #[allow(dead_code)]
@@ -314,9 +597,11 @@ mod tests {
                 service_type: ServiceType::ArchiveNode,
                 schema_files: vec![schema_file.into(), zkapp_table.into()],
                 archive_image: Some("archive-image".into()),
+                archive_port: None,
                 libp2p_pass: "naughty blue potato".into(),
                 libp2p_keyfile: "/path/to/keyfile".into(),
                 libp2p_peerid: "123".into(),
+                ports: PortsTopologyInfo::default(),
             }
         );
     }
@@ -357,9 +642,11 @@ mod tests {
                 git_build: None,
                 service_type: ServiceType::BlockProducer,
                 privkey_path: Some(privkey_path.into()),
+                uptime_service_backend: None,
                 libp2p_pass,
                 libp2p_keyfile: libp2p_keyfile.into(),
                 libp2p_peerid,
+                ports: PortsTopologyInfo::default(),
             }
         );
     }
@@ -400,9 +687,11 @@ mod tests {
                 git_build: None,
                 service_type: ServiceType::Seed,
                 privkey_path: Some(privkey_path.into()),
+                uptime_service_backend: None,
                 libp2p_pass,
                 libp2p_keyfile: libp2p_keyfile.into(),
                 libp2p_peerid,
+                ports: PortsTopologyInfo::default(),
             }
         );
     }
@@ -451,6 +740,7 @@ mod tests {
                 libp2p_pass,
                 libp2p_keyfile: libp2p_keyfile.into(),
                 libp2p_peerid,
+                ports: PortsTopologyInfo::default(),
             }
         );
     }
@@ -468,12 +758,14 @@ mod tests {
             pk,
             sk,
             privkey_path: Some("path/to/privkey/file.json".into()),
+            uptime_service_backend: None,
             service_type,
             docker_image: None,
             git_build: Some(GitBuild::Tag("bp_git_tag".to_string())),
             libp2p_pass,
             libp2p_keyfile,
             libp2p_peerid,
+            ports: PortsTopologyInfo::default(),
         };
 
         let seed_name = "seed".into();
@@ -488,12 +780,14 @@ mod tests {
             pk,
             sk,
             privkey_path: None,
+            uptime_service_backend: None,
             service_type,
             docker_image,
             git_build: None,
             libp2p_pass,
             libp2p_keyfile,
             libp2p_peerid,
+            ports: PortsTopologyInfo::default(),
         };
 
         let snark_name = "snark".into();
@@ -517,6 +811,7 @@ mod tests {
             libp2p_pass,
             libp2p_keyfile,
             libp2p_peerid,
+            ports: PortsTopologyInfo::default(),
         };
 
         let expect: Topology = serde_json::from_str(
@@ -592,12 +887,47 @@ mod tests {
         assert_eq!(num_scs, 1);
     }
 
+    #[test]
+    fn test_from_value_uses_role() {
+        // an archive node missing `schema_files` must not fall back to another variant
+        let value = serde_json::json!({
+            "pk": "pk", "sk": "sk", "role": "Archive_node",
+            "libp2p_pass": "pass", "libp2p_keyfile": "keyfile", "libp2p_peerid": "peerid"
+        });
+        let err = TopologyInfo::from_value(value).unwrap_err();
+        assert_eq!(err.to_string(), "missing field `schema_files`");
+
+        let value = serde_json::json!({ "role": "Snark_worker" });
+        assert!(matches!(
+            TopologyInfo::from_value(value),
+            Err(EntryError::UnknownRole(_))
+        ));
+    }
+
+    #[test]
+    fn test_new_reports_node_name() {
+        let tempdir = tempdir::TempDir::new("test_new_reports_node_name")
+            .expect("Cannot create temporary directory");
+        let path = tempdir.path().join("topology.json");
+        std::fs::write(&path, r#"{"seed": {"role": "Seed_node", "pk": "pk"}}"#).unwrap();
+
+        let err = Topology::new(&path).unwrap_err();
+        assert_eq!(err.to_string(), "node 'seed': missing field `sk`");
+        assert!(Topology::new(&tempdir.path().join("missing.json")).is_err());
+    }
+
+    #[test]
+    fn test_snark_worker_fee() {
+        assert_eq!(snark_worker_fee("0.01mina"), "0.010000000");
+        assert_eq!(snark_worker_fee("0.0001"), "0.0001");
+    }
+
     #[test]
     fn test_topology_to_services() {
         let path = PathBuf::from("./tests/data/large_network/topology.json");
         let topology = Topology::new(&path).unwrap();
         let peer_list_file = PathBuf::from("./tests/data/large_network/peers.txt");
-        let services = topology.services(&peer_list_file);
+        let services = topology.services(&peer_list_file).unwrap();
 
         let num_services = services.len();
         let num_archives = services
@@ -628,4 +958,164 @@ mod tests {
         assert_eq!(num_scs, 1);
         assert_eq!(num_workers, 2);
     }
+
+    #[test]
+    fn test_services_ports_are_deterministic() {
+        let path = PathBuf::from("./tests/data/large_network/topology.json");
+        let peer_list_file = PathBuf::from("peers.txt");
+        let ports = |services: Vec<ServiceConfig>| {
+            services
+                .into_iter()
+                .map(|service| (service.service_name, service.client_port))
+                .collect::<Vec<_>>()
+        };
+
+        let first = ports(
+            Topology::new(&path)
+                .unwrap()
+                .services(&peer_list_file)
+                .unwrap(),
+        );
+        for _ in 0..5 {
+            let next = ports(
+                Topology::new(&path)
+                    .unwrap()
+                    .services(&peer_list_file)
+                    .unwrap(),
+            );
+            assert_eq!(first, next);
+        }
+        assert!(first
+            .windows(2)
+            .all(|pair| pair[0].0 < pair[1].0 || pair[1].1.is_none()));
+        assert_eq!(first[0].1, Some(7075));
+    }
+
+    #[test]
+    fn test_services_explicit_ports() {
+        let node = |ports: Value| {
+            let mut node = serde_json::json!({
+                "pk": "pk", "sk": "sk", "role": "Block_producer",
+                "libp2p_pass": "pass", "libp2p_keyfile": "keyfile", "libp2p_peerid": "peer"
+            });
+            node.as_object_mut()
+                .unwrap()
+                .extend(ports.as_object().unwrap().clone());
+            TopologyInfo::from_value(node).unwrap()
+        };
+        let mut archive = serde_json::json!({
+            "pk": "pk", "sk": "sk", "role": "Archive_node", "schema_files": [],
+            "libp2p_pass": "pass", "libp2p_keyfile": "keyfile", "libp2p_peerid": "peer"
+        });
+        let archive_0 = TopologyInfo::from_value(archive.clone()).unwrap();
+        archive["archive_port"] = 3086.into();
+        let archive_1 = TopologyInfo::from_value(archive).unwrap();
+
+        let topology = Topology {
+            topology: HashMap::from([
+                (
+                    "a".to_string(),
+                    node(serde_json::json!({ "client_port": 7075 })),
+                ),
+                (
+                    "b".to_string(),
+                    node(serde_json::json!({ "rest_port": 9001 })),
+                ),
+                ("c".to_string(), node(serde_json::json!({}))),
+                ("d-archive".to_string(), archive_0),
+                ("e-archive".to_string(), archive_1),
+            ]),
+        };
+        let services = topology.services(Path::new("peers.txt")).unwrap();
+
+        let summary: Vec<_> = services
+            .iter()
+            .map(|service| {
+                (
+                    service.service_name.as_str(),
+                    service.daemon_ports().unwrap().client,
+                    service.daemon_ports().unwrap().rest,
+                    service.archive_port,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("a", 7075, 7076, None),
+                ("b", 7080, 9001, None),
+                ("c", 7085, 7086, None),
+                ("d-archive", 7090, 7091, Some(3087)),
+                ("e-archive", 7095, 7096, Some(3086)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_services_uptime_service_backends() {
+        let block_producer = |backend: Option<Value>| {
+            let mut node = serde_json::json!({
+                "pk": "pk", "sk": "sk", "role": "Block_producer",
+                "libp2p_pass": "pass", "libp2p_keyfile": "keyfile", "libp2p_peerid": "peer"
+            });
+            if let Some(backend) = backend {
+                node["uptime_service_backend"] = backend;
+            }
+            TopologyInfo::from_value(node).unwrap()
+        };
+        let backend = |port: Option<u16>| {
+            TopologyInfo::from_value(serde_json::json!({
+                "role": "Uptime_service_backend", "port": port,
+                "app_config_path": "app_config.json", "minasheets_path": "minasheets.json"
+            }))
+            .unwrap()
+        };
+        let summary = |topology: &Topology| {
+            topology
+                .services(Path::new("peers.txt"))
+                .unwrap()
+                .into_iter()
+                .map(|service| {
+                    (
+                        service.service_name,
+                        service.uptime_service_backend,
+                        service.uptime_service_port,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the only backend is used unless opted out with `null`
+        let mut topology = Topology {
+            topology: HashMap::from([
+                ("bp-0".to_string(), block_producer(None)),
+                ("bp-1".to_string(), block_producer(Some(Value::Null))),
+                ("uptime-a".to_string(), backend(None)),
+            ]),
+        };
+        assert_eq!(
+            summary(&topology),
+            [
+                ("bp-0".to_string(), Some("uptime-a".to_string()), None),
+                ("bp-1".to_string(), None, None),
+                ("uptime-a".to_string(), None, Some(8080)),
+            ]
+        );
+
+        // with several backends, block producers have to choose
+        topology.topology.extend([
+            ("bp-2".to_string(), block_producer(Some("uptime-b".into()))),
+            ("uptime-b".to_string(), backend(Some(8080))),
+        ]);
+        assert_eq!(
+            summary(&topology),
+            [
+                ("bp-0".to_string(), None, None),
+                ("bp-1".to_string(), None, None),
+                ("bp-2".to_string(), Some("uptime-b".to_string()), None),
+                ("uptime-a".to_string(), None, Some(8081)),
+                ("uptime-b".to_string(), None, Some(8080)),
+            ]
+        );
+    }
 }
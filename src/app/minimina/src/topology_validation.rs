@@ -0,0 +1,612 @@
+//! # Topology Validation Module
+//!
+//! Checks a topology file without creating a network and reports every problem found,
+//! not just the first one. Each finding names the offending node and carries a
+//! machine-readable code, so callers like Lucy can act on it.
+//!
+//! Checks per node:
+//! - the entry parses against the fields of its declared `role` (missing, unknown and mistyped fields),
+//! - referenced key and config files exist,
+//! - `snark_worker_fee` is a valid amount (see `minimina::amounts`),
+//! - explicit ports leave room for the ports derived from them.
+//!
+//! Checks across nodes:
+//! - libp2p peer IDs are unique,
+//! - block producers submit to declared uptime service backends, and name one when there are several,
+//! - there is at least one seed node,
+//! - no two nodes bind the same port once omitted ports are assigned.
+
+use crate::native::port_manager;
+use crate::service::{ServiceType, MAX_PORT};
+use crate::topology::{EntryError, Topology, TopologyInfo};
+use minimina::amounts::amount_dsl_to_nanomina;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Code {
+    /// The topology file can't be read
+    ReadError,
+    /// The topology file is not a JSON object of nodes
+    InvalidJson,
+    /// A node entry is not a JSON object
+    NotAnObject,
+    MissingRole,
+    UnknownRole,
+    MissingField,
+    /// A field that is not used for the node's role (warning)
+    UnknownField,
+    /// A field with a value of the wrong type
+    InvalidField,
+    /// A referenced key or config file does not exist
+    FileNotFound,
+    DuplicatePeerId,
+    NoSeedNode,
+    InvalidSnarkWorkerFee,
+    /// A plain decimal snark worker fee, as generated by Lucy (warning)
+    LegacySnarkWorkerFee,
+    /// A port bound by more than one node, or twice by the same node
+    PortConflict,
+    /// A port above `u16::MAX - 4`, whose derived ports would not fit
+    PortOutOfRange,
+    /// A block producer's `uptime_service_backend` is not an uptime service backend of the topology
+    UnknownUptimeServiceBackend,
+    /// A block producer omits `uptime_service_backend` while there are several, so it submits
+    /// to none of them (warning)
+    AmbiguousUptimeServiceBackend,
+}
+
+impl Code {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Code::UnknownField
+            | Code::LegacySnarkWorkerFee
+            | Code::AmbiguousUptimeServiceBackend => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Finding {
+    pub code: Code,
+    pub severity: Severity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(code: Code, node: Option<&str>, message: String) -> Self {
+        Finding {
+            code,
+            severity: code.severity(),
+            node: node.map(str::to_string),
+            message,
+        }
+    }
+}
+
+/// Validate the topology file at `path`, returning findings sorted by node name
+pub fn validate(path: &Path) -> Vec<Finding> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            return vec![Finding::new(
+                Code::ReadError,
+                None,
+                format!("Failed to read '{}': {e}", path.display()),
+            )]
+        }
+    };
+
+    match serde_json::from_str::<Map<String, Value>>(&contents) {
+        Ok(entries) => validate_entries(&entries),
+        Err(e) => vec![Finding::new(
+            Code::InvalidJson,
+            None,
+            format!("Expected a JSON object of nodes: {e}"),
+        )],
+    }
+}
+
+fn validate_entries(entries: &Map<String, Value>) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut nodes = vec![];
+
+    for (name, value) in entries {
+        if let Some(info) = validate_entry(name, value, &mut findings) {
+            nodes.push((name.as_str(), info));
+        }
+    }
+
+    let mut peer_ids: HashMap<&str, &str> = HashMap::new();
+    let mut seeds = 0;
+    let uptime_service_backends: Vec<&str> = nodes
+        .iter()
+        .filter(|(_, info)| matches!(info, TopologyInfo::UptimeServiceBackend(_)))
+        .map(|(name, _)| *name)
+        .collect();
+
+    let mut ports_in_range = true;
+    for (name, info) in &nodes {
+        for port in explicit_ports(name, info).filter(|port| *port > MAX_PORT) {
+            ports_in_range = false;
+            findings.push(Finding::new(
+                Code::PortOutOfRange,
+                Some(name),
+                format!("Port {port} is above {MAX_PORT}, its derived ports would not fit"),
+            ));
+        }
+
+        for (field, path) in referenced_files(info) {
+            if !path.exists() {
+                findings.push(Finding::new(
+                    Code::FileNotFound,
+                    Some(name),
+                    format!("File '{}' in `{field}` does not exist", path.display()),
+                ));
+            }
+        }
+
+        if let Some(peer_id) = peer_id(info) {
+            if let Some(other) = peer_ids.insert(peer_id, name) {
+                findings.push(Finding::new(
+                    Code::DuplicatePeerId,
+                    Some(name),
+                    format!("Peer ID '{peer_id}' is already used by node '{other}'"),
+                ));
+            }
+        }
+
+        match info {
+            TopologyInfo::Node(node) if node.service_type == ServiceType::Seed => seeds += 1,
+            TopologyInfo::Node(node) => match &node.uptime_service_backend {
+                Some(Some(backend)) if !uptime_service_backends.contains(&backend.as_str()) => {
+                    findings.push(Finding::new(
+                        Code::UnknownUptimeServiceBackend,
+                        Some(name),
+                        format!("Uptime service backend '{backend}' is not declared"),
+                    ));
+                }
+                None if node.service_type == ServiceType::BlockProducer
+                    && uptime_service_backends.len() > 1 =>
+                {
+                    findings.push(Finding::new(
+                        Code::AmbiguousUptimeServiceBackend,
+                        Some(name),
+                        format!(
+                            "No `uptime_service_backend` among {}, the node submits to none",
+                            uptime_service_backends.join(", ")
+                        ),
+                    ));
+                }
+                _ => {}
+            },
+            TopologyInfo::SnarkCoordinator(snark) => {
+                if let Some(finding) = check_snark_worker_fee(name, &snark.snark_worker_fee) {
+                    findings.push(finding);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // only meaningful once every node parsed
+    if nodes.len() == entries.len() {
+        if seeds == 0 {
+            findings.push(Finding::new(
+                Code::NoSeedNode,
+                None,
+                "At least one node with role 'Seed_node' is required".to_string(),
+            ));
+        }
+        if ports_in_range {
+            findings.extend(check_port_conflicts(&nodes));
+        }
+    }
+
+    findings.sort_by(|a, b| a.node.cmp(&b.node));
+    findings
+}
+
+/// Check a single entry's fields against its role and parse it
+fn validate_entry(name: &str, value: &Value, findings: &mut Vec<Finding>) -> Option<TopologyInfo> {
+    let role = match TopologyInfo::role(value) {
+        Ok(role) => role,
+        Err(e) => {
+            let code = match e {
+                EntryError::NotAnObject => Code::NotAnObject,
+                EntryError::MissingRole => Code::MissingRole,
+                _ => Code::UnknownRole,
+            };
+            findings.push(Finding::new(code, Some(name), e.to_string()));
+            return None;
+        }
+    };
+
+    let (required, optional) = TopologyInfo::fields(&role)?;
+    let object = value.as_object()?;
+
+    let missing: Vec<&str> = required
+        .iter()
+        .filter(|field| !object.contains_key(**field))
+        .copied()
+        .collect();
+    for field in &missing {
+        findings.push(Finding::new(
+            Code::MissingField,
+            Some(name),
+            format!("Missing field `{field}` required for role {role:?}"),
+        ));
+    }
+
+    for field in object.keys() {
+        if !required.contains(&field.as_str()) && !optional.contains(&field.as_str()) {
+            findings.push(Finding::new(
+                Code::UnknownField,
+                Some(name),
+                format!("Field `{field}` is not used for role {role:?}"),
+            ));
+        }
+    }
+
+    if !missing.is_empty() {
+        return None;
+    }
+
+    match TopologyInfo::from_value(value.clone()) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            findings.push(Finding::new(Code::InvalidField, Some(name), e.to_string()));
+            None
+        }
+    }
+}
+
+fn check_port_conflicts(nodes: &[(&str, TopologyInfo)]) -> Vec<Finding> {
+    let topology = Topology {
+        topology: nodes
+            .iter()
+            .map(|(name, info)| (name.to_string(), info.clone()))
+            .collect(),
+    };
+    // ports were checked to be in range, so their derived ports fit
+    let out_of_range = |e: io::Error| vec![Finding::new(Code::PortOutOfRange, None, e.to_string())];
+    let services = match topology.services(Path::new("peers.txt")) {
+        Ok(services) => services,
+        Err(e) => return out_of_range(e),
+    };
+    let conflicts = match port_manager::find_port_conflicts(&services) {
+        Ok(conflicts) => conflicts,
+        Err(e) => return out_of_range(e),
+    };
+
+    // one finding per pair of nodes, listing all ports they share
+    let mut shared: Vec<((&str, &str), Vec<String>)> = vec![];
+    for (port, first, second) in conflicts {
+        match shared.iter_mut().find(|(pair, _)| *pair == (first, second)) {
+            Some((_, ports)) => ports.push(port.to_string()),
+            None => shared.push(((first, second), vec![port.to_string()])),
+        }
+    }
+
+    shared
+        .into_iter()
+        .map(|((first, second), ports)| {
+            let ports = ports.join(", ");
+            let message = if first == second {
+                format!("Port(s) {ports} bound twice, check its explicit and derived ports")
+            } else {
+                format!("Port(s) {ports} already used by node '{first}'")
+            };
+            Finding::new(Code::PortConflict, Some(second), message)
+        })
+        .collect()
+}
+
+/// Ports set explicitly in a node's entry
+fn explicit_ports(name: &str, info: &TopologyInfo) -> impl Iterator<Item = u16> {
+    let service = info.to_service_config(name.to_string(), Path::new("peers.txt"), &[]);
+    [
+        service.client_port,
+        service.rest_port,
+        service.external_port,
+        service.metrics_port,
+        service.archive_port,
+        service.uptime_service_port,
+    ]
+    .into_iter()
+    .flatten()
+}
+
+fn check_snark_worker_fee(name: &str, fee: &str) -> Option<Finding> {
+    let e = amount_dsl_to_nanomina(fee).err()?;
+    if fee.parse::<f64>().is_ok() {
+        Some(Finding::new(
+            Code::LegacySnarkWorkerFee,
+            Some(name),
+            format!("Snark worker fee '{fee}' has no unit, prefer e.g. '{fee}mina'"),
+        ))
+    } else {
+        Some(Finding::new(
+            Code::InvalidSnarkWorkerFee,
+            Some(name),
+            e.to_string(),
+        ))
+    }
+}
+
+fn peer_id(info: &TopologyInfo) -> Option<&str> {
+    match info {
+        TopologyInfo::Archive(archive) => Some(&archive.libp2p_peerid),
+        TopologyInfo::SnarkCoordinator(snark) => Some(&snark.libp2p_peerid),
+        TopologyInfo::Node(node) => Some(&node.libp2p_peerid),
+        TopologyInfo::UptimeServiceBackend(_) => None,
+    }
+}
+
+/// Local files referenced by a node, with the field referencing them.
+/// Schema files given as URLs are fetched on create and not checked.
+fn referenced_files(info: &TopologyInfo) -> Vec<(&'static str, PathBuf)> {
+    match info {
+        TopologyInfo::Archive(archive) => {
+            let mut files = vec![("libp2p_keyfile", archive.libp2p_keyfile.clone())];
+            files.extend(
+                archive
+                    .schema_files
+                    .iter()
+                    .filter(|file| !file.to_string_lossy().contains("://"))
+                    .map(|file| ("schema_files", file.clone())),
+            );
+            files
+        }
+        TopologyInfo::SnarkCoordinator(snark) => {
+            vec![("libp2p_keyfile", snark.libp2p_keyfile.clone())]
+        }
+        TopologyInfo::Node(node) => {
+            let mut files = vec![("libp2p_keyfile", node.libp2p_keyfile.clone())];
+            if let Some(privkey_path) = &node.privkey_path {
+                files.push(("privkey_path", privkey_path.clone()));
+            }
+            files
+        }
+        TopologyInfo::UptimeServiceBackend(uptime) => {
+            let mut files = vec![
+                ("app_config_path", uptime.app_config_path.clone()),
+                ("minasheets_path", uptime.minasheets_path.clone()),
+            ];
+            files.extend(
+                uptime
+                    .other_config_files
+                    .iter()
+                    .flatten()
+                    .map(|file| ("other_config_files", file.clone())),
+            );
+            files
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempdir::TempDir;
+
+    fn seed(peer_id: &str) -> Value {
+        json!({
+            "pk": "pk", "sk": "sk", "role": "Seed_node", "privkey_path": null,
+            "libp2p_pass": "pass",
+            "libp2p_keyfile": "./tests/data/small_network/libp2p_keys/seed-0.json",
+            "libp2p_peerid": peer_id
+        })
+    }
+
+    fn codes(findings: &[Finding]) -> Vec<(Code, Option<&str>)> {
+        findings
+            .iter()
+            .map(|finding| (finding.code, finding.node.as_deref()))
+            .collect()
+    }
+
+    fn entries(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_repo_topologies_have_no_errors() {
+        for network in ["small_network", "large_network"] {
+            let path = PathBuf::from(format!("./tests/data/{network}/topology.json"));
+            let findings = validate(&path);
+            assert!(
+                findings
+                    .iter()
+                    .all(|finding| finding.severity == Severity::Warning),
+                "{network}: {findings:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_unreadable_and_invalid_files() {
+        let tempdir =
+            TempDir::new("test_invalid_topology").expect("Cannot create temporary directory");
+        let path = tempdir.path().join("topology.json");
+
+        assert_eq!(codes(&validate(&path)), [(Code::ReadError, None)]);
+
+        std::fs::write(&path, "[1, 2]").unwrap();
+        assert_eq!(codes(&validate(&path)), [(Code::InvalidJson, None)]);
+    }
+
+    #[test]
+    fn test_entry_fields() {
+        let findings = validate_entries(&entries(json!({
+            "seed": seed("peer-0"),
+            "no-role": { "pk": "pk" },
+            "worker": { "role": "Snark_worker" },
+            "bp": {
+                "pk": "pk", "role": "Block_producer", "colour": "blue",
+                "libp2p_pass": "pass", "libp2p_keyfile": "keyfile", "libp2p_peerid": "peer-1"
+            },
+            "bad-type": {
+                "pk": "pk", "sk": "sk", "role": "Snark_coordinator", "worker_nodes": "two",
+                "snark_worker_fee": "1mina",
+                "libp2p_pass": "pass", "libp2p_keyfile": "keyfile", "libp2p_peerid": "peer-2"
+            }
+        })));
+
+        assert_eq!(
+            codes(&findings),
+            [
+                (Code::InvalidField, Some("bad-type")),
+                (Code::MissingField, Some("bp")),
+                (Code::UnknownField, Some("bp")),
+                (Code::MissingRole, Some("no-role")),
+                (Code::UnknownRole, Some("worker")),
+            ]
+        );
+        assert_eq!(
+            findings[1].message,
+            "Missing field `sk` required for role BlockProducer"
+        );
+    }
+
+    #[test]
+    fn test_cross_node_checks() {
+        let archive = |peer_id: &str| {
+            json!({
+                "pk": "pk", "sk": "sk", "role": "Archive_node",
+                "schema_files": ["https://example.com/create_schema.sql"],
+                "libp2p_pass": "pass", "libp2p_keyfile": "missing.json", "libp2p_peerid": peer_id
+            })
+        };
+        let findings = validate_entries(&entries(json!({
+            "archive-0": archive("peer-0"),
+            "archive-1": archive("peer-0"),
+        })));
+
+        assert_eq!(
+            codes(&findings),
+            [
+                (Code::NoSeedNode, None),
+                (Code::FileNotFound, Some("archive-0")),
+                (Code::FileNotFound, Some("archive-1")),
+                (Code::DuplicatePeerId, Some("archive-1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_uptime_service_backend() {
+        let config = "./tests/data/uptime_service_network/uptime_service_config_test";
+        let block_producer = |peer_id: &str, backend: Value| {
+            json!({
+                "pk": "pk", "sk": "sk", "role": "Block_producer",
+                "uptime_service_backend": backend,
+                "libp2p_pass": "pass",
+                "libp2p_keyfile": "./tests/data/small_network/libp2p_keys/seed-0.json",
+                "libp2p_peerid": peer_id
+            })
+        };
+        let uptime = |port: u16| {
+            json!({
+                "role": "Uptime_service_backend", "port": port,
+                "app_config_path": format!("{config}/app_config.json"),
+                "minasheets_path": format!("{config}/minasheets.json")
+            })
+        };
+        let mut without_backend = block_producer("peer-4", json!(null));
+        without_backend
+            .as_object_mut()
+            .unwrap()
+            .remove("uptime_service_backend");
+        let mut topology = json!({
+            "seed": seed("peer-0"),
+            "uptime": uptime(8090),
+            "bp-0": block_producer("peer-1", json!("uptime")),
+            "bp-1": block_producer("peer-2", json!(null)),
+            "bp-2": block_producer("peer-3", json!("seed")),
+            "bp-3": without_backend,
+        });
+
+        let findings = validate_entries(&entries(topology.clone()));
+        assert_eq!(
+            codes(&findings),
+            [(Code::UnknownUptimeServiceBackend, Some("bp-2"))]
+        );
+
+        topology["uptime-2"] = uptime(8091);
+        let findings = validate_entries(&entries(topology));
+        assert_eq!(
+            codes(&findings),
+            [
+                (Code::UnknownUptimeServiceBackend, Some("bp-2")),
+                (Code::AmbiguousUptimeServiceBackend, Some("bp-3")),
+            ]
+        );
+        assert_eq!(findings[1].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_port_conflicts() {
+        let with_ports = |peer_id: &str, ports: Value| {
+            let mut node = seed(peer_id);
+            node.as_object_mut()
+                .unwrap()
+                .extend(ports.as_object().unwrap().clone());
+            node
+        };
+        let findings = validate_entries(&entries(json!({
+            "seed-0": with_ports("peer-0", json!({ "client_port": 8000 })),
+            "seed-1": with_ports("peer-1", json!({ "client_port": 8003 })),
+            "seed-2": with_ports("peer-2", json!({ "rest_port": 9000 })),
+        })));
+
+        assert_eq!(codes(&findings), [(Code::PortConflict, Some("seed-1"))]);
+        assert_eq!(
+            findings[0].message,
+            "Port(s) 8003, 8004 already used by node 'seed-0'"
+        );
+
+        // ports without room for their derived ports are reported instead of conflicts
+        let findings = validate_entries(&entries(json!({
+            "seed-0": with_ports("peer-0", json!({ "client_port": 65532 })),
+            "seed-1": with_ports("peer-1", json!({ "client_port": 65531 })),
+            "seed-2": with_ports("peer-2", json!({ "metrics_port": 65535 })),
+        })));
+        assert_eq!(
+            codes(&findings),
+            [
+                (Code::PortOutOfRange, Some("seed-0")),
+                (Code::PortOutOfRange, Some("seed-2"))
+            ]
+        );
+        assert_eq!(findings[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_snark_worker_fee() {
+        assert_eq!(check_snark_worker_fee("snark", "0.01mina"), None);
+        assert_eq!(
+            check_snark_worker_fee("snark", "0.01").map(|finding| finding.code),
+            Some(Code::LegacySnarkWorkerFee)
+        );
+        assert_eq!(
+            check_snark_worker_fee("snark", "lots").map(|finding| finding.code),
+            Some(Code::InvalidSnarkWorkerFee)
+        );
+    }
+}
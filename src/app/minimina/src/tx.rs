@@ -0,0 +1,264 @@
+//! # Tx Module
+//!
+//! Sends payments and delegations between the accounts of a network through a node's graphql.
+//! Accounts are named after their key pairs in `network-keypairs` (see
+//! [`DirectoryManager::network_keypair`]), which are imported into the node and unlocked
+//! with [`MINA_PRIVKEY_PASS`] before sending.
+
+use crate::{
+    backend::Backend,
+    directory_manager::{DirectoryManager, NETWORK_KEYPAIRS},
+    graphql::GraphQl,
+    keys::MINA_PRIVKEY_PASS,
+    output::tx,
+};
+use log::info;
+use minimina::{
+    amounts::nanomina_to_decimal_mina,
+    graphql_client::{
+        types::{Delegation, Payment, TransactionStatus},
+        Client,
+    },
+};
+use std::{
+    io::{self, Result},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Interval between two polls of a transaction's status
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    /// Payment of an amount in nanomina
+    Payment { amount: u64 },
+    /// Delegation of the sender's stake
+    Delegation,
+}
+
+/// Transaction from account `from` to account or public key `to`, fee in nanomina
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub kind: Kind,
+    pub from: String,
+    pub to: String,
+    pub fee: u64,
+    pub memo: Option<String>,
+}
+
+/// Sends transactions on network `network_id`
+pub struct Sender<'a> {
+    pub backend: &'a dyn Backend,
+    pub directory_manager: &'a DirectoryManager,
+    pub graphql: &'a GraphQl,
+    pub network_id: &'a str,
+}
+
+impl Sender<'_> {
+    /// Sends `transaction` through `node`, by default the sender's node if it has
+    /// a graphql endpoint or else the first node which has one.
+    /// With `wait`, waits at most that long for the transaction to be included.
+    pub fn send(
+        &self,
+        node: Option<&str>,
+        transaction: &Transaction,
+        wait: Option<Duration>,
+    ) -> Result<tx::Sent> {
+        let (account_file, sender) = self
+            .directory_manager
+            .network_keypair(self.network_id, &transaction.from)?;
+        let receiver = self.public_key(&transaction.to)?;
+        let (node_id, endpoint) = self.node(node, &transaction.from)?;
+        let client = Client::new(&endpoint);
+
+        let account_path = self
+            .backend
+            .service_path(&Path::new(NETWORK_KEYPAIRS).join(&account_file));
+        client.import_account(&account_path, MINA_PRIVKEY_PASS)?;
+        client.unlock_account(&sender, MINA_PRIVKEY_PASS)?;
+
+        let (kind, amount, sent) = match transaction.kind {
+            Kind::Payment { amount } => {
+                let payment = Payment {
+                    from: sender,
+                    to: receiver,
+                    amount,
+                    fee: transaction.fee,
+                    nonce: None,
+                    memo: transaction.memo.clone(),
+                };
+                ("payment", Some(amount), client.send_payment(&payment)?)
+            }
+            Kind::Delegation => {
+                let delegation = Delegation {
+                    from: sender,
+                    to: receiver,
+                    fee: transaction.fee,
+                    nonce: None,
+                    memo: transaction.memo.clone(),
+                };
+                ("delegation", None, client.send_delegation(&delegation)?)
+            }
+        };
+        info!(
+            "Sent {kind} '{}' from '{}' to '{}' through '{node_id}'.",
+            sent.hash, transaction.from, transaction.to
+        );
+
+        let status = match wait {
+            Some(timeout) => wait_for_inclusion(&client, &sent.id, timeout)?,
+            None => client.transaction_status(&sent.id)?,
+        };
+        Ok(tx::Sent {
+            network_id: self.network_id.to_string(),
+            node_id,
+            kind: kind.to_string(),
+            id: sent.id,
+            hash: sent.hash,
+            from: transaction.from.clone(),
+            to: transaction.to.clone(),
+            amount: amount.map(nanomina_to_decimal_mina),
+            fee: nanomina_to_decimal_mina(transaction.fee),
+            nonce: sent.nonce,
+            status,
+        })
+    }
+
+    /// Public key of account `name`, or `name` itself if it is a public key
+    fn public_key(&self, name: &str) -> Result<String> {
+        if name.starts_with("B62") {
+            return Ok(name.to_string());
+        }
+        let (_, public_key) = self
+            .directory_manager
+            .network_keypair(self.network_id, name)?;
+        Ok(public_key)
+    }
+
+    /// Id and graphql endpoint of the node to send a transaction of `from` through
+    fn node(&self, node: Option<&str>, from: &str) -> Result<(String, String)> {
+        let mut endpoints = self.graphql.endpoints(self.network_id)?;
+        let node_id = match node {
+            Some(node) => node.to_string(),
+            None if endpoints.contains_key(from) => from.to_string(),
+            None => match endpoints.keys().next() {
+                Some(node) => node.clone(),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!(
+                            "No node has a graphql endpoint in network '{}'",
+                            self.network_id
+                        ),
+                    ))
+                }
+            },
+        };
+        match endpoints.remove(&node_id) {
+            Some(endpoint) => Ok((node_id, endpoint)),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "Node '{node_id}' has no graphql endpoint in network '{}'",
+                    self.network_id
+                ),
+            )),
+        }
+    }
+}
+
+/// Polls the status of transaction `id` until it is included or `timeout` expires
+fn wait_for_inclusion(client: &Client, id: &str, timeout: Duration) -> Result<TransactionStatus> {
+    let start = Instant::now();
+    loop {
+        let status = client.transaction_status(id)?;
+        let elapsed = start.elapsed();
+        if status == TransactionStatus::Included || elapsed >= timeout {
+            return Ok(status);
+        }
+        thread::sleep(POLL_INTERVAL.min(timeout - elapsed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::mock::MockBackend, docker::manager::ContainerState};
+    use serde_json::json;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_resolve_accounts_and_nodes() {
+        let tempdir = TempDir::new("test_resolve_accounts_and_nodes")
+            .expect("Cannot create temporary directory");
+        let directory_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        directory_manager.generate_dir_structure("test").unwrap();
+        let keypairs = directory_manager
+            .network_path("test")
+            .join(NETWORK_KEYPAIRS);
+        fs::create_dir_all(&keypairs).unwrap();
+        fs::write(keypairs.join("whale-0"), "{}").unwrap();
+        fs::write(keypairs.join("whale-0.pub"), "B62qwhale0\n").unwrap();
+        let info = json!({
+            "network_id": "test",
+            "nodes": {
+                "mina-bp-1": {
+                    "graphql_uri": "http://localhost:4001/graphql",
+                    "private_key": null,
+                    "node_type": "Block_producer"
+                },
+                "mina-seed-1": {
+                    "graphql_uri": "http://localhost:3101/graphql",
+                    "private_key": null,
+                    "node_type": "Seed_node"
+                },
+                "mina-snark-worker-1": {
+                    "graphql_uri": null,
+                    "private_key": null,
+                    "node_type": "Snark_worker"
+                }
+            }
+        });
+        fs::write(
+            directory_manager.network_file_path("test"),
+            info.to_string(),
+        )
+        .unwrap();
+
+        let backend = MockBackend::with_state("mina-bp-1", ContainerState::Running);
+        let graphql = GraphQl::new(directory_manager.clone());
+        let sender = Sender {
+            backend: &backend,
+            directory_manager: &directory_manager,
+            graphql: &graphql,
+            network_id: "test",
+        };
+
+        assert_eq!(sender.public_key("whale-0").unwrap(), "B62qwhale0");
+        assert_eq!(sender.public_key("B62qsomeone").unwrap(), "B62qsomeone");
+        assert!(sender.public_key("whale-1").is_err());
+
+        let (node_id, endpoint) = sender.node(None, "mina-bp-1").unwrap();
+        assert_eq!(node_id, "mina-bp-1");
+        assert_eq!(endpoint, "http://localhost:4001/graphql");
+        let (node_id, _) = sender.node(None, "whale-0").unwrap();
+        assert_eq!(node_id, "mina-bp-1");
+        let (node_id, _) = sender.node(Some("mina-seed-1"), "mina-bp-1").unwrap();
+        assert_eq!(node_id, "mina-seed-1");
+        let error = sender.node(Some("mina-snark-worker-1"), "whale-0");
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        let transaction = Transaction {
+            kind: Kind::Delegation,
+            from: "whale-1".to_string(),
+            to: "mina-bp-1".to_string(),
+            fee: 10_000_000,
+            memo: None,
+        };
+        assert!(sender.send(None, &transaction, None).is_err());
+        assert!(backend.calls().is_empty());
+    }
+}
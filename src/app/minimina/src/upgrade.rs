@@ -0,0 +1,330 @@
+//! # Upgrade Module
+//!
+//! Rolling upgrade of the images (docker mode) or binaries (native mode) of a network's
+//! services, to rehearse release rollouts and run mixed-version networks.
+//!
+//! Services are upgraded one batch at a time: their configs are updated in `services.json`,
+//! the backend recreates them with their state (see [`Backend::upgrade_service`]), and with
+//! `wait_synced` the next batch waits until the upgraded nodes are synced again.
+
+use crate::{
+    backend::Backend,
+    commands,
+    directory_manager::DirectoryManager,
+    graphql::GraphQl,
+    output::network,
+    service::{self, Role, ServiceConfig, ServiceType},
+    wait::{self, Condition},
+};
+use log::info;
+use std::{
+    io::{self, Result},
+    path::PathBuf,
+    time::Duration,
+};
+
+/// Images or binaries services are moved to, those not set are kept
+pub struct Release {
+    /// Docker image of the mina daemons
+    pub image: Option<String>,
+    /// Docker image of the archive services
+    pub archive_image: Option<String>,
+    /// Directory of the mina binaries in native mode
+    pub bin_path: Option<PathBuf>,
+}
+
+impl Release {
+    /// Updates the image or binaries of `service`
+    pub fn apply(&self, service: &mut ServiceConfig) {
+        if let Some(image) = &self.image {
+            service.docker_image = Some(image.clone());
+        }
+        if let Some(archive_image) = &self.archive_image {
+            if service.service_type == ServiceType::ArchiveNode {
+                service.archive_docker_image = Some(archive_image.clone());
+            }
+        }
+        if let Some(bin_path) = &self.bin_path {
+            service.bin_path = Some(bin_path.clone());
+        }
+    }
+
+    /// Whether the release only moves archive nodes, the only services with an archive image
+    fn archive_only(&self) -> bool {
+        self.archive_image.is_some() && self.image.is_none() && self.bin_path.is_none()
+    }
+}
+
+pub struct Upgrade {
+    pub release: Release,
+    /// Roles of the services to upgrade, all daemons when empty
+    pub roles: Vec<Role>,
+    /// Services to upgrade instead of those of `roles`
+    pub nodes: Vec<String>,
+    /// Number of services upgraded at once
+    pub batch: usize,
+    /// How long to wait for the upgraded nodes to be synced before the next batch
+    pub wait_synced: Option<Duration>,
+}
+
+/// Upgrades the services of network `network_id`
+pub struct Upgrader<'a> {
+    pub backend: &'a dyn Backend,
+    pub directory_manager: &'a DirectoryManager,
+    pub graphql: &'a GraphQl,
+    pub network_id: &'a str,
+}
+
+impl Upgrader<'_> {
+    pub fn run(&self, upgrade: &Upgrade) -> Result<network::Upgrade> {
+        let mut services = self.directory_manager.get_services_info(self.network_id)?;
+        let mut targets = service::select_services(&services, &upgrade.roles, &upgrade.nodes)?;
+        if upgrade.release.archive_only() {
+            targets.retain(|name| {
+                services.iter().any(|service| {
+                    &service.service_name == name
+                        && service.service_type == ServiceType::ArchiveNode
+                })
+            });
+            if targets.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "No archive node to move to the archive image",
+                ));
+            }
+        }
+        let batches: Vec<Vec<String>> = targets
+            .chunks(upgrade.batch.max(1))
+            .map(<[String]>::to_vec)
+            .collect();
+
+        let mut upgraded = network::Upgrade {
+            network_id: self.network_id.to_string(),
+            image: upgrade.release.image.clone(),
+            archive_image: upgrade.release.archive_image.clone(),
+            bin_path: upgrade
+                .release
+                .bin_path
+                .as_ref()
+                .map(|p| p.display().to_string()),
+            batches: vec![],
+        };
+        for (i, batch) in batches.into_iter().enumerate() {
+            info!(
+                "Upgrading batch {} of network '{}': {batch:?}.",
+                i + 1,
+                self.network_id
+            );
+            for service in services
+                .iter_mut()
+                .filter(|service| batch.contains(&service.service_name))
+            {
+                upgrade.release.apply(service);
+            }
+            // saved first, so that services.json matches what runs if the batch fails
+            self.directory_manager
+                .save_services_info(self.network_id, &services)?;
+            for node in &batch {
+                let service = services
+                    .iter()
+                    .find(|service| &service.service_name == node)
+                    .expect("Targets are services of the network");
+                self.backend.upgrade_service(service, &services)?;
+            }
+            let nodes: Vec<&str> = batch.iter().map(String::as_str).collect();
+            commands::reapply_faults(
+                self.backend,
+                self.directory_manager,
+                self.network_id,
+                &nodes,
+            )?;
+            if let Some(timeout) = upgrade.wait_synced {
+                self.wait_synced(&batch, timeout)?;
+            }
+            upgraded.batches.push(batch);
+        }
+        Ok(upgraded)
+    }
+
+    /// Waits until the nodes of `batch` which have a graphql endpoint are synced
+    fn wait_synced(&self, batch: &[String], timeout: Duration) -> Result<()> {
+        let endpoints = self.graphql.endpoints(self.network_id)?;
+        let nodes: Vec<String> = batch
+            .iter()
+            .filter(|node| endpoints.contains_key(*node))
+            .cloned()
+            .collect();
+        if nodes.is_empty() {
+            return Ok(());
+        }
+        let report = wait::wait(
+            self.graphql,
+            self.network_id,
+            &nodes,
+            &Condition::Synced,
+            timeout,
+        )?;
+        if !report.met {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "Nodes {nodes:?} were not synced within {}s, stopping the upgrade",
+                    timeout.as_secs()
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::mock::{service, MockBackend},
+        faults::Faults,
+    };
+    use serde_json::json;
+    use tempdir::TempDir;
+
+    fn default_upgrade() -> Upgrade {
+        Upgrade {
+            release: Release {
+                image: Some("mina-daemon:2".to_string()),
+                archive_image: None,
+                bin_path: None,
+            },
+            roles: vec![],
+            nodes: vec![],
+            batch: 2,
+            wait_synced: None,
+        }
+    }
+
+    #[test]
+    fn test_rolling_upgrade() {
+        let tempdir = TempDir::new("test_upgrade").expect("Cannot create temporary directory");
+        let directory_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        directory_manager.generate_dir_structure("test").unwrap();
+        let mut archive = service("mina-archive", ServiceType::ArchiveNode);
+        archive.archive_docker_image = Some("mina-archive:1".to_string());
+        let services = vec![
+            service("mina-seed-1", ServiceType::Seed),
+            service("mina-bp-1", ServiceType::BlockProducer),
+            service("mina-bp-2", ServiceType::BlockProducer),
+            archive,
+        ];
+        directory_manager
+            .save_services_info("test", &services)
+            .unwrap();
+        let faults = Faults {
+            groups: vec![vec!["mina-bp-2".into()], vec!["mina-archive".into()]],
+            links: vec![],
+        };
+        directory_manager.save_faults("test", &faults).unwrap();
+        let backend = MockBackend::default();
+        let graphql = GraphQl::new(directory_manager.clone());
+        let upgrader = Upgrader {
+            backend: &backend,
+            directory_manager: &directory_manager,
+            graphql: &graphql,
+            network_id: "test",
+        };
+
+        let mut upgrade = default_upgrade();
+        upgrade.release.archive_image = Some("mina-archive:2".to_string());
+        let upgraded = upgrader.run(&upgrade).unwrap();
+        assert_eq!(
+            upgraded.batches,
+            [
+                vec!["mina-seed-1", "mina-bp-1"],
+                vec!["mina-bp-2", "mina-archive"]
+            ]
+        );
+        assert_eq!(
+            backend.calls(),
+            [
+                "upgrade_service mina-seed-1 mina-daemon:2 of 4",
+                "upgrade_service mina-bp-1 mina-daemon:2 of 4",
+                "upgrade_service mina-bp-2 mina-daemon:2 of 4",
+                "upgrade_service mina-archive mina-daemon:2 of 4",
+                "apply_faults 2 0 mina-bp-2,mina-archive",
+            ]
+        );
+        let saved = directory_manager.get_services_info("test").unwrap();
+        assert!(saved
+            .iter()
+            .all(|s| s.docker_image.as_deref() == Some("mina-daemon:2")));
+        assert_eq!(
+            saved[3].archive_docker_image.as_deref(),
+            Some("mina-archive:2")
+        );
+        assert_eq!(saved[0].archive_docker_image, None);
+
+        // the upgrade stops at the first batch whose nodes do not sync
+        let info = json!({
+            "network_id": "test",
+            "nodes": {
+                "mina-seed-1": {
+                    "graphql_uri": "http://127.0.0.1:1/graphql",
+                    "private_key": null,
+                    "node_type": "Seed_node"
+                }
+            }
+        });
+        std::fs::write(
+            directory_manager.network_file_path("test"),
+            info.to_string(),
+        )
+        .unwrap();
+        let mut upgrade = default_upgrade();
+        upgrade.release.image = Some("mina-daemon:3".to_string());
+        upgrade.wait_synced = Some(Duration::ZERO);
+        let err = upgrader.run(&upgrade).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        let saved = directory_manager.get_services_info("test").unwrap();
+        assert_eq!(saved[1].docker_image.as_deref(), Some("mina-daemon:3"));
+        assert_eq!(saved[2].docker_image.as_deref(), Some("mina-daemon:2"));
+    }
+
+    #[test]
+    fn test_archive_only_upgrade() {
+        let tempdir = TempDir::new("test_upgrade").expect("Cannot create temporary directory");
+        let directory_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        directory_manager.generate_dir_structure("test").unwrap();
+        let services = vec![
+            service("mina-seed-1", ServiceType::Seed),
+            service("mina-bp-1", ServiceType::BlockProducer),
+            service("mina-archive-1", ServiceType::ArchiveNode),
+            service("mina-archive-2", ServiceType::ArchiveNode),
+        ];
+        directory_manager
+            .save_services_info("test", &services)
+            .unwrap();
+        let backend = MockBackend::default();
+        let graphql = GraphQl::new(directory_manager.clone());
+        let upgrader = Upgrader {
+            backend: &backend,
+            directory_manager: &directory_manager,
+            graphql: &graphql,
+            network_id: "test",
+        };
+
+        let mut upgrade = default_upgrade();
+        upgrade.release.image = None;
+        upgrade.release.archive_image = Some("mina-archive:2".to_string());
+        let upgraded = upgrader.run(&upgrade).unwrap();
+        assert_eq!(upgraded.batches, [vec!["mina-archive-1", "mina-archive-2"]]);
+        assert_eq!(
+            backend.calls(),
+            [
+                "upgrade_service mina-archive-1  of 4",
+                "upgrade_service mina-archive-2  of 4",
+            ]
+        );
+
+        upgrade.nodes = vec!["mina-bp-1".to_string()];
+        let err = upgrader.run(&upgrade).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
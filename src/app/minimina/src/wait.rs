@@ -0,0 +1,244 @@
+//! # Wait Module
+//!
+//! Polls the graphql endpoints of a network's nodes until all of them meet a [`Condition`]
+//! on their sync status or best tip, reporting the last polled state as [`network::Wait`].
+
+use crate::{
+    graphql::GraphQl,
+    output::network::{self, NodeTip},
+};
+use log::info;
+use minimina::graphql_client::{types::SyncStatus, Client};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Result},
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Interval between two polls of the nodes' graphql endpoints
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Condition a node waits for: `synced`, `height>=N`, `epoch>=E` or `slot>=S`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Condition {
+    Synced,
+    Height(u64),
+    Epoch(u64),
+    /// Global slot since genesis
+    Slot(u64),
+}
+
+impl Condition {
+    pub fn is_met(&self, tip: &NodeTip) -> bool {
+        let at_least = |value: Option<u64>, min: u64| value.is_some_and(|value| value >= min);
+        match self {
+            Condition::Synced => tip.sync_status == Some(SyncStatus::Synced),
+            Condition::Height(height) => at_least(tip.height, *height),
+            Condition::Epoch(epoch) => at_least(tip.epoch, *epoch),
+            Condition::Slot(slot) => at_least(tip.slot, *slot),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid =
+            || format!("Invalid condition '{s}', expected synced, height>=N, epoch>=E or slot>=S");
+        if s.trim() == "synced" {
+            return Ok(Condition::Synced);
+        }
+        let (key, value) = s.split_once(">=").ok_or_else(invalid)?;
+        let value = value.trim().parse().map_err(|_| invalid())?;
+        match key.trim() {
+            "height" => Ok(Condition::Height(value)),
+            "epoch" => Ok(Condition::Epoch(value)),
+            "slot" => Ok(Condition::Slot(value)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for Condition {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Synced => write!(f, "synced"),
+            Condition::Height(height) => write!(f, "height>={height}"),
+            Condition::Epoch(epoch) => write!(f, "epoch>={epoch}"),
+            Condition::Slot(slot) => write!(f, "slot>={slot}"),
+        }
+    }
+}
+
+/// Waits until `nodes` of the network, or all nodes with a graphql endpoint
+/// when empty, meet `until` or `timeout` expires.
+/// Nodes which cannot be polled yet, e.g. still starting, do not meet the condition.
+pub fn wait(
+    graphql: &GraphQl,
+    network_id: &str,
+    nodes: &[String],
+    until: &Condition,
+    timeout: Duration,
+) -> Result<network::Wait> {
+    let endpoints = graphql.node_endpoints(network_id, nodes)?;
+    let clients: BTreeMap<String, Client> = endpoints
+        .into_iter()
+        .map(|(node, endpoint)| (node, Client::new(&endpoint)))
+        .collect();
+
+    info!("Waiting for {until} on network '{network_id}'.");
+    let start = Instant::now();
+    loop {
+        let tips: BTreeMap<String, NodeTip> = clients
+            .iter()
+            .map(|(node, client)| {
+                let mut tip = poll(client).unwrap_or_else(|e| NodeTip {
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                });
+                tip.met = until.is_met(&tip);
+                (node.clone(), tip)
+            })
+            .collect();
+
+        let met = tips.values().all(|tip| tip.met);
+        let elapsed = start.elapsed();
+        if met || elapsed >= timeout {
+            return Ok(network::Wait {
+                network_id: network_id.to_string(),
+                until: until.to_string(),
+                met,
+                elapsed_secs: elapsed.as_secs(),
+                nodes: tips,
+            });
+        }
+        let waiting: Vec<&String> = tips
+            .iter()
+            .filter(|(_, t)| !t.met)
+            .map(|(n, _)| n)
+            .collect();
+        info!("Still waiting for {until} on {waiting:?}.");
+        thread::sleep(POLL_INTERVAL.min(timeout - elapsed));
+    }
+}
+
+/// Polls the sync status and best tip of a node
+fn poll(client: &Client) -> io::Result<NodeTip> {
+    let sync_status = client.sync_status()?;
+    // the best chain is empty until the node has bootstrapped
+    let best_tip = client.best_chain(1)?.pop();
+    let consensus_state = best_tip.map(|block| block.protocol_state.consensus_state);
+    Ok(NodeTip {
+        met: false,
+        sync_status: Some(sync_status),
+        height: consensus_state.as_ref().map(|state| state.block_height),
+        epoch: consensus_state.as_ref().map(|state| state.epoch),
+        slot: consensus_state
+            .as_ref()
+            .map(|state| state.slot_since_genesis),
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::directory_manager::DirectoryManager;
+    use serde_json::json;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_parse_condition() {
+        assert_eq!("synced".parse(), Ok(Condition::Synced));
+        assert_eq!("height>=10".parse(), Ok(Condition::Height(10)));
+        assert_eq!("epoch >= 2".parse(), Ok(Condition::Epoch(2)));
+        assert_eq!("slot>=30".parse(), Ok(Condition::Slot(30)));
+        assert!("height>10".parse::<Condition>().is_err());
+        assert!("length>=10".parse::<Condition>().is_err());
+        assert!("slot>=-1".parse::<Condition>().is_err());
+
+        for condition in ["synced", "height>=10", "epoch>=2", "slot>=30"] {
+            assert_eq!(
+                condition.parse::<Condition>().unwrap().to_string(),
+                condition
+            );
+        }
+    }
+
+    #[test]
+    fn test_condition_is_met() {
+        let tip = NodeTip {
+            sync_status: Some(SyncStatus::Synced),
+            height: Some(12),
+            epoch: Some(1),
+            slot: Some(30),
+            ..Default::default()
+        };
+        assert!(Condition::Synced.is_met(&tip));
+        assert!(Condition::Height(12).is_met(&tip));
+        assert!(!Condition::Height(13).is_met(&tip));
+        assert!(Condition::Epoch(1).is_met(&tip));
+        assert!(!Condition::Slot(31).is_met(&tip));
+
+        let bootstrapping = NodeTip {
+            sync_status: Some(SyncStatus::Bootstrap),
+            ..Default::default()
+        };
+        assert!(!Condition::Synced.is_met(&bootstrapping));
+        assert!(!Condition::Height(0).is_met(&bootstrapping));
+    }
+
+    #[test]
+    fn test_wait_timeout() {
+        let tempdir = TempDir::new("test_wait_timeout").expect("Cannot create temporary directory");
+        let directory_manager = DirectoryManager::_new_with_base_path(tempdir.path().into());
+        directory_manager.create_network_directory("test").unwrap();
+        let info = json!({
+            "network_id": "test",
+            "nodes": {
+                "mina-bp-1": {
+                    "graphql_uri": "http://127.0.0.1:1/graphql",
+                    "private_key": null,
+                    "node_type": "Block_producer"
+                },
+                "mina-snark-worker-1": {
+                    "graphql_uri": null,
+                    "private_key": null,
+                    "node_type": "Snark_worker"
+                }
+            }
+        });
+        std::fs::write(
+            directory_manager.network_file_path("test"),
+            info.to_string(),
+        )
+        .unwrap();
+        let graphql = GraphQl::new(directory_manager);
+
+        let report = wait(&graphql, "test", &[], &Condition::Synced, Duration::ZERO).unwrap();
+        assert!(!report.met);
+        assert_eq!(report.until, "synced");
+        let tip = &report.nodes["mina-bp-1"];
+        assert!(!tip.met);
+        assert!(tip.error.is_some());
+        assert_eq!(report.nodes.len(), 1);
+
+        let nodes = ["mina-snark-worker-1".to_string()];
+        let error = wait(&graphql, "test", &nodes, &Condition::Synced, Duration::ZERO);
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}